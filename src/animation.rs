@@ -0,0 +1,144 @@
+//! Animated SVG from a sequence of frames (e.g. an animated GIF).
+
+use crate::enhanced_vectorizer::{
+    generate_enhanced_svg_with_options, provenance_comment, svg_root_tag, vectorize_enhanced, EnhancedOptions,
+};
+use crate::image_processor::ImageData;
+use anyhow::Result;
+use std::time::Duration;
+
+/// Delay used for frames that specify none, as browsers do for GIFs.
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// Vectorize each frame and return one SVG that cycles through them forever.
+///
+/// Every frame becomes a `<g class="frame">` whose SMIL `<animate>` toggles
+/// `display`, so it shows for its delay; a zero delay counts as 100 ms.
+/// Without SMIL support only the first frame shows. All frames must share
+/// one size.
+pub fn convert_animated(frames: &[(ImageData, Duration)], options: &EnhancedOptions) -> Result<String> {
+    options.validate()?;
+    let Some((first, _)) = frames.first() else {
+        return Err(anyhow::anyhow!("an animation needs at least one frame"));
+    };
+    let (width, height) = (first.width, first.height);
+    if let Some((frame, _)) = frames.iter().find(|(f, _)| (f.width, f.height) != (width, height)) {
+        return Err(anyhow::anyhow!(
+            "frame is {}x{} but the animation is {}x{}",
+            frame.width, frame.height, width, height
+        ));
+    }
+
+    // Each frame is rendered on its own and only its body kept; document-level
+    // output (HTML wrapper, provenance, legend, shared defs) is written once or not at all
+    let frame_options = EnhancedOptions {
+        wrap_html: false,
+        embed_provenance: false,
+        emit_legend: false,
+        dedup_shapes: false,
+        ..options.clone()
+    };
+    let delays: Vec<f64> = frames
+        .iter()
+        .map(|(_, delay)| if delay.is_zero() { DEFAULT_FRAME_DELAY } else { *delay }.as_secs_f64())
+        .collect();
+    let total: f64 = delays.iter().sum();
+
+    let mut root_attributes = Vec::new();
+    if let Some(value) = &options.preserve_aspect_ratio {
+        root_attributes.push(("preserveAspectRatio".to_string(), value.clone()));
+    }
+    root_attributes.extend(options.root_attributes.iter().cloned());
+    let mut svg = svg_root_tag(width, height, &root_attributes);
+    if options.embed_provenance {
+        svg.push_str(&provenance_comment(options));
+    }
+
+    let mut start = 0.0;
+    for (i, ((image, _), delay)) in frames.iter().zip(&delays).enumerate() {
+        let data = vectorize_enhanced(image, &frame_options)?;
+        let frame_svg = generate_enhanced_svg_with_options(&data, &frame_options);
+        let end = start + delay;
+        // Discrete keyframes: hidden, shown from `start`, hidden again from `end`
+        let mut values = Vec::new();
+        let mut key_times = Vec::new();
+        if i > 0 {
+            values.push("none");
+            key_times.push(0.0);
+        }
+        values.push("inline");
+        key_times.push(start / total);
+        if i + 1 < frames.len() {
+            values.push("none");
+            key_times.push(end / total);
+        }
+        let key_times: Vec<String> = key_times.iter().map(|t| format!("{:.4}", t)).collect();
+
+        let display = if i > 0 { " display=\"none\"" } else { "" };
+        svg.push_str(&format!("  <g class=\"frame\"{}>\n", display));
+        svg.push_str(&format!(
+            "    <animate attributeName=\"display\" values=\"{}\" keyTimes=\"{}\" dur=\"{:.3}s\" calcMode=\"discrete\" repeatCount=\"indefinite\"/>\n",
+            values.join(";"),
+            key_times.join(";"),
+            total
+        ));
+        for line in svg_body(&frame_svg).lines() {
+            svg.push_str("  ");
+            svg.push_str(line);
+            svg.push('\n');
+        }
+        svg.push_str("  </g>\n");
+        start = end;
+    }
+    svg.push_str("</svg>");
+    Ok(svg)
+}
+
+/// The markup between the root `<svg ...>` tag and its closing tag.
+fn svg_body(svg: &str) -> &str {
+    let open = svg.find("<svg").and_then(|i| svg[i..].find('>').map(|j| i + j + 1)).unwrap_or(0);
+    let close = svg.rfind("</svg>").unwrap_or(svg.len());
+    svg[open..close].trim_matches('\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_processor::{load_animation_frames, load_image_frames};
+    use crate::validation::validate_svg_wellformed;
+
+    #[test]
+    fn test_convert_animated_gif() {
+        let path = std::env::temp_dir().join("img2svg_animated.gif");
+        {
+            let file = std::fs::File::create(&path).unwrap();
+            let mut encoder = image::codecs::gif::GifEncoder::new(file);
+            let frames = [(200, 0, 0), (0, 0, 200)].map(|(r, g, b)| {
+                let mut img = image::RgbaImage::from_pixel(20, 20, image::Rgba([255, 255, 255, 255]));
+                for y in 5..15 {
+                    for x in 5..15 {
+                        img.put_pixel(x, y, image::Rgba([r, g, b, 255]));
+                    }
+                }
+                image::Frame::from_parts(img, 0, 0, image::Delay::from_numer_denom_ms(250, 1))
+            });
+            encoder.encode_frames(frames).unwrap();
+        }
+
+        assert_eq!(load_image_frames(&path).unwrap().len(), 2);
+        let frames = load_animation_frames(&path).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].1, Duration::from_millis(250));
+
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+        let svg = convert_animated(&frames, &options).unwrap();
+        validate_svg_wellformed(&svg).unwrap();
+        assert_eq!(svg.matches("<g class=\"frame\"").count(), 2, "{}", svg);
+        assert_eq!(svg.matches("<animate ").count(), 2);
+        assert!(svg.contains("dur=\"0.500s\""));
+        assert!(svg.contains("#c80000") && svg.contains("#0000c8"));
+        assert_eq!(svg.matches("<svg").count(), 1);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
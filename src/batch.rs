@@ -0,0 +1,96 @@
+//! Batch conversion of a directory of images, several files at a time.
+
+use crate::enhanced_vectorizer::{
+    vectorize_enhanced, write_enhanced_svg_with_options, ConversionStats, EnhancedOptions,
+};
+use crate::image_processor::{is_supported_image, load_image};
+use anyhow::Result;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use std::path::{Path, PathBuf};
+
+/// Outcome of [`batch_convert_parallel`].
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// (input, written SVG) for every file that converted
+    pub converted: Vec<(PathBuf, PathBuf)>,
+    /// Files that failed, with the reason
+    pub failed: Vec<(PathBuf, anyhow::Error)>,
+}
+
+/// Convert every supported image in `input_dir` to `output_dir/<stem>.svg`
+/// with the enhanced pipeline, running up to `max_in_flight` files at once
+/// (0 = one per core). A failing file is recorded in the report and does not
+/// stop the others; only an unreadable directory or invalid options fail
+/// the whole call.
+pub fn batch_convert_parallel(
+    input_dir: &Path,
+    output_dir: &Path,
+    options: &EnhancedOptions,
+    max_in_flight: usize,
+) -> Result<BatchReport> {
+    options.validate()?;
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut inputs: Vec<PathBuf> = std::fs::read_dir(input_dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && is_supported_image(p))
+        .collect();
+    inputs.sort();
+
+    let pool = ThreadPoolBuilder::new().num_threads(max_in_flight).build()?;
+    let results: Vec<(PathBuf, Result<PathBuf>)> = pool.install(|| {
+        inputs
+            .into_par_iter()
+            .map(|input| {
+                let stem = input.file_stem().unwrap_or_default();
+                let output = output_dir.join(stem).with_extension("svg");
+                let result = convert_file(&input, &output, options).map(|_| output);
+                (input, result)
+            })
+            .collect()
+    });
+
+    let mut report = BatchReport::default();
+    for (input, result) in results {
+        match result {
+            Ok(output) => report.converted.push((input, output)),
+            Err(e) => report.failed.push((input, e)),
+        }
+    }
+    Ok(report)
+}
+
+/// Convert each of `inputs` to `output_dir/<stem>.svg` in order, one file
+/// at a time, returning every input with its stats or the reason it
+/// failed. Unlike [`batch_convert_parallel`] nothing fails the whole call:
+/// invalid options or an unwritable `output_dir` show up on every entry.
+pub fn convert_all(
+    inputs: &[PathBuf],
+    output_dir: &Path,
+    options: &EnhancedOptions,
+) -> Vec<(PathBuf, Result<ConversionStats>)> {
+    inputs
+        .iter()
+        .map(|input| {
+            let result = options
+                .validate()
+                .and_then(|()| Ok(std::fs::create_dir_all(output_dir)?))
+                .and_then(|()| {
+                    let stem = input.file_stem().unwrap_or_default();
+                    convert_file(input, &output_dir.join(stem).with_extension("svg"), options)
+                });
+            (input.clone(), result)
+        })
+        .collect()
+}
+
+fn convert_file(input: &Path, output: &Path, options: &EnhancedOptions) -> Result<ConversionStats> {
+    let options = &EnhancedOptions { source_path: Some(input.to_path_buf()), ..options.clone() };
+    let image_data = load_image(input)?;
+    let mut data = vectorize_enhanced(&image_data, options)?;
+    write_enhanced_svg_with_options(&data, output, options)?;
+    data.stats.svg_bytes = std::fs::metadata(output)?.len() as usize;
+    Ok(data.stats)
+}
@@ -14,10 +14,15 @@ pub struct BezierCurve {
     pub end: Point,
 }
 
+/// Samples taken along a curve when looking for loops and cusps.
+const LOOP_SAMPLES: usize = 32;
+
 /// Fit cubic Bézier curves to a sequence of points.
 pub struct BezierFitter {
     tolerance: f64,
     max_iterations: usize,
+    adaptive: bool,
+    loop_guard: bool,
 }
 
 impl BezierFitter {
@@ -25,7 +30,46 @@ impl BezierFitter {
         Self {
             tolerance,
             max_iterations: 12,
+            adaptive: false,
+            loop_guard: false,
+        }
+    }
+
+    /// Scale the tolerance per segment with local curvature: tight turns are
+    /// fitted more tightly (more segments), flat runs more loosely.
+    pub fn with_adaptive_tolerance(mut self, adaptive: bool) -> Self {
+        self.adaptive = adaptive;
+        self
+    }
+
+    /// Check every fitted curve for loops and cusps, and tame the ones that
+    /// have them: handles are shortened to a third of the chord, and a curve
+    /// that still loops becomes a straight line.
+    pub fn with_loop_guard(mut self, loop_guard: bool) -> Self {
+        self.loop_guard = loop_guard;
+        self
+    }
+
+    /// Effective tolerance for a run of points. Uses the peak turn
+    /// (sine of the turn angle between consecutive edges) inside the run.
+    fn segment_tolerance(&self, points: &[Point]) -> f64 {
+        if !self.adaptive || points.len() < 3 {
+            return self.tolerance;
+        }
+        let mut max_curvature = 0.0f64;
+        for i in 1..points.len() - 1 {
+            let v1x = points[i].x - points[i - 1].x;
+            let v1y = points[i].y - points[i - 1].y;
+            let v2x = points[i + 1].x - points[i].x;
+            let v2y = points[i + 1].y - points[i].y;
+            let len1 = (v1x * v1x + v1y * v1y).sqrt();
+            let len2 = (v2x * v2x + v2y * v2y).sqrt();
+            if len1 > 0.0 && len2 > 0.0 {
+                max_curvature = max_curvature.max((v1x * v2y - v1y * v2x).abs() / (len1 * len2));
+            }
         }
+        // 1.5× for straight-ish runs down to 0.25× for turns of ~35° and more
+        self.tolerance * (1.5 - 3.5 * max_curvature).clamp(0.25, 1.5)
     }
 
     /// Fit a path (sequence of points) into a series of cubic Bézier curves.
@@ -114,9 +158,68 @@ impl BezierFitter {
             }
         }
 
+        // The box margin is generous for short segments, so clamped handles
+        // can still swing far enough to tie a curve into a loop
+        if self.loop_guard {
+            for curve in &mut curves {
+                if self.has_loop_or_cusp(curve) {
+                    *curve = self.tame_curve(curve);
+                }
+            }
+        }
+
         curves
     }
 
+    /// Whether a curve crosses itself or doubles back on itself (a cusp).
+    ///
+    /// The curve is flattened into a polyline: a cusp shows up as a tangent
+    /// reversal between consecutive samples, a loop as two non-adjacent
+    /// sample segments crossing.
+    pub fn has_loop_or_cusp(&self, curve: &BezierCurve) -> bool {
+        let samples: Vec<Point> = (0..=LOOP_SAMPLES)
+            .map(|i| self.evaluate(curve, i as f64 / LOOP_SAMPLES as f64))
+            .collect();
+        let dirs: Vec<(f64, f64)> = samples.windows(2).map(|w| (w[1].x - w[0].x, w[1].y - w[0].y)).collect();
+        if dirs.windows(2).any(|d| d[0].0 * d[1].0 + d[0].1 * d[1].1 < 0.0) {
+            return true;
+        }
+        for i in 0..dirs.len() {
+            for j in i + 2..dirs.len() {
+                if segments_cross(&samples[i], &samples[i + 1], &samples[j], &samples[j + 1]) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Shorten both handles to at most a third of the chord, keeping their
+    /// directions; fall back to a straight line if that still loops.
+    fn tame_curve(&self, curve: &BezierCurve) -> BezierCurve {
+        let chord = (curve.end.x - curve.start.x).hypot(curve.end.y - curve.start.y);
+        let limit = |anchor: &Point, control: &Point| {
+            let (dx, dy) = (control.x - anchor.x, control.y - anchor.y);
+            let len = dx.hypot(dy);
+            if len <= chord / 3.0 {
+                return control.clone();
+            }
+            let scale = chord / 3.0 / len;
+            Point { x: anchor.x + dx * scale, y: anchor.y + dy * scale }
+        };
+        let tamed = BezierCurve {
+            start: curve.start.clone(),
+            control1: limit(&curve.start, &curve.control1),
+            control2: limit(&curve.end, &curve.control2),
+            end: curve.end.clone(),
+        };
+        if self.has_loop_or_cusp(&tamed) {
+            self.linear_to_cubic(&curve.start, &curve.end)
+        } else {
+            tamed
+        }
+    }
+
     /// Detect sharp corners (turn angle > 60°) in a point sequence.
     /// The angle measures the turn between consecutive edge vectors:
     /// 0° = straight, 90° = right angle, 180° = U-turn.
@@ -158,9 +261,10 @@ impl BezierFitter {
             curves.push(self.linear_to_cubic(&points[0], &points[1]));
             return;
         }
+        let tolerance = self.segment_tolerance(points);
 
         // Check if points are nearly collinear — use linear Bézier
-        if self.is_nearly_linear(points) {
+        if self.is_nearly_linear(points, tolerance) {
             curves.push(self.linear_to_cubic(&points[0], &points[points.len() - 1]));
             return;
         }
@@ -186,7 +290,7 @@ impl BezierFitter {
         let mut best_curve = self.least_squares_fit(points, &t_values);
         let (mut best_err, mut best_idx) = self.max_fitting_error(&best_curve, points);
 
-        if best_err <= self.tolerance {
+        if best_err <= tolerance {
             curves.push(best_curve);
             return;
         }
@@ -203,7 +307,7 @@ impl BezierFitter {
                 best_err = new_err;
                 best_idx = new_idx;
 
-                if best_err <= self.tolerance {
+                if best_err <= tolerance {
                     curves.push(best_curve);
                     return;
                 }
@@ -224,7 +328,7 @@ impl BezierFitter {
     /// Check if a sequence of points is nearly collinear (max deviation < threshold).
     /// For long segments, uses a relative threshold (1% of segment length) to avoid
     /// fitting curves to what are essentially straight lines with tiny deviations.
-    fn is_nearly_linear(&self, points: &[Point]) -> bool {
+    fn is_nearly_linear(&self, points: &[Point], tolerance: f64) -> bool {
         if points.len() < 3 {
             return true;
         }
@@ -239,7 +343,7 @@ impl BezierFitter {
         // Use the larger of: fixed tolerance, or 1% of segment length.
         // This prevents long near-vertical/horizontal lines from being curved
         // due to tiny pixel-level deviations after simplification.
-        let threshold = (self.tolerance * 0.5).max(line_len * 0.01);
+        let threshold = (tolerance * 0.5).max(line_len * 0.01);
         for p in &points[1..points.len() - 1] {
             let dist = ((p.y - start.y) * dx - (p.x - start.x) * dy).abs() / line_len;
             if dist > threshold {
@@ -509,69 +613,339 @@ impl BezierFitter {
     }
 }
 
-/// Format Bézier curves as SVG path data.
-/// Uses `L` for near-linear curves and `C` for true curves to minimize SVG size.
-/// Merges consecutive collinear `L` segments into a single `L`.
-pub fn bezier_to_svg_path(curves: &[BezierCurve], closed: bool) -> String {
-    if curves.is_empty() {
-        return String::new();
+/// Number formatting settings for emitted SVG path data.
+#[derive(Debug, Clone)]
+pub struct PathFormat {
+    /// Decimal places kept in coordinates (trailing zeros are trimmed)
+    pub precision: u8,
+    /// Emit `S` instead of `C` when a curve's first control point is the
+    /// reflection of the previous curve's second one
+    pub smooth_shorthand: bool,
+    /// Distance in px a point may stray from the line through its
+    /// neighbors and still be merged into one `L`; lines shorter than a
+    /// third of this are always merged
+    pub merge_tolerance: f64,
+}
+
+impl Default for PathFormat {
+    fn default() -> Self {
+        Self { precision: 2, smooth_shorthand: false, merge_tolerance: 1.5 }
     }
+}
 
-    let mut path = format!("M{},{}", fmt_num(curves[0].start.x), fmt_num(curves[0].start.y));
+impl PathFormat {
+    /// Format Bézier curves as SVG path data.
+    /// Uses `L` for near-linear curves and `C` for true curves to minimize SVG size.
+    /// Merges consecutive collinear `L` segments into a single `L`, and with
+    /// `smooth_shorthand` writes G1-continuous follow-on curves as `S`.
+    pub fn bezier_path(&self, curves: &[BezierCurve], closed: bool) -> String {
+        if curves.is_empty() {
+            return String::new();
+        }
 
-    let mut i = 0;
-    while i < curves.len() {
-        let curve = &curves[i];
-        if is_linear_curve(curve) {
-            // Merge consecutive collinear L segments using distance-based check.
-            // This catches diagonal staircases from marching squares where
-            // cross-product fails due to pixel-grid stepping.
-            let start = &curves[i].start;
-            let mut end = &curve.end;
-            let mut j = i + 1;
-            while j < curves.len() {
-                let next = &curves[j];
-                if !is_linear_curve(next) {
-                    break;
+        let mut path = String::new();
+        let mut pen: Option<&Point> = None;
+        // The curve just written with `C`/`S`, whose control point `S` reflects
+        let mut prev_cubic: Option<&BezierCurve> = None;
+
+        let mut i = 0;
+        while i < curves.len() {
+            let curve = &curves[i];
+            // A curve that doesn't continue from the pen starts a new subpath
+            if pen.is_none_or(|p| !same_point(p, &curve.start)) {
+                if pen.is_some() && closed {
+                    path.push('Z');
                 }
-                // Check if ALL intermediate points lie within 1.5px of the
-                // line from start to next.end (distance-based collinear test)
-                let candidate_end = &next.end;
-                let dx = candidate_end.x - start.x;
-                let dy = candidate_end.y - start.y;
-                let line_len = (dx * dx + dy * dy).sqrt();
-                if line_len < 0.5 {
-                    end = candidate_end;
-                    j += 1;
-                    continue;
+                path.push_str(&format!("M{},{}", self.num(curve.start.x), self.num(curve.start.y)));
+                prev_cubic = None;
+            }
+
+            if is_linear_curve(curve) {
+                // Merge consecutive collinear L segments using distance-based check.
+                // This catches diagonal staircases from marching squares where
+                // cross-product fails due to pixel-grid stepping.
+                let start = &curves[i].start;
+                let mut end = &curve.end;
+                let mut j = i + 1;
+                while j < curves.len() {
+                    let next = &curves[j];
+                    if !is_linear_curve(next) || !same_point(&next.start, end) {
+                        break;
+                    }
+                    // Check if ALL intermediate points lie within merge_tolerance of
+                    // the line from start to next.end (distance-based collinear test)
+                    let candidate_end = &next.end;
+                    let dx = candidate_end.x - start.x;
+                    let dy = candidate_end.y - start.y;
+                    let line_len = (dx * dx + dy * dy).sqrt();
+                    if line_len < self.merge_tolerance / 3.0 {
+                        end = candidate_end;
+                        j += 1;
+                        continue;
+                    }
+                    // Check current end point distance to the proposed line
+                    let dist = ((end.y - start.y) * dx - (end.x - start.x) * dy).abs() / line_len;
+                    if dist < self.merge_tolerance {
+                        end = candidate_end;
+                        j += 1;
+                    } else {
+                        break;
+                    }
                 }
-                // Check current end point distance to the proposed line
-                let dist = ((end.y - start.y) * dx - (end.x - start.x) * dy).abs() / line_len;
-                if dist < 1.5 {
-                    end = candidate_end;
-                    j += 1;
+                path.push_str(&format!("L{},{}", self.num(end.x), self.num(end.y)));
+                pen = Some(end);
+                prev_cubic = None;
+                i = j;
+            } else if self.smooth_shorthand && prev_cubic.is_some_and(|prev| self.reflects(prev, curve)) {
+                path.push_str(&format!(
+                    "S{},{} {},{}",
+                    self.num(curve.control2.x), self.num(curve.control2.y),
+                    self.num(curve.end.x), self.num(curve.end.y),
+                ));
+                pen = Some(&curve.end);
+                prev_cubic = Some(curve);
+                i += 1;
+            } else {
+                path.push_str(&format!(
+                    "C{},{} {},{} {},{}",
+                    self.num(curve.control1.x), self.num(curve.control1.y),
+                    self.num(curve.control2.x), self.num(curve.control2.y),
+                    self.num(curve.end.x), self.num(curve.end.y),
+                ));
+                pen = Some(&curve.end);
+                prev_cubic = Some(curve);
+                i += 1;
+            }
+        }
+
+        if closed {
+            path.push('Z');
+        }
+
+        path
+    }
+
+    /// Whether `curve.control1` prints the same as the reflection of
+    /// `prev.control2` about their shared point, as a renderer computes it
+    /// from the printed numbers, so `S` reproduces the `C` output exactly.
+    fn reflects(&self, prev: &BezierCurve, curve: &BezierCurve) -> bool {
+        let printed = |v: f64| self.num(v).parse::<f64>().unwrap_or(v);
+        let reflect = |end: f64, control: f64| self.num(2.0 * printed(end) - printed(control));
+        reflect(prev.end.x, prev.control2.x) == self.num(curve.control1.x)
+            && reflect(prev.end.y, prev.control2.y) == self.num(curve.control1.y)
+    }
+
+    /// Format a point sequence verbatim as SVG path data (`M` then one `L` per point).
+    pub fn polyline_path(&self, points: &[Point], closed: bool) -> String {
+        let mut path = String::with_capacity(points.len() * 12);
+        for (i, p) in points.iter().enumerate() {
+            path.push(if i == 0 { 'M' } else { 'L' });
+            path.push_str(&self.num(p.x));
+            path.push(',');
+            path.push_str(&self.num(p.y));
+        }
+        if closed && !path.is_empty() {
+            path.push('Z');
+        }
+        path
+    }
+
+    /// Format a float compactly: integer if close to whole, else `precision`
+    /// decimal places with trailing zeros trimmed.
+    pub fn num(&self, v: f64) -> String {
+        let scale = 10f64.powi(self.precision as i32);
+        let rounded = (v * scale).round() / scale;
+        if (rounded - rounded.round()).abs() < 1e-9 {
+            // Normalize -0 so it never prints as "-0"
+            format!("{}", rounded.round() as i64)
+        } else {
+            let s = format!("{:.*}", self.precision as usize, rounded);
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        }
+    }
+}
+
+/// Format Bézier curves as SVG path data with the default 2-decimal precision.
+/// See [`PathFormat::bezier_path`].
+pub fn bezier_to_svg_path(curves: &[BezierCurve], closed: bool) -> String {
+    PathFormat::default().bezier_path(curves, closed)
+}
+
+/// Format a point sequence verbatim as SVG path data (`M` then one `L` per point).
+pub fn polyline_to_svg_path(points: &[Point], closed: bool) -> String {
+    PathFormat::default().polyline_path(points, closed)
+}
+
+/// One absolute path command with its number tokens as written.
+struct PathCommand<'a> {
+    command: char,
+    args: Vec<&'a str>,
+    values: Vec<f64>,
+}
+
+impl PathCommand<'_> {
+    /// End point of the command (the last coordinate pair).
+    fn end(&self) -> Option<(f64, f64)> {
+        let n = self.values.len();
+        (n >= 2).then(|| (self.values[n - 2], self.values[n - 1]))
+    }
+}
+
+/// Split absolute `M`/`L`/`C`/`S`/`Z` path data into one command per
+/// segment (extra `M` pairs become `L`). `None` for anything else.
+fn parse_path_commands(d: &str) -> Option<Vec<PathCommand<'_>>> {
+    let mut tokens: Vec<(char, Vec<&str>)> = Vec::new();
+    let bytes = d.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        if matches!(c, 'M' | 'L' | 'C' | 'S' | 'Z') {
+            tokens.push((c, Vec::new()));
+            i += 1;
+        } else if c == ',' || c.is_ascii_whitespace() {
+            i += 1;
+        } else if c == '-' || c == '.' || c.is_ascii_digit() {
+            let start = i;
+            i += 1;
+            let mut seen_dot = c == '.';
+            while i < bytes.len() {
+                let n = bytes[i] as char;
+                if n.is_ascii_digit() || (n == '.' && !seen_dot) {
+                    seen_dot |= n == '.';
+                    i += 1;
                 } else {
                     break;
                 }
             }
-            path.push_str(&format!("L{},{}", fmt_num(end.x), fmt_num(end.y)));
-            i = j;
+            tokens.last_mut()?.1.push(&d[start..i]);
         } else {
-            path.push_str(&format!(
-                "C{},{} {},{} {},{}",
-                fmt_num(curve.control1.x), fmt_num(curve.control1.y),
-                fmt_num(curve.control2.x), fmt_num(curve.control2.y),
-                fmt_num(curve.end.x), fmt_num(curve.end.y),
-            ));
-            i += 1;
+            return None;
         }
     }
 
-    if closed {
-        path.push('Z');
+    let mut commands = Vec::new();
+    for (command, args) in tokens {
+        let arity = match command {
+            'M' | 'L' => 2,
+            'S' => 4,
+            'C' => 6,
+            _ => 0,
+        };
+        if arity == 0 {
+            if !args.is_empty() {
+                return None;
+            }
+            commands.push(PathCommand { command, args, values: Vec::new() });
+            continue;
+        }
+        if args.is_empty() || args.len() % arity != 0 {
+            return None;
+        }
+        for (k, chunk) in args.chunks(arity).enumerate() {
+            let values = chunk.iter().map(|a| a.parse::<f64>().ok()).collect::<Option<Vec<_>>>()?;
+            let command = if command == 'M' && k > 0 { 'L' } else { command };
+            commands.push(PathCommand { command, args: chunk.to_vec(), values });
+        }
+    }
+    Some(commands)
+}
+
+/// Peephole pass over path data: drops zero-length lines and lines that
+/// `Z` would draw anyway, merges runs of exactly collinear same-direction
+/// `L`s, and writes repeated commands implicitly (`L1,2 3,4`). The shape
+/// is unchanged; path data this cannot parse is returned as is.
+pub fn collapse_path_commands(d: &str) -> String {
+    let Some(commands) = parse_path_commands(d) else {
+        return d.to_string();
+    };
+
+    let mut kept: Vec<&PathCommand> = Vec::with_capacity(commands.len());
+    let (mut pen, mut subpath_start) = ((0.0, 0.0), (0.0, 0.0));
+    // Where the last kept command started, if it is an `L`
+    let mut line_from: Option<(f64, f64)> = None;
+    for (i, command) in commands.iter().enumerate() {
+        let next = commands.get(i + 1).map(|c| c.command);
+        match command.command {
+            'M' => {
+                pen = command.end().unwrap_or(pen);
+                subpath_start = pen;
+                line_from = None;
+            }
+            'L' => {
+                let to = command.end().unwrap_or(pen);
+                // An `S` right after reflects nothing when it follows an
+                // `L`, so the `L` must stay even if it has no length
+                if next != Some('S') && (to == pen || (to == subpath_start && next == Some('Z'))) {
+                    pen = to;
+                    continue;
+                }
+                if let Some(from) = line_from
+                    && continues_line(from, pen, to)
+                {
+                    kept.pop();
+                    kept.push(command);
+                    pen = to;
+                    continue;
+                }
+                line_from = Some(pen);
+                pen = to;
+                kept.push(command);
+                continue;
+            }
+            'Z' => {
+                pen = subpath_start;
+                line_from = None;
+            }
+            _ => {
+                pen = command.end().unwrap_or(pen);
+                line_from = None;
+            }
+        }
+        kept.push(command);
     }
 
-    path
+    let mut out = String::with_capacity(d.len());
+    let mut previous: Option<char> = None;
+    for command in kept {
+        if previous == Some(command.command) && !matches!(command.command, 'M' | 'Z') {
+            out.push(' ');
+        } else {
+            out.push(command.command);
+        }
+        for (k, pair) in command.args.chunks(2).enumerate() {
+            if k > 0 {
+                out.push(' ');
+            }
+            out.push_str(&pair.join(","));
+        }
+        previous = Some(command.command);
+    }
+    out
+}
+
+/// Whether the segment `b`→`c` carries on straight ahead from `a`→`b`.
+fn continues_line(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> bool {
+    let (ux, uy) = (b.0 - a.0, b.1 - a.1);
+    let (vx, vy) = (c.0 - b.0, c.1 - b.1);
+    let cross = ux * vy - uy * vx;
+    cross.abs() <= 1e-9 * ux.hypot(uy) * vx.hypot(vy) && ux * vx + uy * vy > 0.0
+}
+
+/// Whether two points coincide (up to floating-point noise).
+fn same_point(a: &Point, b: &Point) -> bool {
+    (a.x - b.x).abs() < 1e-6 && (a.y - b.y).abs() < 1e-6
+}
+
+/// Whether segments `a0-a1` and `b0-b1` properly cross. Near-collinear
+/// samples (a straight curve) count as touching, not crossing.
+fn segments_cross(a0: &Point, a1: &Point, b0: &Point, b1: &Point) -> bool {
+    let side = |p: &Point, q: &Point, r: &Point| {
+        let cross = (q.x - p.x) * (r.y - p.y) - (q.y - p.y) * (r.x - p.x);
+        if cross.abs() < 1e-9 { 0.0 } else { cross }
+    };
+    let (d1, d2) = (side(a0, a1, b0), side(a0, a1, b1));
+    let (d3, d4) = (side(b0, b1, a0), side(b0, b1, a1));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
 }
 
 /// Check if a cubic Bézier is effectively a straight line
@@ -588,20 +962,74 @@ fn is_linear_curve(curve: &BezierCurve) -> bool {
     d1 < 1.0 && d2 < 1.0
 }
 
-/// Format a float compactly: integer if close to whole, else 2 decimal places trimmed.
-fn fmt_num(v: f64) -> String {
-    if (v - v.round()).abs() < 1e-4 {
-        format!("{}", v.round() as i64)
-    } else {
-        let s = format!("{:.2}", v);
-        s.trim_end_matches('0').trim_end_matches('.').to_string()
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Long gentle arc, then a tight rounded corner (every step turns less
+    /// than the 30° split threshold), then a straight run.
+    fn gentle_arc_then_tight_turn() -> (Vec<Point>, Point) {
+        let mut points = Vec::new();
+        let r = 150.0;
+        for i in 0..=40 {
+            let a = (i as f64 / 40.0) * 0.8;
+            points.push(Point { x: r * a.sin(), y: r - r * a.cos() });
+        }
+        let last = points.last().unwrap().clone();
+        let heading = 0.8f64;
+        // Tight turn of radius 3, 20° per step for 120°
+        let rc = 3.0;
+        let (cx, cy) = (last.x - rc * heading.sin(), last.y + rc * heading.cos());
+        for k in 1..=6 {
+            let a = heading + (k as f64 * 20.0).to_radians();
+            points.push(Point { x: cx + rc * a.sin(), y: cy - rc * a.cos() });
+        }
+        let corner = Point { x: cx, y: cy };
+        let end = points.last().unwrap().clone();
+        let dir = heading + 120f64.to_radians();
+        for k in 1..=10 {
+            let d = k as f64 * 3.0;
+            points.push(Point { x: end.x + d * dir.cos(), y: end.y + d * dir.sin() });
+        }
+        (points, corner)
+    }
+
+    #[test]
+    fn test_adaptive_tolerance_clusters_segments_at_tight_turn() {
+        let (points, corner) = gentle_arc_then_tight_turn();
+        let count = |adaptive: bool| {
+            let curves = BezierFitter::new(2.0)
+                .with_adaptive_tolerance(adaptive)
+                .fit_path(&points, false);
+            let near = curves
+                .iter()
+                .filter(|c| (c.end.x - corner.x).hypot(c.end.y - corner.y) < 12.0)
+                .count();
+            (near, curves.len() - near)
+        };
+        let (near_fixed, far_fixed) = count(false);
+        let (near_adaptive, far_adaptive) = count(true);
+        assert!(
+            near_adaptive > near_fixed,
+            "expected more segments at the turn: {} vs {}",
+            near_adaptive,
+            near_fixed
+        );
+        assert!(far_adaptive <= far_fixed);
+    }
+
+    #[test]
+    fn test_polyline_to_svg_path() {
+        let points = vec![
+            Point { x: 0.0, y: 0.5 },
+            Point { x: 3.0, y: 0.5 },
+            Point { x: 3.0, y: 2.25 },
+        ];
+        assert_eq!(polyline_to_svg_path(&points, true), "M0,0.5L3,0.5L3,2.25Z");
+        assert_eq!(polyline_to_svg_path(&points, false), "M0,0.5L3,0.5L3,2.25");
+        assert_eq!(polyline_to_svg_path(&[], true), "");
+    }
+
     #[test]
     fn test_linear_to_cubic() {
         let fitter = BezierFitter::new(2.0);
@@ -683,16 +1111,88 @@ mod tests {
         assert!(path.ends_with('Z'));
     }
 
+    #[test]
+    fn test_merge_tolerance_keeps_slight_bend() {
+        let fitter = BezierFitter::new(1.0);
+        let line = |x0: f64, y0: f64, x1: f64, y1: f64| {
+            fitter.linear_to_cubic(&Point { x: x0, y: y0 }, &Point { x: x1, y: y1 })
+        };
+        // (10,1) sits 1px off the chord from (0,0) to (20,0)
+        let curves = vec![line(0.0, 0.0, 10.0, 1.0), line(10.0, 1.0, 20.0, 0.0)];
+        assert_eq!(PathFormat::default().bezier_path(&curves, false), "M0,0L20,0");
+        let tight = PathFormat { merge_tolerance: 0.5, ..Default::default() };
+        assert_eq!(tight.bezier_path(&curves, false), "M0,0L10,1L20,0");
+    }
+
+    #[test]
+    fn test_bezier_path_starts_new_subpath_on_gap() {
+        let fitter = BezierFitter::new(1.0);
+        let line = |x0: f64, y0: f64, x1: f64, y1: f64| {
+            fitter.linear_to_cubic(&Point { x: x0, y: y0 }, &Point { x: x1, y: y1 })
+        };
+        let curves = vec![
+            line(0.0, 0.0, 4.0, 0.0),
+            line(4.0, 0.0, 4.0, 4.0),
+            line(10.0, 10.0, 14.0, 10.0),
+            line(14.0, 10.0, 14.0, 14.0),
+        ];
+        let path = bezier_to_svg_path(&curves, true);
+        assert_eq!(path, "M0,0L4,0L4,4ZM10,10L14,10L14,14Z");
+    }
+
+    #[test]
+    fn test_smooth_shorthand_emits_s_for_g1_join() {
+        let p = |x, y| Point { x, y };
+        let curves = vec![
+            BezierCurve { start: p(0.0, 0.0), control1: p(0.0, 10.0), control2: p(10.0, 10.0), end: p(10.0, 5.0) },
+            // control1 mirrors the previous control2 (10,10) about (10,5)
+            BezierCurve { start: p(10.0, 5.0), control1: p(10.0, 0.0), control2: p(20.0, 0.0), end: p(20.0, 10.0) },
+            // Not mirrored: stays a full C
+            BezierCurve { start: p(20.0, 10.0), control1: p(25.0, 10.0), control2: p(30.0, 20.0), end: p(30.0, 30.0) },
+        ];
+        let format = PathFormat { smooth_shorthand: true, ..Default::default() };
+        assert_eq!(
+            format.bezier_path(&curves, false),
+            "M0,0C0,10 10,10 10,5S20,0 20,10C25,10 30,20 30,30"
+        );
+        assert!(!bezier_to_svg_path(&curves, false).contains('S'));
+    }
+
     #[test]
     fn test_fmt_num_integer() {
-        assert_eq!(fmt_num(5.0), "5");
-        assert_eq!(fmt_num(5.0001), "5");
+        let fmt = PathFormat::default();
+        assert_eq!(fmt.num(5.0), "5");
+        assert_eq!(fmt.num(5.0001), "5");
+        assert_eq!(fmt.num(-0.001), "0");
     }
 
     #[test]
     fn test_fmt_num_decimal() {
-        assert_eq!(fmt_num(5.25), "5.25");
-        assert_eq!(fmt_num(5.10), "5.1");
+        let fmt = PathFormat::default();
+        assert_eq!(fmt.num(5.25), "5.25");
+        assert_eq!(fmt.num(5.10), "5.1");
+        assert_eq!(fmt.num(70000.5), "70000.5");
+        assert_eq!(fmt.num(3_000_000_000.0), "3000000000");
+    }
+
+    #[test]
+    fn test_fmt_num_precision() {
+        assert_eq!(PathFormat { precision: 0, ..Default::default() }.num(5.6), "6");
+        assert_eq!(PathFormat { precision: 1, ..Default::default() }.num(5.25), "5.3");
+        assert_eq!(PathFormat { precision: 3, ..Default::default() }.num(5.1234), "5.123");
+    }
+
+    #[test]
+    fn test_precision_changes_path_length() {
+        let curves = vec![BezierCurve {
+            start: Point { x: 1.23456, y: 2.34567 },
+            control1: Point { x: 10.98765, y: 3.45678 },
+            control2: Point { x: 20.13579, y: 14.24681 },
+            end: Point { x: 30.86421, y: 5.97531 },
+        }];
+        let d = |precision| PathFormat { precision, ..Default::default() }.bezier_path(&curves, false);
+        assert!(d(3).len() >= d(1).len() + 16, "{} vs {}", d(3), d(1));
+        assert!(!d(0).contains('.'), "{}", d(0));
     }
 
     #[test]
@@ -713,4 +1213,76 @@ mod tests {
             assert!(curve.control1.y >= -2.0 && curve.control1.y <= 7.0);
         }
     }
+
+    #[test]
+    fn test_loop_guard_untangles_cusped_curve() {
+        // A V whose apex is repeated: the zero-length edge hides the corner,
+        // so the run is fitted as one curve, and the 2px clamp margin on this
+        // small box still lets the handles tie it into a loop.
+        let points: Vec<Point> = [(4.0, 1.0), (2.0, 3.0), (2.0, 3.0), (3.0, 2.0), (5.0, 1.0)]
+            .iter()
+            .map(|&(x, y)| Point { x, y })
+            .collect();
+        let plain = BezierFitter::new(2.0);
+        assert!(plain.fit_path(&points, false).iter().any(|c| plain.has_loop_or_cusp(c)));
+
+        let guarded = BezierFitter::new(2.0).with_loop_guard(true);
+        let curves = guarded.fit_path(&points, false);
+        assert!(!curves.is_empty());
+        for curve in &curves {
+            assert!(!guarded.has_loop_or_cusp(curve), "still loops: {:?}", curve);
+        }
+        assert!(same_point(&curves[0].start, &points[0]));
+        assert!(same_point(&curves.last().unwrap().end, &points[4]));
+    }
+
+    #[test]
+    fn test_has_loop_or_cusp() {
+        let fitter = BezierFitter::new(1.0);
+        let p = |x, y| Point { x, y };
+        let arc = BezierCurve { start: p(0.0, 0.0), control1: p(0.0, 5.0), control2: p(5.0, 10.0), end: p(10.0, 10.0) };
+        let line = fitter.linear_to_cubic(&p(0.0, 0.0), &p(10.0, 0.0));
+        // Handles crossed over each other: the curve ties a loop
+        let looped = BezierCurve { start: p(0.0, 0.0), control1: p(15.0, 10.0), control2: p(-5.0, 10.0), end: p(10.0, 0.0) };
+        // Control points mirrored across the chord midpoint: a cusp
+        let cusp = BezierCurve { start: p(0.0, 0.0), control1: p(10.0, 10.0), control2: p(0.0, 10.0), end: p(10.0, 0.0) };
+        assert!(!fitter.has_loop_or_cusp(&arc));
+        assert!(!fitter.has_loop_or_cusp(&line));
+        assert!(fitter.has_loop_or_cusp(&looped));
+        assert!(fitter.has_loop_or_cusp(&cusp));
+    }
+
+    #[test]
+    fn test_collapse_path_commands() {
+        let d = "M0,0L1,0L2,0L3,0L3,3L3,3L0,3L0,0Z";
+        let collapsed = collapse_path_commands(d);
+        assert_eq!(collapsed, "M0,0L3,0 3,3 0,3Z");
+        assert!(collapsed.len() < d.len());
+
+        // Same ring: every dropped vertex lay on the kept outline
+        let ring = |d: &str| -> Vec<(f64, f64)> {
+            parse_path_commands(d).unwrap().iter().filter_map(|c| c.end()).collect()
+        };
+        let kept = ring(&collapsed);
+        for p in ring(d) {
+            let on_outline = (0..kept.len()).any(|k| {
+                let (a, b) = (kept[k], kept[(k + 1) % kept.len()]);
+                let cross = (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0);
+                let within = p.0 >= a.0.min(b.0) && p.0 <= a.0.max(b.0) && p.1 >= a.1.min(b.1) && p.1 <= a.1.max(b.1);
+                cross == 0.0 && within
+            });
+            assert!(on_outline, "{:?} is off the collapsed outline", p);
+        }
+
+        // Curves are kept verbatim; a zero-length L before S stays
+        assert_eq!(
+            collapse_path_commands("M0,0C0,10 10,10 10,5C10,0 20,0 20,10L20,10L30,10L40,10Z"),
+            "M0,0C0,10 10,10 10,5 10,0 20,0 20,10L40,10Z"
+        );
+        assert_eq!(collapse_path_commands("M0,0C1,1 2,2 3,3L3,3S5,5 6,6"), "M0,0C1,1 2,2 3,3L3,3S5,5 6,6");
+        // Turning back is not a continuation
+        assert_eq!(collapse_path_commands("M0,0L5,0L2,0"), "M0,0L5,0 2,0");
+        // Unknown commands are left alone
+        assert_eq!(collapse_path_commands("M0,0H5V5Z"), "M0,0H5V5Z");
+    }
 }
@@ -0,0 +1,311 @@
+//! Centerline extraction for thin strokes in line drawings.
+//!
+//! A thin region is thinned to a one-pixel skeleton, the skeleton is cut into
+//! branches at its line ends and junctions, and branches that run straight
+//! through a junction are joined again, so a drawn "+" comes out as two
+//! crossing lines rather than four stubs.
+
+use crate::morphology::{distance_to_unset, thin};
+use crate::region_extractor::flood_fill_by_index;
+use crate::vectorizer::Point;
+
+/// Two branches meeting at a junction are joined into one line when they
+/// leave it in nearly opposite directions: the cosine of the angle between
+/// them is below this (about 135°).
+const JOIN_COSINE: f64 = -0.7;
+/// Skeleton pixels walked from a branch end to estimate its direction.
+const DIRECTION_SAMPLES: usize = 5;
+/// Junction pixels this close (px, per axis) count as one junction: where
+/// thick strokes cross, thinning leaves a small knot rather than one pixel.
+const JUNCTION_RADIUS: usize = 2;
+
+/// A centerline through pixel centers (ends extended to the region's edge)
+/// and the stroke width that covers the region around it.
+#[derive(Debug, Clone)]
+pub struct Centerline {
+    pub points: Vec<Point>,
+    pub width: f64,
+}
+
+/// Replace the thin components of `mask` by their centerlines.
+///
+/// An 8-connected component is thin when it is on average at most
+/// `max_width` px wide and its skeleton is at least twice that long; its
+/// pixels are cleared from `mask`. Broad components stay in the mask to be filled.
+pub fn extract_centerlines(mask: &mut [bool], width: usize, height: usize, max_width: f64) -> Vec<Centerline> {
+    let depth = distance_to_unset(mask, width, height);
+    let skeleton = thin(mask, width, height);
+    let labels: Vec<usize> = mask.iter().map(|&m| m as usize).collect();
+    let stroke_width = |idx: usize| 2.0 * depth[idx] as f64 - 1.0;
+    let mut visited = vec![false; mask.len()];
+    let mut lines = Vec::new();
+
+    for start in 0..mask.len() {
+        if labels[start] == 0 || visited[start] {
+            continue;
+        }
+        let pixels = flood_fill_by_index(
+            (start % width) as u32,
+            (start / width) as u32,
+            width as u32,
+            height as u32,
+            &labels,
+            1,
+            &mut visited,
+        );
+        let indices: Vec<usize> = pixels.iter().map(|&(x, y)| y as usize * width + x as usize).collect();
+        let bones: Vec<usize> = indices.iter().copied().filter(|&i| skeleton[i]).collect();
+        // Area per skeleton pixel is the mean stroke width, which unlike the
+        // depth does not swell where strokes cross
+        let mean_width = indices.len() as f64 / bones.len().max(1) as f64;
+        if mean_width > max_width || (bones.len() as f64) < 2.0 * mean_width {
+            continue;
+        }
+
+        let graph = SkeletonGraph { skeleton: &skeleton, width, height };
+        for branch in join_branches(graph.branches(&bones), &graph) {
+            let mut widths: Vec<f64> = branch.iter().map(|&i| stroke_width(i)).collect();
+            widths.sort_by(f64::total_cmp);
+            let line_width = widths[widths.len() / 2];
+            let mut points: Vec<Point> = branch
+                .iter()
+                .map(|&i| Point { x: (i % width) as f64 + 0.5, y: (i / width) as f64 + 0.5 })
+                .collect();
+            // Thinning stops short of a line's ends by about half its width
+            if branch.first() != branch.last() {
+                for end in [0, points.len() - 1] {
+                    if graph.neighbors(branch[end]).len() <= 1 {
+                        extend_end(&mut points, end, line_width / 2.0, width as f64, height as f64);
+                    }
+                }
+            }
+            lines.push(Centerline { points, width: line_width });
+        }
+        for i in indices {
+            mask[i] = false;
+        }
+    }
+    lines
+}
+
+/// 8-connected skeleton pixels, minus the diagonal steps that merely cut
+/// the corner of an L (those would make every staircase look like a junction).
+struct SkeletonGraph<'a> {
+    skeleton: &'a [bool],
+    width: usize,
+    height: usize,
+}
+
+impl SkeletonGraph<'_> {
+    fn set(&self, x: isize, y: isize) -> bool {
+        x >= 0
+            && y >= 0
+            && (x as usize) < self.width
+            && (y as usize) < self.height
+            && self.skeleton[y as usize * self.width + x as usize]
+    }
+
+    fn neighbors(&self, idx: usize) -> Vec<usize> {
+        let (x, y) = ((idx % self.width) as isize, (idx / self.width) as isize);
+        let mut out = Vec::with_capacity(4);
+        for (dx, dy) in [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)] {
+            let diagonal = dx != 0 && dy != 0;
+            if self.set(x + dx, y + dy) && !(diagonal && (self.set(x + dx, y) || self.set(x, y + dy))) {
+                out.push((y + dy) as usize * self.width + (x + dx) as usize);
+            }
+        }
+        out
+    }
+
+    /// Pixel runs between line ends and junctions (each branch starts and
+    /// ends on such a node), plus closed loops that have neither.
+    fn branches(&self, bones: &[usize]) -> Vec<Vec<usize>> {
+        let is_node = |i: usize| self.neighbors(i).len() != 2;
+        let mut walked = std::collections::HashSet::new();
+        let mut on_branch = std::collections::HashSet::new();
+        let mut branches = Vec::new();
+        let walk = |from: usize, first: usize, walked: &mut std::collections::HashSet<(usize, usize)>| {
+            let mut branch = vec![from, first];
+            walked.insert((from.min(first), from.max(first)));
+            let (mut prev, mut cur) = (from, first);
+            while !is_node(cur) && cur != from {
+                let Some(next) = self.neighbors(cur).into_iter().find(|&n| n != prev) else { break };
+                if !walked.insert((cur.min(next), cur.max(next))) {
+                    break;
+                }
+                branch.push(next);
+                (prev, cur) = (cur, next);
+            }
+            branch
+        };
+
+        for &node in bones.iter().filter(|&&i| is_node(i)) {
+            on_branch.insert(node);
+            for next in self.neighbors(node) {
+                if !walked.contains(&(node.min(next), node.max(next))) {
+                    let branch = walk(node, next, &mut walked);
+                    on_branch.extend(branch.iter().copied());
+                    branches.push(branch);
+                }
+            }
+        }
+        for &bone in bones {
+            if on_branch.contains(&bone) {
+                continue;
+            }
+            if let Some(&next) = self.neighbors(bone).first() {
+                let branch = walk(bone, next, &mut walked);
+                on_branch.extend(branch.iter().copied());
+                branches.push(branch);
+            } else {
+                branches.push(vec![bone]);
+            }
+        }
+        branches
+    }
+}
+
+/// Join branches that pass straight through a junction, straightest pair
+/// first, until no pair at any junction is close enough to opposite.
+fn join_branches(mut branches: Vec<Vec<usize>>, graph: &SkeletonGraph) -> Vec<Vec<usize>> {
+    let mut junctions: Vec<usize> = branches
+        .iter()
+        .flat_map(|b| [b[0], b[b.len() - 1]])
+        .filter(|&i| graph.neighbors(i).len() >= 3)
+        .collect();
+    junctions.sort_unstable();
+    junctions.dedup();
+    // Knot id per junction pixel: first pixel of its cluster
+    let mut knot: std::collections::HashMap<usize, usize> = std::collections::HashMap::new();
+    for &j in &junctions {
+        if knot.contains_key(&j) {
+            continue;
+        }
+        let mut stack = vec![j];
+        knot.insert(j, j);
+        while let Some(cur) = stack.pop() {
+            for &other in &junctions {
+                let near = (cur % graph.width).abs_diff(other % graph.width) <= JUNCTION_RADIUS
+                    && (cur / graph.width).abs_diff(other / graph.width) <= JUNCTION_RADIUS;
+                if near && !knot.contains_key(&other) {
+                    knot.insert(other, j);
+                    stack.push(other);
+                }
+            }
+        }
+    }
+    let knot_of = |i: usize| knot.get(&i).copied();
+    // Stubs running from a knot back into it are part of the knot
+    if branches.len() > 1 {
+        branches.retain(|b| {
+            let (a, z) = (knot_of(b[0]), knot_of(b[b.len() - 1]));
+            !(a.is_some() && a == z && b[0] != b[b.len() - 1] && b.len() <= 2 * JUNCTION_RADIUS + 1)
+        });
+    }
+
+    let direction = |branch: &[usize], at_start: bool| -> (f64, f64) {
+        let k = DIRECTION_SAMPLES.min(branch.len() - 1);
+        let (end, inner) = if at_start {
+            (branch[0], branch[k])
+        } else {
+            (branch[branch.len() - 1], branch[branch.len() - 1 - k])
+        };
+        let (ex, ey) = ((end % graph.width) as f64, (end / graph.width) as f64);
+        let (ix, iy) = ((inner % graph.width) as f64, (inner / graph.width) as f64);
+        let len = (ix - ex).hypot(iy - ey).max(f64::EPSILON);
+        ((ix - ex) / len, (iy - ey) / len)
+    };
+    loop {
+        // (cosine, branch a, a joins at its start, branch b, b joins at its start)
+        let mut best: Option<(f64, usize, bool, usize, bool)> = None;
+        for a in 0..branches.len() {
+            for b in a + 1..branches.len() {
+                for a_start in [true, false] {
+                    for b_start in [true, false] {
+                        let (ba, bb) = (&branches[a], &branches[b]);
+                        if ba.len() < 2 || bb.len() < 2 || ba.first() == ba.last() || bb.first() == bb.last() {
+                            continue;
+                        }
+                        let end_a = if a_start { ba[0] } else { ba[ba.len() - 1] };
+                        let end_b = if b_start { bb[0] } else { bb[bb.len() - 1] };
+                        if knot_of(end_a).is_none() || knot_of(end_a) != knot_of(end_b) {
+                            continue;
+                        }
+                        let (da, db) = (direction(ba, a_start), direction(bb, b_start));
+                        let cosine = da.0 * db.0 + da.1 * db.1;
+                        if cosine < JOIN_COSINE && best.is_none_or(|(c, ..)| cosine < c) {
+                            best = Some((cosine, a, a_start, b, b_start));
+                        }
+                    }
+                }
+            }
+        }
+        let Some((_, a, a_start, b, b_start)) = best else {
+            return branches;
+        };
+        let mut second = branches.remove(b);
+        let mut first = branches.remove(a);
+        if a_start {
+            first.reverse();
+        }
+        if !b_start {
+            second.reverse();
+        }
+        let skip = (first.last() == second.first()) as usize;
+        first.extend_from_slice(&second[skip..]);
+        branches.push(first);
+    }
+}
+
+/// Push the point at `end` (first or last) out along the line by `by` px,
+/// staying inside the image.
+fn extend_end(points: &mut [Point], end: usize, by: f64, width: f64, height: f64) {
+    let inner = if end == 0 { points.len().min(DIRECTION_SAMPLES + 1) - 1 } else { end.saturating_sub(DIRECTION_SAMPLES) };
+    let (tip, from) = (&points[end], &points[inner]);
+    let (dx, dy) = (tip.x - from.x, tip.y - from.y);
+    let len = dx.hypot(dy);
+    if len > 0.0 {
+        points[end] = Point {
+            x: (tip.x + dx / len * by).clamp(0.0, width),
+            y: (tip.y + dy / len * by).clamp(0.0, height),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plus(size: usize, arm: std::ops::Range<usize>) -> Vec<bool> {
+        (0..size * size)
+            .map(|i| {
+                let (x, y) = (i % size, i / size);
+                let inset = 4..size - 4;
+                (arm.contains(&y) && inset.contains(&x)) || (arm.contains(&x) && inset.contains(&y))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plus_gives_two_crossing_lines() {
+        let mut mask = plus(41, 19..22);
+        let lines = extract_centerlines(&mut mask, 41, 41, 6.0);
+        assert_eq!(lines.len(), 2, "{:?}", lines);
+        assert!(mask.iter().all(|&m| !m), "thin pixels should leave the mask");
+        for line in &lines {
+            assert!((line.width - 3.0).abs() < 1.0, "width {}", line.width);
+            let (first, last) = (&line.points[0], line.points.last().unwrap());
+            let span = (last.x - first.x).abs().max((last.y - first.y).abs());
+            assert!(span > 30.0, "line should cross the whole plus: {:?}", line.points);
+        }
+    }
+
+    #[test]
+    fn test_broad_regions_stay_filled() {
+        let (w, h) = (30, 30);
+        let mut mask: Vec<bool> = (0..w * h).map(|i| (5..25).contains(&(i % w)) && (5..25).contains(&(i / w))).collect();
+        let before = mask.clone();
+        assert!(extract_centerlines(&mut mask, w, h, 6.0).is_empty());
+        assert_eq!(mask, before);
+    }
+}
@@ -1,5 +1,9 @@
-use clap::Parser;
-use std::path::PathBuf;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+pub use img2svg::image_processor::is_supported_image;
+use img2svg::{EnhancedOptions, PreprocessOptions};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "img2svg")]
@@ -7,13 +11,21 @@ use std::path::PathBuf;
 #[command(version)]
 pub struct Cli {
     /// Input image file or directory (batch mode)
-    #[arg(short, long)]
-    pub input: PathBuf,
+    #[arg(short, long, required_unless_present = "stdin")]
+    pub input: Option<PathBuf>,
 
-    /// Output SVG file or directory (batch mode)
+    /// Output SVG file or directory (batch mode); `-` writes to stdout
     #[arg(short, long)]
     pub output: Option<PathBuf>,
 
+    /// Read the input image bytes from stdin instead of --input
+    #[arg(long)]
+    pub stdin: bool,
+
+    /// Write the SVG to stdout instead of a file
+    #[arg(long)]
+    pub stdout: bool,
+
     /// Maximum image dimension (auto-resize larger images to prevent OOM)
     #[arg(long, default_value = "4096")]
     pub max_size: u32,
@@ -45,16 +57,266 @@ pub struct Cli {
     /// Use original pipeline (line segments, RDP simplification) instead of default Bézier
     #[arg(long)]
     pub original: bool,
+
+    /// Worker threads for the enhanced pipeline (default: one per core)
+    #[arg(long, value_name = "N")]
+    pub threads: Option<usize>,
+
+    /// Also write one SVG per color next to the output (`<name>_rrggbb.svg`)
+    #[arg(long)]
+    pub separations: bool,
+
+    /// Draw thin strokes of line drawings as stroked centerlines instead of filled outlines
+    #[arg(long)]
+    pub centerline: bool,
+
+    /// Record the version, source file name and options in an SVG comment
+    #[arg(long)]
+    pub embed_provenance: bool,
+
+    /// Print conversion statistics as one JSON line on stdout after converting
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Load options from a TOML file; explicit flags override its values
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Enhanced pipeline options from the config file's `[enhanced]` table
+    #[arg(skip)]
+    pub enhanced: Option<EnhancedOptions>,
+
+    /// Preprocessing options from the config file's `[preprocessing]` table
+    #[arg(skip)]
+    pub preprocessing: Option<PreprocessOptions>,
+
+    /// Whether `colors` was set on the command line or at the config top level
+    #[arg(skip)]
+    colors_explicit: bool,
+}
+
+/// Options loaded from a `--config` TOML file. Top-level keys mirror the
+/// CLI flags; `[enhanced]` and `[preprocessing]` hold library options.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub max_size: Option<u32>,
+    pub colors: Option<usize>,
+    pub threshold: Option<f64>,
+    pub smooth: Option<u8>,
+    pub hierarchical: Option<bool>,
+    pub advanced: Option<bool>,
+    pub preprocess: Option<bool>,
+    pub original: Option<bool>,
+    pub enhanced: Option<EnhancedOptions>,
+    pub preprocessing: Option<PreprocessOptions>,
+}
+
+impl FileConfig {
+    /// Parse a config from TOML text.
+    pub fn from_toml_str(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// Read and parse a config file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read config {}: {}", path.display(), e))?;
+        Self::from_toml_str(&text).map_err(|e| format!("invalid config {}: {}", path.display(), e))
+    }
+}
+
+impl Cli {
+    /// Parse the process arguments and merge in `--config` if given.
+    pub fn parse_with_config() -> Result<Self, String> {
+        Self::from_matches(&Self::command().get_matches())
+    }
+
+    fn from_matches(matches: &ArgMatches) -> Result<Self, String> {
+        let mut cli = Self::from_arg_matches(matches).map_err(|e| e.to_string())?;
+        if let Some(path) = cli.config.clone() {
+            cli.apply_config(FileConfig::load(&path)?, matches);
+        }
+        cli.colors_explicit |= matches.value_source("colors") == Some(ValueSource::CommandLine);
+        Ok(cli)
+    }
+
+    /// Fill every option not given on the command line from `config`.
+    pub fn apply_config(&mut self, config: FileConfig, matches: &ArgMatches) {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        macro_rules! merge {
+            ($($field:ident),*) => {$(
+                if let Some(value) = config.$field && !from_cli(stringify!($field)) {
+                    self.$field = value;
+                }
+            )*};
+        }
+        merge!(max_size, colors, threshold, smooth, hierarchical, advanced, preprocess, original);
+        self.colors_explicit = config.colors.is_some() || from_cli("colors");
+        self.enhanced = config.enhanced;
+        self.preprocessing = config.preprocessing;
+    }
+
+    /// Options for the enhanced pipeline: the config's `[enhanced]` table (or
+    /// defaults), with the color count and preprocessing flag applied.
+    pub fn enhanced_options(&self) -> EnhancedOptions {
+        let mut options = self.enhanced.clone().unwrap_or_default();
+        if self.enhanced.is_none() || self.colors_explicit {
+            options.num_colors = self.colors;
+        }
+        options.preprocess |= self.preprocess;
+        options.color_separations |= self.separations;
+        options.embed_provenance |= self.embed_provenance;
+        options.centerline |= self.centerline;
+        options
+    }
+
+    /// Options for `--preprocess`: the config's `[preprocessing]` table or the
+    /// photo preset.
+    pub fn preprocess_options(&self) -> PreprocessOptions {
+        self.preprocessing.clone().unwrap_or_else(PreprocessOptions::photo)
+    }
+
+    /// Whether the SVG goes to stdout (`--stdout` or `--output -`).
+    pub fn writes_stdout(&self) -> bool {
+        self.stdout || self.output.as_deref() == Some(std::path::Path::new("-"))
+    }
+
+    /// Check flag combinations clap cannot express on its own, and the
+    /// enhanced options they combine into with the config.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.stdin && self.input.is_some() {
+            return Err("--stdin cannot be combined with --input".to_string());
+        }
+        if self.stdin && self.output.is_none() && !self.stdout {
+            return Err("--stdin needs --output <file> or --stdout".to_string());
+        }
+        if self.writes_stdout() && self.input.as_deref().is_some_and(|p| p.is_dir()) {
+            return Err("batch mode cannot write to stdout".to_string());
+        }
+        if self.stats && self.writes_stdout() {
+            return Err("--stats cannot be combined with SVG on stdout".to_string());
+        }
+        if self.stats && self.original {
+            return Err("--stats is only supported by the default pipeline".to_string());
+        }
+        self.enhanced_options().validate().map_err(|e| format!("invalid options: {}", e))?;
+        Ok(())
+    }
 }
 
-/// Check if a file extension is a supported image format.
-pub fn is_supported_image(path: &std::path::Path) -> bool {
-    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-        matches!(
-            ext.to_lowercase().as_str(),
-            "bmp" | "png" | "jpg" | "jpeg" | "gif" | "ico" | "tiff" | "tif" | "webp" | "pnm" | "tga" | "dds" | "farbfeld"
-        )
-    } else {
-        false
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, String> {
+        let cli = Cli::try_parse_from(std::iter::once("img2svg").chain(args.iter().copied()))
+            .map_err(|e| e.to_string())?;
+        cli.validate()?;
+        Ok(cli)
+    }
+
+    #[test]
+    fn test_input_required_without_stdin() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["-i", "in.png"]).is_ok());
+    }
+
+    #[test]
+    fn test_stdin_to_stdout() {
+        let cli = parse(&["--stdin", "--stdout"]).unwrap();
+        assert!(cli.input.is_none());
+        assert!(cli.writes_stdout());
+    }
+
+    #[test]
+    fn test_output_dash_means_stdout() {
+        let cli = parse(&["--stdin", "-o", "-"]).unwrap();
+        assert!(cli.writes_stdout());
+        let cli = parse(&["-i", "in.png", "-o", "out.svg"]).unwrap();
+        assert!(!cli.writes_stdout());
+    }
+
+    #[test]
+    fn test_stdin_rejects_input_and_missing_output() {
+        assert!(parse(&["--stdin", "-i", "in.png", "--stdout"]).is_err());
+        assert!(parse(&["--stdin"]).is_err());
+        assert!(parse(&["--stdin", "-o", "out.svg"]).is_ok());
+    }
+
+    #[test]
+    fn test_stats_flag() {
+        let cli = parse(&["-i", "in.png", "--stats"]).unwrap();
+        assert!(cli.stats);
+        assert!(!parse(&["-i", "in.png"]).unwrap().stats);
+        assert!(parse(&["-i", "in.png", "--stats", "--stdout"]).is_err());
+        assert!(parse(&["-i", "in.png", "--stats", "--original"]).is_err());
+    }
+
+    const SAMPLE_CONFIG: &str = r#"
+colors = 8
+smooth = 2
+preprocess = true
+
+[enhanced]
+curve_tolerance = 1.25
+heal_gaps = true
+decimal_precision = 1
+
+[preprocessing]
+spatial_sigma = 4.0
+iterations = 3
+"#;
+
+    fn parse_with(args: &[&str], config: &str) -> Cli {
+        let matches = Cli::command()
+            .try_get_matches_from(std::iter::once("img2svg").chain(args.iter().copied()))
+            .unwrap();
+        let mut cli = Cli::from_matches(&matches).unwrap();
+        cli.apply_config(FileConfig::from_toml_str(config).unwrap(), &matches);
+        cli
+    }
+
+    #[test]
+    fn test_config_deserializes() {
+        let config = FileConfig::from_toml_str(SAMPLE_CONFIG).unwrap();
+        assert_eq!(config.colors, Some(8));
+        assert_eq!(config.smooth, Some(2));
+        assert_eq!(config.threshold, None);
+        let enhanced = config.enhanced.unwrap();
+        assert_eq!(enhanced.curve_tolerance, 1.25);
+        assert!(enhanced.heal_gaps);
+        assert_eq!(enhanced.decimal_precision, 1);
+        // Unset keys keep their defaults
+        assert_eq!(enhanced.min_region_area, EnhancedOptions::default().min_region_area);
+        let preprocessing = config.preprocessing.unwrap();
+        assert_eq!(preprocessing.spatial_sigma, 4.0);
+        assert_eq!(preprocessing.iterations, 3);
+
+        assert!(FileConfig::from_toml_str("colour = 3").is_err());
+    }
+
+    #[test]
+    fn test_cli_flags_override_config() {
+        let cli = parse_with(&["-i", "in.png"], SAMPLE_CONFIG);
+        assert_eq!(cli.colors, 8);
+        assert_eq!(cli.smooth, 2);
+        assert!(cli.preprocess);
+        assert_eq!(cli.threshold, 0.1);
+        let options = cli.enhanced_options();
+        assert_eq!(options.num_colors, 8);
+        assert_eq!(options.curve_tolerance, 1.25);
+        assert_eq!(cli.preprocess_options().iterations, 3);
+
+        let cli = parse_with(&["-i", "in.png", "-c", "4", "-s", "7"], SAMPLE_CONFIG);
+        assert_eq!(cli.colors, 4);
+        assert_eq!(cli.smooth, 7);
+        assert_eq!(cli.enhanced_options().num_colors, 4);
+    }
+
+    #[test]
+    fn test_is_supported_image() {
+        assert!(is_supported_image(std::path::Path::new("a.PNG")));
+        assert!(!is_supported_image(std::path::Path::new("a.svg")));
     }
 }
@@ -0,0 +1,102 @@
+//! Reusable, thread-safe converter holding validated options.
+//!
+//! Build one [`Converter`] up front and share it (e.g. behind an `Arc`)
+//! across a thread pool instead of re-validating options per image.
+
+use crate::enhanced_vectorizer::{
+    generate_enhanced_svg_with_options, vectorize_enhanced, EnhancedOptions, EnhancedVectorData,
+};
+use crate::image_processor::ImageData;
+use anyhow::Result;
+
+/// Enhanced-pipeline converter with options validated at construction.
+#[derive(Debug, Clone)]
+pub struct Converter {
+    options: EnhancedOptions,
+}
+
+// Converter is shared across threads by design; keep it that way.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Converter>();
+    assert_send_sync::<EnhancedVectorData>();
+};
+
+impl Converter {
+    /// Validate `options` and build a converter from them.
+    pub fn new(options: EnhancedOptions) -> Result<Self> {
+        options.validate()?;
+        Ok(Self { options })
+    }
+
+    /// The options this converter was built with.
+    pub fn options(&self) -> &EnhancedOptions {
+        &self.options
+    }
+
+    /// Vectorize one image.
+    pub fn convert_image(&self, image_data: &ImageData) -> Result<EnhancedVectorData> {
+        vectorize_enhanced(image_data, &self.options)
+    }
+
+    /// Vectorize one image and render it to an SVG string.
+    pub fn convert_to_svg_string(&self, image_data: &ImageData) -> Result<String> {
+        let data = self.convert_image(image_data)?;
+        Ok(generate_enhanced_svg_with_options(&data, &self.options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rgb::RGBA8;
+    use std::sync::Arc;
+
+    fn two_tone(w: u32, h: u32, color: RGBA8) -> ImageData {
+        let pixels = (0..w * h)
+            .map(|i| if i % w < w / 2 { color } else { RGBA8::new(255, 255, 255, 255) })
+            .collect();
+        ImageData { width: w, height: h, pixels }
+    }
+
+    #[test]
+    fn test_rejects_invalid_options() {
+        let options = EnhancedOptions { curve_tolerance: 0.0, ..Default::default() };
+        assert!(Converter::new(options).is_err());
+        let options = EnhancedOptions { corner_threshold: f64::NAN, ..Default::default() };
+        assert!(Converter::new(options).is_err());
+        assert!(Converter::new(EnhancedOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_shared_across_threads() {
+        let converter = Arc::new(
+            Converter::new(EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() })
+                .unwrap(),
+        );
+        let colors = [
+            RGBA8::new(200, 0, 0, 255),
+            RGBA8::new(0, 150, 0, 255),
+            RGBA8::new(0, 0, 200, 255),
+            RGBA8::new(90, 90, 90, 255),
+        ];
+        let handles: Vec<_> = colors
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| {
+                let converter = Arc::clone(&converter);
+                std::thread::spawn(move || {
+                    let size = 16 + 4 * i as u32;
+                    let data = converter.convert_image(&two_tone(size, size, color)).unwrap();
+                    (size, data)
+                })
+            })
+            .collect();
+
+        for (handle, color) in handles.into_iter().zip(colors) {
+            let (size, data) = handle.join().unwrap();
+            assert_eq!(data.width, size);
+            assert!(data.paths.iter().any(|p| p.color == (color.r, color.g, color.b, color.a)));
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! `data:` URIs for embedding generated SVG directly in HTML or CSS.
+
+use crate::enhanced_vectorizer::{generate_enhanced_svg_with_options, vectorize_enhanced, EnhancedOptions};
+use crate::image_processor::load_image;
+use anyhow::Result;
+use base64::Engine;
+use std::path::Path;
+
+/// How the SVG is packed into a data URI.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DataUriEncoding {
+    /// `data:image/svg+xml;base64,...`: works everywhere
+    #[default]
+    Base64,
+    /// `data:image/svg+xml;utf8,...` with unsafe characters percent-encoded:
+    /// usually smaller, since most of an SVG needs no escaping
+    Utf8,
+}
+
+/// Convert an image file with the enhanced pipeline and return the SVG as
+/// a base64 data URI. Use [`svg_data_uri`] on the SVG string for the
+/// percent-encoded variant.
+pub fn convert_to_data_uri(input: &Path, options: &EnhancedOptions) -> Result<String> {
+    options.validate()?;
+    let options = &EnhancedOptions { source_path: Some(input.to_path_buf()), ..options.clone() };
+    let image_data = load_image(input)?;
+    let data = vectorize_enhanced(&image_data, options)?;
+    let svg = generate_enhanced_svg_with_options(&data, options);
+    Ok(svg_data_uri(&svg, DataUriEncoding::Base64))
+}
+
+/// Wrap SVG markup in a `data:image/svg+xml` URI.
+pub fn svg_data_uri(svg: &str, encoding: DataUriEncoding) -> String {
+    match encoding {
+        DataUriEncoding::Base64 => {
+            format!("data:image/svg+xml;base64,{}", base64::engine::general_purpose::STANDARD.encode(svg))
+        }
+        DataUriEncoding::Utf8 => {
+            let mut uri = String::with_capacity(svg.len() + 24);
+            uri.push_str("data:image/svg+xml;utf8,");
+            for &byte in svg.as_bytes() {
+                if byte.is_ascii_alphanumeric() || b"-_.!~*'()/:;=,+@?&$".contains(&byte) {
+                    uri.push(byte as char);
+                } else {
+                    uri.push_str(&format!("%{:02X}", byte));
+                }
+            }
+            uri
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::validate_svg_wellformed;
+
+    const SVG: &str = "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"2\" height=\"2\">\n  <path fill=\"#c80000\" d=\"M0,0L2,0L2,2Z\"/>\n</svg>\n";
+
+    fn percent_decode(text: &str) -> String {
+        let bytes = text.as_bytes();
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%' {
+                out.push(u8::from_str_radix(&text[i + 1..i + 3], 16).unwrap());
+                i += 3;
+            } else {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_svg_data_uri_round_trips() {
+        let uri = svg_data_uri(SVG, DataUriEncoding::Base64);
+        let payload = uri.strip_prefix("data:image/svg+xml;base64,").unwrap();
+        let decoded = base64::engine::general_purpose::STANDARD.decode(payload).unwrap();
+        assert_eq!(String::from_utf8(decoded).unwrap(), SVG);
+
+        let uri = svg_data_uri(SVG, DataUriEncoding::Utf8);
+        let payload = uri.strip_prefix("data:image/svg+xml;utf8,").unwrap();
+        assert!(!payload.contains(['<', '>', '"', '#', ' ', '\n']));
+        assert_eq!(percent_decode(payload), SVG);
+        validate_svg_wellformed(&percent_decode(payload)).unwrap();
+    }
+
+    #[test]
+    fn test_convert_to_data_uri() {
+        let path = std::env::temp_dir().join("img2svg_data_uri.png");
+        let mut img = image::RgbaImage::from_pixel(20, 20, image::Rgba([255, 255, 255, 255]));
+        for y in 5..15 {
+            for x in 5..15 {
+                img.put_pixel(x, y, image::Rgba([200, 0, 0, 255]));
+            }
+        }
+        img.save(&path).unwrap();
+
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+        let uri = convert_to_data_uri(&path, &options).unwrap();
+        assert!(uri.starts_with("data:image/svg+xml"));
+        let payload = uri.strip_prefix("data:image/svg+xml;base64,").unwrap();
+        let svg = String::from_utf8(base64::engine::general_purpose::STANDARD.decode(payload).unwrap()).unwrap();
+        validate_svg_wellformed(&svg).unwrap();
+        assert!(svg.contains("#c80000"));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -5,9 +5,12 @@
 //! palette quality and cleaner region boundaries.
 
 use crate::edge_detector::EdgeMap;
-use crate::image_processor::ImageData;
-use rand::Rng;
+use crate::image_processor::{ImageData, MAX_COLORS};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rgb::RGBA8;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
 
 /// Perceptual color distance squared (weighted RGB, approximates human vision).
 /// Weights: R=2, G=4, B=3 (green most sensitive).
@@ -19,31 +22,117 @@ pub fn perceptual_dist_sq(a: &RGBA8, b: &RGBA8) -> i32 {
     2 * dr * dr + 4 * dg * dg + 3 * db * db
 }
 
+/// Space in which k-means measures color distance.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorSpace {
+    /// Weighted RGB of [`perceptual_dist_sq`]: cheap, fine for graphics
+    #[default]
+    WeightedRgb,
+    /// CIELAB (D65), distance squared ΔE*ab: ranks perceptual differences
+    /// better, which gives cleaner palettes for photographs
+    Lab,
+}
+
+impl ColorSpace {
+    /// Coordinates of `c` in this space (alpha is ignored).
+    pub fn point(self, c: &RGBA8) -> [f64; 3] {
+        match self {
+            ColorSpace::WeightedRgb => [c.r as f64, c.g as f64, c.b as f64],
+            ColorSpace::Lab => srgb_to_lab(c),
+        }
+    }
+
+    /// Squared distance between two points of this space; for
+    /// [`ColorSpace::WeightedRgb`] exactly [`perceptual_dist_sq`].
+    #[inline]
+    pub fn dist_sq(self, a: &[f64; 3], b: &[f64; 3]) -> f64 {
+        let w = match self {
+            ColorSpace::WeightedRgb => [2.0, 4.0, 3.0],
+            ColorSpace::Lab => [1.0, 1.0, 1.0],
+        };
+        (0..3).map(|i| w[i] * (a[i] - b[i]).powi(2)).sum()
+    }
+
+    fn points(self, colors: &[RGBA8]) -> Vec<[f64; 3]> {
+        colors.iter().map(|c| self.point(c)).collect()
+    }
+}
+
+/// CIELAB coordinates of an sRGB color under the D65 white point.
+fn srgb_to_lab(c: &RGBA8) -> [f64; 3] {
+    static LINEAR: OnceLock<[f64; 256]> = OnceLock::new();
+    let linear = LINEAR.get_or_init(|| {
+        std::array::from_fn(|v| {
+            let v = v as f64 / 255.0;
+            if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+        })
+    });
+    let (r, g, b) = (linear[c.r as usize], linear[c.g as usize], linear[c.b as usize]);
+    let x = (0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / 0.950_47;
+    let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+    let z = (0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b) / 1.088_83;
+    let f = |t: f64| if t > 216.0 / 24389.0 { t.cbrt() } else { (24389.0 / 27.0 * t + 16.0) / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
 /// K-means++ initialization: choose centroids with probability proportional
-/// to squared distance from nearest existing centroid.
-fn kmeans_plusplus_init(samples: &[RGBA8], k: usize) -> Vec<RGBA8> {
-    let mut rng = rand::thread_rng();
+/// to squared distance from nearest existing centroid (times the sample's
+/// weight, when given). `points` are the samples in `space`. `pinned`
+/// colors come first and count as already chosen; the result holds at
+/// least all of them.
+fn kmeans_plusplus_init(
+    samples: &[RGBA8],
+    points: &[[f64; 3]],
+    space: ColorSpace,
+    weights: Option<&[u64]>,
+    pinned: &[RGBA8],
+    k: usize,
+    rng: &mut StdRng,
+) -> Vec<RGBA8> {
     let n = samples.len();
-    if n == 0 || k == 0 {
-        return Vec::new();
+    let mut centroids = pinned.to_vec();
+    if n == 0 || k <= centroids.len() {
+        return centroids;
+    }
+    let weight = |i: usize| weights.map_or(1.0, |w| w[i] as f64);
+
+    centroids.reserve(k - centroids.len());
+    if centroids.is_empty() {
+        let first = match weights {
+            Some(w) => {
+                let mut rand_val = rng.gen_range(0..w.iter().sum::<u64>().max(1));
+                let mut pick = 0;
+                for (i, &wi) in w.iter().enumerate() {
+                    if rand_val < wi {
+                        pick = i;
+                        break;
+                    }
+                    rand_val -= wi;
+                }
+                pick
+            }
+            None => rng.gen_range(0..n),
+        };
+        centroids.push(samples[first]);
     }
 
-    let mut centroids = Vec::with_capacity(k);
-    centroids.push(samples[rng.gen_range(0..n)]);
-
-    let mut distances = vec![0.0f64; n];
+    let mut distances = vec![f64::INFINITY; n];
+    // Centroids before this index are already folded into `distances`
+    let mut measured = 0;
 
-    for iteration in 1..k {
-        let new_cent = centroids.last().unwrap();
+    while centroids.len() < k {
         let mut total_dist = 0.0f64;
 
-        for (i, sample) in samples.iter().enumerate() {
-            let dist_sq = perceptual_dist_sq(sample, new_cent) as f64;
-            if iteration == 1 || dist_sq < distances[i] {
-                distances[i] = dist_sq;
+        let new_points = space.points(&centroids[measured..]);
+        for (i, point) in points.iter().enumerate() {
+            for c in &new_points {
+                distances[i] = distances[i].min(space.dist_sq(point, c) * weight(i));
             }
             total_dist += distances[i];
         }
+        measured = centroids.len();
 
         if total_dist == 0.0 {
             break;
@@ -67,36 +156,98 @@ fn kmeans_plusplus_init(samples: &[RGBA8], k: usize) -> Vec<RGBA8> {
     centroids
 }
 
-/// Refine palette using k-means iterations with perceptual distance.
-fn kmeans_refine(mut palette: Vec<RGBA8>, samples: &[RGBA8], iterations: usize) -> Vec<RGBA8> {
+/// Convergence control for k-means palette refinement.
+#[derive(Debug, Clone)]
+pub struct KmeansOptions {
+    /// Maximum number of refinement iterations
+    pub max_iterations: usize,
+    /// Stop once no centroid moves more than this (RGB distance)
+    pub epsilon: f64,
+    /// Cluster a color histogram of every pixel (bucket means weighted by
+    /// count) instead of a uniform pixel sample, so small distinct objects
+    /// are never skipped by the sampling stride
+    pub histogram: bool,
+    /// Colors forced into the palette as fixed centroids: k-means never
+    /// moves them, so they are reproduced exactly
+    pub pinned: Vec<RGBA8>,
+    /// Space distances are measured in, for clustering and for mapping
+    /// pixels to the palette
+    pub color_space: ColorSpace,
+    /// Map pixels to the palette with Floyd–Steinberg error diffusion
+    /// instead of plain nearest color, so gradients become a mix of palette
+    /// colors rather than bands. Majority-vote smoothing in
+    /// [`quantize_edge_aware_with_kmeans`] works against this: each pass
+    /// folds the mix on flat areas back into solid regions, so use few or
+    /// no passes to keep the dither
+    pub dither: bool,
+    /// Seed for k-means++ initialization, for reproducible palettes;
+    /// None seeds from entropy
+    pub seed: Option<u64>,
+    /// Use exactly these colors as the palette and skip k-means: pixels map
+    /// to the nearest one (by [`perceptual_dist_sq`] in the default color
+    /// space). `num_colors` and `pinned` are ignored
+    pub fixed_palette: Option<Vec<RGBA8>>,
+}
+
+/// Random source for k-means++ initialization.
+fn kmeans_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+impl Default for KmeansOptions {
+    fn default() -> Self {
+        Self {
+            max_iterations: 8,
+            epsilon: 0.0,
+            histogram: false,
+            pinned: Vec::new(),
+            color_space: ColorSpace::WeightedRgb,
+            dither: false,
+            seed: None,
+            fixed_palette: None,
+        }
+    }
+}
+
+/// Refine palette using k-means iterations with perceptual distance, each
+/// sample counting `weights[i]` times when weights are given. `points` are
+/// the samples in `options.color_space`; centroids are RGB means. The first
+/// `options.pinned.len()` palette entries are the pinned colors and stay put.
+/// Returns the refined palette and the number of iterations run.
+fn kmeans_refine(
+    mut palette: Vec<RGBA8>,
+    samples: &[RGBA8],
+    points: &[[f64; 3]],
+    weights: Option<&[u64]>,
+    options: &KmeansOptions,
+) -> (Vec<RGBA8>, usize) {
     if palette.is_empty() || samples.is_empty() {
-        return palette;
+        return (palette, 0);
     }
 
-    for _ in 0..iterations {
+    let mut iterations = 0;
+    while iterations < options.max_iterations {
+        iterations += 1;
         let k = palette.len();
         let mut sums = vec![[0u64; 4]; k];
         let mut counts = vec![0u64; k];
-
-        for s in samples {
-            let mut best_idx = 0;
-            let mut best_dist = i32::MAX;
-            for (j, c) in palette.iter().enumerate() {
-                let d = perceptual_dist_sq(s, c);
-                if d < best_dist {
-                    best_dist = d;
-                    best_idx = j;
-                }
-            }
-            sums[best_idx][0] += s.r as u64;
-            sums[best_idx][1] += s.g as u64;
-            sums[best_idx][2] += s.b as u64;
-            sums[best_idx][3] += s.a as u64;
-            counts[best_idx] += 1;
+        let palette_points = options.color_space.points(&palette);
+
+        for (i, s) in samples.iter().enumerate() {
+            let w = weights.map_or(1, |w| w[i]);
+            let best_idx = nearest_palette_index(&points[i], &palette_points, options.color_space);
+            sums[best_idx][0] += s.r as u64 * w;
+            sums[best_idx][1] += s.g as u64 * w;
+            sums[best_idx][2] += s.b as u64 * w;
+            sums[best_idx][3] += s.a as u64 * w;
+            counts[best_idx] += w;
         }
 
-        let mut changed = false;
-        for (j, c) in palette.iter_mut().enumerate() {
+        let mut max_shift = 0.0f64;
+        for (j, c) in palette.iter_mut().enumerate().skip(options.pinned.len()) {
             if counts[j] == 0 {
                 continue;
             }
@@ -107,27 +258,57 @@ fn kmeans_refine(mut palette: Vec<RGBA8>, samples: &[RGBA8], iterations: usize)
                 (sums[j][2] / n) as u8,
                 (sums[j][3] / n) as u8,
             );
-            if c.r != new_c.r || c.g != new_c.g || c.b != new_c.b {
-                changed = true;
+            let dr = c.r as f64 - new_c.r as f64;
+            let dg = c.g as f64 - new_c.g as f64;
+            let db = c.b as f64 - new_c.b as f64;
+            let shift = (dr * dr + dg * dg + db * db).sqrt();
+            if shift > 0.0 {
+                max_shift = max_shift.max(shift);
                 *c = new_c;
             }
         }
 
-        if !changed {
+        if max_shift <= options.epsilon {
             break;
         }
     }
 
-    palette
+    (palette, iterations)
+}
+
+/// Bucket pixels by the top 5 bits of each channel. Returns the mean color
+/// of each non-empty bucket and its pixel count.
+fn color_histogram(pixels: &[RGBA8]) -> (Vec<RGBA8>, Vec<u64>) {
+    let mut buckets: std::collections::HashMap<u32, ([u64; 4], u64)> = std::collections::HashMap::new();
+    for p in pixels {
+        let key = (p.r as u32 >> 3) << 15 | (p.g as u32 >> 3) << 10 | (p.b as u32 >> 3) << 5 | p.a as u32 >> 3;
+        let (sum, count) = buckets.entry(key).or_insert(([0; 4], 0));
+        sum[0] += p.r as u64;
+        sum[1] += p.g as u64;
+        sum[2] += p.b as u64;
+        sum[3] += p.a as u64;
+        *count += 1;
+    }
+    // Fixed order so results do not depend on hash iteration
+    let mut buckets: Vec<_> = buckets.into_iter().collect();
+    buckets.sort_unstable_by_key(|(key, _)| *key);
+    buckets
+        .into_iter()
+        .map(|(_, (sum, n))| {
+            let mean = RGBA8::new((sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8, (sum[3] / n) as u8);
+            (mean, n)
+        })
+        .unzip()
 }
 
-/// Find nearest palette color index using perceptual distance.
+/// Find nearest palette color index, with the pixel and palette given as
+/// points of `space`.
 #[inline]
-fn nearest_palette_index(pixel: &RGBA8, palette: &[RGBA8]) -> usize {
+fn nearest_palette_index(pixel: &[f64; 3], palette: &[[f64; 3]], space: ColorSpace) -> usize {
     let mut best_idx = 0usize;
-    let mut best_dist = i32::MAX;
+    let mut best_dist = f64::INFINITY;
     for (idx, c) in palette.iter().enumerate() {
-        let d = perceptual_dist_sq(pixel, c);
+        let d = space.dist_sq(pixel, c);
         if d < best_dist {
             best_dist = d;
             best_idx = idx;
@@ -142,30 +323,61 @@ pub fn quantize_enhanced(
     image_data: &ImageData,
     num_colors: usize,
 ) -> (ImageData, Vec<usize>, Vec<RGBA8>) {
-    let n_pixels = image_data.pixels.len();
-
-    // Downsample for palette building: cap at 100K samples
-    let sample_step = (n_pixels / 100_000).max(1);
-    let samples: Vec<RGBA8> = image_data
-        .pixels
-        .iter()
-        .step_by(sample_step)
-        .copied()
-        .collect();
+    let (quantized, indices, palette, _) =
+        quantize_enhanced_with_kmeans(image_data, num_colors, &KmeansOptions::default());
+    (quantized, indices, palette)
+}
 
-    // K-means++ init → k-means refinement (8 iterations)
-    let initial_palette = kmeans_plusplus_init(&samples, num_colors);
-    let palette = kmeans_refine(initial_palette, &samples, 8);
+/// [`quantize_enhanced`] with explicit k-means convergence control.
+/// Returns (quantized image, palette indices, palette, k-means iterations run).
+/// `num_colors` above [`MAX_COLORS`] is clamped to it.
+pub fn quantize_enhanced_with_kmeans(
+    image_data: &ImageData,
+    num_colors: usize,
+    kmeans: &KmeansOptions,
+) -> (ImageData, Vec<usize>, Vec<RGBA8>, usize) {
+    let n_pixels = image_data.pixels.len();
+    let num_colors = num_colors.min(MAX_COLORS);
+
+    let space = kmeans.color_space;
+    let mut rng = kmeans_rng(kmeans.seed);
+
+    // K-means++ init → k-means refinement
+    let (palette, iterations) = if let Some(fixed) = kmeans.fixed_palette.as_ref().filter(|f| !f.is_empty()) {
+        (fixed.clone(), 0)
+    } else if kmeans.histogram {
+        let (samples, weights) = color_histogram(&image_data.pixels);
+        let points = space.points(&samples);
+        let initial_palette =
+            kmeans_plusplus_init(&samples, &points, space, Some(&weights), &kmeans.pinned, num_colors, &mut rng);
+        kmeans_refine(initial_palette, &samples, &points, Some(&weights), kmeans)
+    } else {
+        // Downsample for palette building: cap at 100K samples
+        let sample_step = (n_pixels / 100_000).max(1);
+        let samples: Vec<RGBA8> = image_data
+            .pixels
+            .iter()
+            .step_by(sample_step)
+            .copied()
+            .collect();
+        let points = space.points(&samples);
+        let initial_palette =
+            kmeans_plusplus_init(&samples, &points, space, None, &kmeans.pinned, num_colors, &mut rng);
+        kmeans_refine(initial_palette, &samples, &points, None, kmeans)
+    };
 
     // Map each pixel to nearest palette color
-    let mut indices = vec![0usize; n_pixels];
-    let mut quantized_pixels = Vec::with_capacity(n_pixels);
-
-    for (i, pixel) in image_data.pixels.iter().enumerate() {
-        let idx = nearest_palette_index(pixel, &palette);
-        indices[i] = idx;
-        quantized_pixels.push(palette[idx]);
-    }
+    let indices = if kmeans.dither {
+        dither_indices(image_data, &palette, space)
+    } else {
+        let palette_points = space.points(&palette);
+        image_data
+            .pixels
+            .iter()
+            .map(|pixel| nearest_palette_index(&space.point(pixel), &palette_points, space))
+            .collect()
+    };
+    let quantized_pixels: Vec<RGBA8> = indices.iter().map(|&i| palette[i]).collect();
 
     let quantized = ImageData {
         width: image_data.width,
@@ -173,7 +385,47 @@ pub fn quantize_enhanced(
         pixels: quantized_pixels,
     };
 
-    (quantized, indices, palette)
+    (quantized, indices, palette, iterations)
+}
+
+/// Palette index of every pixel by Floyd–Steinberg error diffusion: each
+/// pixel's RGB error against its palette color is spread over the
+/// unvisited neighbors (7/16 right, 3/16 below left, 5/16 below, 1/16
+/// below right). Alpha is mapped as is.
+fn dither_indices(image_data: &ImageData, palette: &[RGBA8], space: ColorSpace) -> Vec<usize> {
+    let w = image_data.width as usize;
+    let h = image_data.height as usize;
+    let palette_points = space.points(palette);
+    let mut error = vec![[0.0f32; 3]; w * h];
+    let mut indices = vec![0usize; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            let i = y * w + x;
+            let p = image_data.pixels[i];
+            let channel = |v: u8, e: f32| (v as f32 + e).round().clamp(0.0, 255.0);
+            let wanted = [channel(p.r, error[i][0]), channel(p.g, error[i][1]), channel(p.b, error[i][2])];
+            let target = RGBA8::new(wanted[0] as u8, wanted[1] as u8, wanted[2] as u8, p.a);
+            let idx = nearest_palette_index(&space.point(&target), &palette_points, space);
+            indices[i] = idx;
+
+            let c = palette[idx];
+            let diff = [wanted[0] - c.r as f32, wanted[1] - c.g as f32, wanted[2] - c.b as f32];
+            let mut spread = |nx: usize, ny: usize, share: f32| {
+                if nx < w && ny < h {
+                    for (e, d) in error[ny * w + nx].iter_mut().zip(diff) {
+                        *e += d * share;
+                    }
+                }
+            };
+            spread(x + 1, y, 7.0 / 16.0);
+            if x > 0 {
+                spread(x - 1, y + 1, 3.0 / 16.0);
+            }
+            spread(x, y + 1, 5.0 / 16.0);
+            spread(x + 1, y + 1, 1.0 / 16.0);
+        }
+    }
+    indices
 }
 
 /// Edge-aware quantization: after initial quantization, apply majority-vote
@@ -185,7 +437,29 @@ pub fn quantize_edge_aware(
     edge_threshold: u8,
     num_passes: usize,
 ) -> (ImageData, Vec<usize>, Vec<RGBA8>) {
-    let (_, mut indices, palette) = quantize_enhanced(image_data, num_colors);
+    let (quantized, indices, palette, _) = quantize_edge_aware_with_kmeans(
+        image_data,
+        num_colors,
+        edges,
+        edge_threshold,
+        num_passes,
+        &KmeansOptions::default(),
+    );
+    (quantized, indices, palette)
+}
+
+/// [`quantize_edge_aware`] with explicit k-means convergence control.
+/// Also returns the number of k-means iterations run.
+pub fn quantize_edge_aware_with_kmeans(
+    image_data: &ImageData,
+    num_colors: usize,
+    edges: &EdgeMap,
+    edge_threshold: u8,
+    num_passes: usize,
+    kmeans: &KmeansOptions,
+) -> (ImageData, Vec<usize>, Vec<RGBA8>, usize) {
+    let (_, mut indices, palette, iterations) =
+        quantize_enhanced_with_kmeans(image_data, num_colors, kmeans);
 
     let w = image_data.width as usize;
     let h = image_data.height as usize;
@@ -244,7 +518,66 @@ pub fn quantize_edge_aware(
         pixels: quantized_pixels,
     };
 
-    (quantized, indices, palette)
+    (quantized, indices, palette, iterations)
+}
+
+/// Pick a color count by the elbow method: run k-means for a ladder of k
+/// values up to `max_colors` and choose the k where the distortion curve
+/// bends the most (farthest point from the chord between its ends).
+pub fn elbow_color_count(image_data: &ImageData, max_colors: usize) -> usize {
+    elbow_color_count_seeded(image_data, max_colors, None)
+}
+
+/// [`elbow_color_count`] with k-means++ seeded by `seed`.
+pub(crate) fn elbow_color_count_seeded(image_data: &ImageData, max_colors: usize, seed: Option<u64>) -> usize {
+    let max_colors = max_colors.max(1);
+    let sample_step = (image_data.pixels.len() / 10_000).max(1);
+    let samples: Vec<RGBA8> = image_data.pixels.iter().step_by(sample_step).copied().collect();
+    if samples.is_empty() {
+        return 1;
+    }
+
+    let mut ks: Vec<usize> = [1, 2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32, 48, 64, 96, 128, 192, 256]
+        .into_iter()
+        .filter(|&k| k < max_colors)
+        .collect();
+    ks.push(max_colors);
+
+    let space = ColorSpace::WeightedRgb;
+    let points = space.points(&samples);
+    let mut curve: Vec<(usize, f64)> = Vec::with_capacity(ks.len());
+    let mut rng = kmeans_rng(seed);
+    for k in ks {
+        let initial = kmeans_plusplus_init(&samples, &points, space, None, &[], k, &mut rng);
+        let (palette, _) = kmeans_refine(initial, &samples, &points, None, &KmeansOptions::default());
+        let palette_points = space.points(&palette);
+        let distortion: f64 = points
+            .iter()
+            .map(|p| space.dist_sq(p, &palette_points[nearest_palette_index(p, &palette_points, space)]))
+            .sum();
+        // An exact fit cannot be improved on by more colors
+        if distortion == 0.0 {
+            return k;
+        }
+        curve.push((k, distortion));
+    }
+
+    let (k0, d0) = curve[0];
+    let (k1, d1) = curve[curve.len() - 1];
+    if curve.len() < 3 || d0 <= d1 {
+        return k1;
+    }
+    // Normalize both axes to 0..1, then take the point farthest below the chord
+    let mut best = (k1, f64::MIN);
+    for &(k, d) in &curve {
+        let x = (k - k0) as f64 / (k1 - k0) as f64;
+        let y = (d - d1) / (d0 - d1);
+        let gap = 1.0 - x - y;
+        if gap > best.1 {
+            best = (k, gap);
+        }
+    }
+    best.0
 }
 
 /// Count distinct colors in an image.
@@ -290,18 +623,76 @@ mod tests {
         assert_eq!(perceptual_dist_sq(&a, &a), 0);
     }
 
+    #[test]
+    fn test_lab_distance_matches_reference_values() {
+        let white = srgb_to_lab(&RGBA8::new(255, 255, 255, 255));
+        assert!((white[0] - 100.0).abs() < 0.01 && white[1].abs() < 0.01 && white[2].abs() < 0.01);
+        let red = srgb_to_lab(&RGBA8::new(255, 0, 0, 255));
+        assert!((red[0] - 53.24).abs() < 0.05 && (red[1] - 80.09).abs() < 0.05 && (red[2] - 67.20).abs() < 0.05);
+
+        let (a, b) = (RGBA8::new(10, 20, 30, 255), RGBA8::new(40, 50, 60, 255));
+        let space = ColorSpace::WeightedRgb;
+        assert_eq!(space.dist_sq(&space.point(&a), &space.point(&b)), perceptual_dist_sq(&a, &b) as f64);
+    }
+
+    #[test]
+    fn test_lab_palette_differs_on_blue_purple_gradient() {
+        // Three steps from sky blue to violet: weighted RGB ranks the first
+        // two closest, CIELAB the last two
+        let steps = [RGBA8::new(1, 92, 242, 255), RGBA8::new(0, 80, 253, 255), RGBA8::new(68, 82, 255, 255)];
+        let pixels: Vec<RGBA8> = (0..30 * 10).map(|i| steps[i % 30 / 10]).collect();
+        let img = ImageData { width: 30, height: 10, pixels };
+        let palette = |color_space| {
+            let kmeans = KmeansOptions { max_iterations: 20, color_space, ..Default::default() };
+            let (_, indices, mut palette, _) = quantize_enhanced_with_kmeans(&img, 2, &kmeans);
+            // Which steps share a palette entry
+            let merged = [indices[0] == indices[10], indices[10] == indices[20]];
+            palette.sort_by_key(|c| c.r);
+            (merged, palette)
+        };
+        let (rgb_merged, rgb) = palette(ColorSpace::WeightedRgb);
+        let (lab_merged, lab) = palette(ColorSpace::Lab);
+        assert_eq!(rgb_merged, [true, false]);
+        assert_eq!(lab_merged, [false, true]);
+        assert_ne!(rgb, lab);
+    }
+
+    #[test]
+    fn test_dither_breaks_up_gray_ramp_bands() {
+        // Vertical ramp: black at the top, white at the bottom
+        let (w, h) = (32usize, 128usize);
+        let pixels: Vec<RGBA8> = (0..w * h)
+            .map(|i| {
+                let v = (i / w * 255 / (h - 1)) as u8;
+                RGBA8::new(v, v, v, 255)
+            })
+            .collect();
+        let img = ImageData { width: w as u32, height: h as u32, pixels };
+        let transitions = |dither: bool| {
+            let kmeans = KmeansOptions { dither, ..Default::default() };
+            let (_, indices, palette, _) = quantize_enhanced_with_kmeans(&img, 4, &kmeans);
+            assert!(indices.iter().all(|&i| i < palette.len()));
+            (0..w * (h - 1)).filter(|&i| indices[i] != indices[i + w]).count()
+        };
+        let (plain, dithered) = (transitions(false), transitions(true));
+        // Plain mapping changes index once per band boundary in each column
+        assert!(plain <= 3 * w, "{}", plain);
+        assert!(dithered > 4 * plain, "{} vs {}", dithered, plain);
+    }
+
     #[test]
     fn test_kmeans_plusplus_init_returns_k_centroids() {
         let samples: Vec<RGBA8> = (0..100)
             .map(|i| RGBA8::new(i as u8 * 2, 0, 0, 255))
             .collect();
-        let centroids = kmeans_plusplus_init(&samples, 8);
+        let points = ColorSpace::WeightedRgb.points(&samples);
+        let centroids = kmeans_plusplus_init(&samples, &points, ColorSpace::WeightedRgb, None, &[], 8, &mut kmeans_rng(None));
         assert_eq!(centroids.len(), 8);
     }
 
     #[test]
     fn test_kmeans_plusplus_init_empty() {
-        let centroids = kmeans_plusplus_init(&[], 5);
+        let centroids = kmeans_plusplus_init(&[], &[], ColorSpace::WeightedRgb, None, &[], 5, &mut kmeans_rng(None));
         assert!(centroids.is_empty());
     }
 
@@ -328,7 +719,7 @@ mod tests {
         let img = ImageData { width: 5, height: 5, pixels };
         let (quantized, _, palette) = quantize_enhanced(&img, 4);
         // Should produce 1 color since input is uniform
-        assert!(palette.len() >= 1);
+        assert!(!palette.is_empty());
         // All output pixels should be the same
         let first = quantized.pixels[0];
         for p in &quantized.pixels {
@@ -350,6 +741,15 @@ mod tests {
         assert_eq!(count_distinct_colors(&img), 3);
     }
 
+    #[test]
+    fn test_quantize_enhanced_clamps_to_max_colors() {
+        let pixels = (0..40 * 40).map(|i| RGBA8::new((i % 40 * 6) as u8, (i / 40 * 6) as u8, 90, 255)).collect();
+        let img = ImageData { width: 40, height: 40, pixels };
+        let (_, indices, palette) = quantize_enhanced(&img, 1000);
+        assert!(!palette.is_empty() && palette.len() <= MAX_COLORS, "{} colors", palette.len());
+        assert!(indices.iter().all(|&i| i < palette.len()));
+    }
+
     #[test]
     fn test_adaptive_color_count() {
         let small = ImageData { width: 50, height: 50, pixels: vec![RGBA8::new(0,0,0,255); 2500] };
@@ -380,4 +780,101 @@ mod tests {
         assert_eq!(indices.len(), 100);
         assert!(palette.len() <= 4);
     }
+
+    #[test]
+    fn test_kmeans_stops_early_on_separated_clusters() {
+        let mut pixels = vec![RGBA8::new(250, 10, 10, 255); 200];
+        pixels.extend(vec![RGBA8::new(10, 10, 250, 255); 200]);
+        let img = ImageData { width: 20, height: 20, pixels };
+        let kmeans = KmeansOptions { max_iterations: 50, epsilon: 0.5, ..Default::default() };
+        let (_, _, palette, iterations) = quantize_enhanced_with_kmeans(&img, 2, &kmeans);
+        assert_eq!(palette.len(), 2);
+        assert!((1..50).contains(&iterations), "ran {} iterations", iterations);
+    }
+
+    #[test]
+    fn test_histogram_kmeans_keeps_small_object() {
+        // Object pixels sit on odd indices only, which the stride-2 sample
+        // for this 250K-pixel image never visits
+        let (w, h) = (500usize, 500usize);
+        let mut pixels = vec![RGBA8::new(250, 250, 250, 255); w * h];
+        for y in 200..210 {
+            pixels[y * w + 301] = RGBA8::new(200, 0, 0, 255);
+        }
+        let img = ImageData { width: w as u32, height: h as u32, pixels };
+
+        let kmeans = KmeansOptions { histogram: true, ..Default::default() };
+        let (quantized, _, palette, _) = quantize_enhanced_with_kmeans(&img, 2, &kmeans);
+        assert!(palette.contains(&RGBA8::new(250, 250, 250, 255)), "{:?}", palette);
+        assert!(palette.contains(&RGBA8::new(200, 0, 0, 255)), "{:?}", palette);
+        assert_eq!(quantized.pixels[205 * w + 301], RGBA8::new(200, 0, 0, 255));
+
+        let (_, _, sampled, _) = quantize_enhanced_with_kmeans(&img, 2, &KmeansOptions::default());
+        assert!(!sampled.contains(&RGBA8::new(200, 0, 0, 255)));
+    }
+
+    #[test]
+    fn test_fixed_palette_skips_kmeans() {
+        let pixels = (0..400).map(|i| RGBA8::new((i % 20 * 13) as u8, 40, (i / 20 * 13) as u8, 255)).collect();
+        let img = ImageData { width: 20, height: 20, pixels };
+        let fixed = vec![RGBA8::new(0, 0, 0, 255), RGBA8::new(255, 255, 255, 255)];
+        let kmeans = KmeansOptions { fixed_palette: Some(fixed.clone()), ..Default::default() };
+        let (quantized, indices, palette, iterations) = quantize_enhanced_with_kmeans(&img, 8, &kmeans);
+        assert_eq!((palette, iterations), (fixed.clone(), 0));
+        for ((p, q), &i) in img.pixels.iter().zip(&quantized.pixels).zip(&indices) {
+            assert_eq!(*q, fixed[i]);
+            let nearest = fixed.iter().min_by_key(|c| perceptual_dist_sq(p, c)).unwrap();
+            assert_eq!(perceptual_dist_sq(p, q), perceptual_dist_sq(p, nearest));
+        }
+    }
+
+    #[test]
+    fn test_pinned_colors_stay_fixed() {
+        // Half the pixels sit just off the pinned color, pulling a free centroid
+        let mut pixels = vec![RGBA8::new(204, 0, 0, 255); 200];
+        pixels.extend(vec![RGBA8::new(220, 30, 30, 255); 200]);
+        let img = ImageData { width: 20, height: 20, pixels };
+        let pinned = RGBA8::new(204, 0, 0, 255);
+        let kmeans = KmeansOptions { pinned: vec![pinned], ..Default::default() };
+        let (quantized, _, palette, _) = quantize_enhanced_with_kmeans(&img, 1, &kmeans);
+        assert_eq!(palette, vec![pinned]);
+        assert!(quantized.pixels.iter().all(|&p| p == pinned));
+
+        let (_, _, palette, _) = quantize_enhanced_with_kmeans(&img, 3, &kmeans);
+        assert_eq!(palette[0], pinned);
+        assert!(palette.len() <= 3);
+    }
+
+    #[test]
+    fn test_kmeans_respects_max_iterations() {
+        let pixels: Vec<RGBA8> = (0..400)
+            .map(|i| RGBA8::new((i % 256) as u8, (i / 2 % 256) as u8, 90, 255))
+            .collect();
+        let img = ImageData { width: 20, height: 20, pixels };
+        let kmeans = KmeansOptions { max_iterations: 1, epsilon: 0.0, ..Default::default() };
+        let (_, _, _, iterations) = quantize_enhanced_with_kmeans(&img, 8, &kmeans);
+        assert_eq!(iterations, 1);
+    }
+
+    #[test]
+    fn test_elbow_color_count() {
+        let three: Vec<RGBA8> = (0..900)
+            .map(|i| match i % 30 / 10 {
+                0 => RGBA8::new(220, 30, 30, 255),
+                1 => RGBA8::new(30, 200, 40, 255),
+                _ => RGBA8::new(20, 40, 210, 255),
+            })
+            .collect();
+        let three = ImageData { width: 30, height: 30, pixels: three };
+        let k_three = elbow_color_count(&three, 32);
+        assert!((2..=4).contains(&k_three), "three-color image picked {}", k_three);
+
+        let gradient: Vec<RGBA8> = (0..64 * 64)
+            .map(|i| RGBA8::new((i % 64 * 4) as u8, (i / 64 * 4) as u8, 100, 255))
+            .collect();
+        let gradient = ImageData { width: 64, height: 64, pixels: gradient };
+        let k_gradient = elbow_color_count(&gradient, 32);
+        assert!(k_gradient > k_three, "gradient picked {} vs {}", k_gradient, k_three);
+        assert!(k_gradient <= 32);
+    }
 }
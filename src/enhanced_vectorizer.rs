@@ -7,24 +7,143 @@
 //!
 //! The original marching-squares pipeline is preserved in vectorizer.rs for comparison.
 
-use crate::bezier_fitter::{bezier_to_svg_path, BezierCurve, BezierFitter};
-use crate::edge_detector::detect_edges_sobel;
+use crate::bezier_fitter::{
+    bezier_to_svg_path, collapse_path_commands, BezierCurve, BezierFitter, PathFormat,
+};
+use crate::centerline::{extract_centerlines, Centerline};
+use crate::edge_detector::{detect_edges_sobel, EdgeMap};
 use crate::enhanced_quantizer::{
-    adaptive_color_count, count_distinct_colors, quantize_edge_aware,
+    adaptive_color_count, count_distinct_colors, elbow_color_count_seeded, perceptual_dist_sq,
+    quantize_edge_aware_with_kmeans, ColorSpace, KmeansOptions,
+};
+use crate::image_processor::{downscale_to_fit, has_cutout_transparency, ImageData, MAX_COLORS};
+use crate::morphology::{heal_gaps, majority_smooth, merge_small_regions};
+use crate::path_simplifier::{
+    detect_corners, resample_even, smooth_with_corners, snap_axis_aligned, visvalingam_whyatt,
+    visvalingam_whyatt_closed,
 };
-use crate::image_processor::ImageData;
-use crate::path_simplifier::{detect_corners, smooth_with_corners, visvalingam_whyatt};
-use crate::preprocessor::{preprocess, PreprocessOptions};
-use crate::region_extractor::detect_background_color;
+use crate::preprocessor::{binarize_alpha, flatten_alpha, preprocess, PreprocessOptions};
+use crate::primitives::{fit_primitive, Primitive, PRIMITIVE_TOLERANCE};
+use crate::region_extractor::{detect_background_color, detect_background_color_with_fallback, flood_fill_by_index};
+use crate::svg_generator::opacity_attr;
 use crate::vectorizer::{marching_squares_contours, Point};
 use anyhow::Result;
 use rayon::prelude::*;
-use rgb::RGBA8;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::Write;
+use std::path::PathBuf;
+
+/// `stroke-linejoin` used on stroked paths.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StrokeLinejoin {
+    Miter,
+    #[default]
+    Round,
+    Bevel,
+}
+
+impl StrokeLinejoin {
+    /// SVG attribute value.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StrokeLinejoin::Miter => "miter",
+            StrokeLinejoin::Round => "round",
+            StrokeLinejoin::Bevel => "bevel",
+        }
+    }
+}
+
+/// `stroke-linecap` used on stroked paths.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StrokeLinecap {
+    /// SVG default; not written out
+    #[default]
+    Butt,
+    Round,
+    Square,
+}
+
+impl StrokeLinecap {
+    /// SVG attribute value.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StrokeLinecap::Butt => "butt",
+            StrokeLinecap::Round => "round",
+            StrokeLinecap::Square => "square",
+        }
+    }
+}
+
+/// `fill-rule` used on filled paths.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FillRule {
+    /// SVG default; not written out. Holes cut out because every hole
+    /// winds opposite to the outline around it.
+    NonZero,
+    /// Holes cut out by nesting alone, whatever the winding; written on
+    /// elements with more than one subpath
+    #[default]
+    EvenOdd,
+}
+
+impl FillRule {
+    /// SVG attribute value.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FillRule::NonZero => "nonzero",
+            FillRule::EvenOdd => "evenodd",
+        }
+    }
+}
+
+/// Paint order of the traced regions; later paths are drawn on top.
+/// Regions are ranked by the pixel count of their color (or component, with
+/// `split_components`), so the paths of one color stay together.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZOrder {
+    /// Largest area at the back, so small detail paints on top
+    #[default]
+    LargestFirst,
+    /// Smallest area at the back, e.g. for stencils whose small regions
+    /// would otherwise be buried
+    SmallestFirst,
+    /// The order regions were traced in: colors by pixel count, then the
+    /// raster scan order of each color's contours
+    PreserveScan,
+}
+
+impl ZOrder {
+    /// Arrange `items` in this order of `area`. The sort is stable, so
+    /// items of equal area keep their relative order.
+    fn sort<T>(self, items: &mut [T], area: impl Fn(&T) -> usize) {
+        match self {
+            ZOrder::LargestFirst => items.sort_by_key(|item| std::cmp::Reverse(area(item))),
+            ZOrder::SmallestFirst => items.sort_by_key(|item| area(item)),
+            ZOrder::PreserveScan => {}
+        }
+    }
+}
+
+/// How regions are painted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderMode {
+    /// Filled regions on the background rect
+    #[default]
+    Fill,
+    /// Region outlines only, stroked in their color at this width (e.g. for
+    /// laser cutters and pen plotters); no background rect
+    Stroke { width: f64 },
+}
 
 /// Enhanced vectorization options.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct EnhancedOptions {
     /// Number of colors (0 = auto-detect based on image size)
     pub num_colors: usize,
@@ -46,8 +165,202 @@ pub struct EnhancedOptions {
     pub preprocess: bool,
     /// Whether to recolor from original image
     pub recolor: bool,
+    /// Close single-pixel background seams between neighboring regions so
+    /// their contours share exact boundaries
+    pub heal_gaps: bool,
+    /// Scale the curve tolerance with local curvature (tighter at sharp turns)
+    pub adaptive_tolerance: bool,
+    /// Debug mode: emit marching-squares contours verbatim as polylines
+    /// (no smoothing, simplification or Bézier fitting)
+    pub raw_contours: bool,
+    /// Composite semi-transparent pixels over the detected background before
+    /// quantization, so fills match what the source shows on screen
+    pub flatten_alpha: bool,
+    /// Decimal places kept in emitted coordinates
+    pub decimal_precision: u8,
+    /// Write smoothly joined curves with the shorter `S` command
+    pub smooth_shorthand: bool,
+    /// How far (px) a vertex may sit off a straight run and still be merged
+    /// into one line; never finer than the printed precision can show
+    pub line_merge_tolerance: f64,
+    /// Run a final peephole pass over each `d` string that drops redundant
+    /// line commands and writes repeated commands implicitly
+    pub collapse_path_commands: bool,
+    /// Extra attributes for the root `<svg>` element (e.g. `class`,
+    /// `preserveAspectRatio`); a name that matches a built-in attribute
+    /// replaces its value
+    pub root_attributes: Vec<(String, String)>,
+    /// `preserveAspectRatio` for the root `<svg>` (e.g. `"xMidYMid meet"`),
+    /// controlling how the viewBox scales into a differently shaped box
+    pub preserve_aspect_ratio: Option<String>,
+    /// Write an XML comment recording the crate version, the source file
+    /// and these options, so the output can be reproduced
+    pub embed_provenance: bool,
+    /// Record the source's full path in the provenance comment rather than
+    /// only its file name
+    pub provenance_full_path: bool,
+    /// Image being converted, named in the provenance comment; set by
+    /// file-based callers, never read from or written to config
+    #[serde(skip)]
+    pub source_path: Option<PathBuf>,
+    /// Wrap the SVG in a minimal HTML document
+    pub wrap_html: bool,
+    /// Maximum k-means palette refinement iterations
+    pub kmeans_iterations: usize,
+    /// Stop k-means early once no centroid moves more than this (RGB distance)
+    pub kmeans_epsilon: f64,
+    /// Build the k-means palette from a count-weighted color histogram of
+    /// every pixel rather than a uniform sample
+    pub histogram_kmeans: bool,
+    /// Color space k-means clusters and maps pixels in
+    pub color_space: ColorSpace,
+    /// Floyd–Steinberg dither pixels onto the palette to break up banding
+    /// in gradients. `smoothing_passes` undoes much of the dither on flat
+    /// areas, so lower it to keep the pattern
+    pub dither: bool,
+    /// Seed k-means++ initialization so the same image and options always
+    /// give the same palette and SVG; None picks a fresh seed each run
+    pub seed: Option<u64>,
+    /// Colors forced into the palette and never moved by k-means or
+    /// recoloring (e.g. a logo's brand color)
+    pub pinned_colors: Vec<(u8, u8, u8)>,
+    /// Quantize to exactly these colors (e.g. a brand style guide) instead
+    /// of a palette derived from the image: k-means is skipped, every pixel
+    /// takes the nearest one and recoloring is off
+    pub fixed_palette: Option<Vec<(u8, u8, u8)>>,
+    /// Treat images whose pixels all lie within this per-channel distance
+    /// of two colors (scans, line art) as black-and-white: pixels snap to
+    /// the nearer color and the foreground mask is traced directly, skipping
+    /// preprocessing and k-means; `None` always quantizes
+    pub binary_tolerance: Option<u8>,
+    /// 3×3 majority-vote passes over each color mask before tracing, to
+    /// remove single-pixel jaggies the contour smoothing cannot reach
+    pub mask_smoothing: usize,
+    /// Trace each 8-connected blob of a color on its own and emit it as one
+    /// path holding its exterior and holes, instead of one path per color
+    pub split_components: bool,
+    /// Put every path of a color into one element at the color's first
+    /// appearance, not just consecutive runs. Smaller, but later paths of
+    /// that color move back in z-order: only safe when they don't overlap
+    /// regions of other colors
+    pub merge_all_same_color: bool,
+    /// Drop the indentation and line breaks between elements
+    pub minify: bool,
+    /// Write paths that repeat the same shape at different offsets once in
+    /// `<defs>` and place each copy with `<use>`
+    pub dedup_shapes: bool,
+    /// Draw thin regions (line art strokes) as stroked centerlines at their
+    /// measured width instead of filled outlines
+    pub centerline: bool,
+    /// Widest mean stroke (px) still treated as a line by `centerline`
+    pub centerline_max_width: f64,
+    /// Hard cap on the palette size, whatever the color count source
+    /// (explicit, elbow or adaptive); more colors mean more tracing passes
+    pub max_colors: usize,
+    /// Hand regions below the minimum area to their most similar neighbor
+    /// before tracing, instead of dropping them and leaving a gap
+    pub merge_small_into_neighbor: bool,
+    /// Fill over holes (inner contours) smaller than this many pixels;
+    /// 0 applies the same minimum area as outer contours
+    pub fill_holes_below_area: f64,
+    /// Append a strip of palette swatches below the artwork
+    pub emit_legend: bool,
+    /// Emit single-ring, straight-edged shapes as `<polygon>` instead of `<path>`
+    pub prefer_polygons: bool,
+    /// When `num_colors` is 0, pick the count by the k-means elbow method
+    /// (up to this many colors) instead of bucketing by image size
+    pub elbow_max_colors: Option<usize>,
+    /// Pixels with alpha below this are left out of every region; the rest
+    /// count as opaque (0 = disabled, except that all-or-nothing alpha such
+    /// as PNG `tRNS` transparency always leaves its transparent pixels out)
+    pub alpha_cutoff: u8,
+    /// Resample simplified contours to this even point spacing (pixels)
+    /// before Bézier fitting
+    pub resample_spacing: Option<f64>,
+    /// Also write one SVG per color (see [`write_color_separations`])
+    pub color_separations: bool,
+    /// Draw a stroke in the fill color around each fill to hide
+    /// anti-aliasing seams between neighboring regions
+    pub gap_stroke: bool,
+    /// Width of that stroke in px; raise it for high-resolution exports,
+    /// lower it for tiny icons
+    pub gap_stroke_width: f64,
+    /// Line join for the gap-filling stroke
+    pub stroke_linejoin: StrokeLinejoin,
+    /// Line cap for the gap-filling stroke
+    pub stroke_linecap: StrokeLinecap,
+    /// Fill rule for filled paths. Either way outlines wind clockwise and
+    /// holes counter-clockwise, so both rules cut holes out
+    pub fill_rule: FillRule,
+    /// Paint order of the regions
+    pub z_order: ZOrder,
+    /// Fill regions or only stroke their outlines
+    pub render_mode: RenderMode,
+    /// Emit contours that fill at least [`RECT_FILL_RATIO`] of their bounding
+    /// box as exact integer rectangles instead of fitting curves
+    pub detect_rects: bool,
+    /// Emit outlines within [`PRIMITIVE_TOLERANCE`] of a circle or an
+    /// axis-aligned ellipse as `<circle>`/`<ellipse>` elements, and ones
+    /// within [`RECT_AREA_TOLERANCE`] of their bounding box as `<rect>`
+    pub detect_primitives: bool,
+    /// Trace a copy shrunk to at most this many pixels on its longer side,
+    /// then scale the result back up: a coarse but fast preview. Unlike the
+    /// CLI's `--max-size`, the output keeps the input's dimensions.
+    pub preview_downscale: Option<u32>,
+    /// Byte budget for the SVG: when the output is larger, the curve and
+    /// simplification tolerances are raised together until it fits (within
+    /// a capped number of attempts; the coarsest try is kept if none does)
+    pub target_max_bytes: Option<usize>,
+    /// Stroke the outer boundary of all non-background pixels as a top
+    /// layer, with this width and color (e.g. a sticker cut line)
+    pub silhouette_outline: Option<(f64, (u8, u8, u8))>,
+    /// After simplification, make segments within this many degrees of
+    /// horizontal or vertical exactly axis-aligned
+    pub axis_snap_deg: Option<f64>,
+    /// Simplify closed contours as cycles (see [`visvalingam_whyatt_closed`])
+    /// instead of protecting their arbitrary start and end points
+    pub simplify_closed: bool,
+    /// Lower the minimum contour area for colors that contrast with the
+    /// background (scaled by luminance difference), so small dark text on a
+    /// light page survives while faint specks are still dropped
+    pub contrast_min_area: bool,
+    /// Add `data-area="<pixels>"` (the sum of [`EnhancedPath::area`] over
+    /// the paths it draws) to each emitted shape, for click-to-select tooling
+    pub emit_area_attr: bool,
+    /// Debugging aid: tag each shape with `data-area` and the quantized
+    /// color(s) it was traced from, before recoloring, as
+    /// `data-quant="#rrggbb"`, and add a `<!-- img2svg <version>; paths=<n> -->`
+    /// comment, so two conversions can be diffed
+    pub emit_metadata: bool,
+    /// When every foreground path has the same color, fill with
+    /// `currentColor` and drop the background rect, so the icon takes its
+    /// color from CSS
+    pub monochrome_current_color: bool,
+    /// Round every emitted coordinate, control points included, to a
+    /// multiple of this grid size (e.g. for CAD or laser tools)
+    pub snap_grid: Option<f64>,
+    /// Shrink the boundary smoothing window per contour by the mean edge
+    /// magnitude along it: crisp edges keep their detail, soft ones are
+    /// smoothed fully
+    pub edge_adaptive_smoothing: bool,
+    /// Replace fitted curves that loop or cusp with tamer ones (shortened
+    /// handles, or a straight line)
+    pub loop_guard: bool,
+    /// Share (0–1) of the border the most frequent border color must cover
+    /// to be taken as the background; below it, the most common color of
+    /// the whole image is used, for subjects that bleed off every edge
+    pub background_min_border_share: f64,
 }
 
+/// Share of its bounding box a contour must cover to count as a rectangle
+/// under [`EnhancedOptions::detect_rects`].
+pub const RECT_FILL_RATIO: f64 = 0.97;
+
+/// Largest relative difference between a contour's area and its bounding
+/// box's for it to become a `<rect>` under
+/// [`EnhancedOptions::detect_primitives`].
+pub const RECT_AREA_TOLERANCE: f64 = 0.02;
+
 impl Default for EnhancedOptions {
     fn default() -> Self {
         Self {
@@ -61,7 +374,521 @@ impl Default for EnhancedOptions {
             smooth_window: 3,
             preprocess: true,
             recolor: true,
+            heal_gaps: false,
+            adaptive_tolerance: false,
+            raw_contours: false,
+            flatten_alpha: false,
+            decimal_precision: 2,
+            smooth_shorthand: false,
+            line_merge_tolerance: 1.5,
+            collapse_path_commands: false,
+            root_attributes: Vec::new(),
+            preserve_aspect_ratio: None,
+            embed_provenance: false,
+            provenance_full_path: false,
+            source_path: None,
+            wrap_html: false,
+            kmeans_iterations: 8,
+            kmeans_epsilon: 0.0,
+            histogram_kmeans: false,
+            color_space: ColorSpace::WeightedRgb,
+            dither: false,
+            seed: None,
+            pinned_colors: Vec::new(),
+            fixed_palette: None,
+            binary_tolerance: Some(16),
+            mask_smoothing: 0,
+            split_components: false,
+            merge_all_same_color: false,
+            minify: false,
+            dedup_shapes: false,
+            centerline: false,
+            centerline_max_width: 6.0,
+            max_colors: MAX_COLORS,
+            merge_small_into_neighbor: false,
+            fill_holes_below_area: 0.0,
+            emit_legend: false,
+            prefer_polygons: false,
+            elbow_max_colors: None,
+            alpha_cutoff: 0,
+            resample_spacing: None,
+            color_separations: false,
+            gap_stroke: true,
+            gap_stroke_width: 0.5,
+            stroke_linejoin: StrokeLinejoin::Round,
+            stroke_linecap: StrokeLinecap::Butt,
+            fill_rule: FillRule::EvenOdd,
+            z_order: ZOrder::LargestFirst,
+            render_mode: RenderMode::Fill,
+            detect_rects: false,
+            detect_primitives: false,
+            preview_downscale: None,
+            target_max_bytes: None,
+            silhouette_outline: None,
+            axis_snap_deg: None,
+            simplify_closed: false,
+            contrast_min_area: false,
+            emit_area_attr: false,
+            emit_metadata: false,
+            monochrome_current_color: false,
+            snap_grid: None,
+            edge_adaptive_smoothing: false,
+            loop_guard: false,
+            background_min_border_share: 0.3,
+        }
+    }
+}
+
+/// Curve-fitting tolerances used to turn a polyline into Bézier path data.
+#[derive(Debug, Clone)]
+pub struct CurveOptions {
+    /// Curve fitting tolerance (lower = more accurate, larger SVG)
+    pub curve_tolerance: f64,
+    /// Path simplification tolerance
+    pub simplification_tolerance: f64,
+    /// Corner detection threshold in degrees
+    pub corner_threshold: f64,
+    /// Smoothing window size for boundary points
+    pub smooth_window: usize,
+    /// Scale the curve tolerance with local curvature
+    pub adaptive_tolerance: bool,
+    /// Resample to this even point spacing before fitting
+    pub resample_spacing: Option<f64>,
+    /// Snap segments this close (degrees) to an axis onto it
+    pub axis_snap_deg: Option<f64>,
+    /// Simplify closed polylines as cycles, with no protected seam
+    pub simplify_closed: bool,
+    /// Tame fitted curves that loop or cusp
+    pub loop_guard: bool,
+    /// Whether the polyline is a closed contour
+    pub closed: bool,
+}
+
+impl Default for CurveOptions {
+    fn default() -> Self {
+        EnhancedOptions::default().curve_options()
+    }
+}
+
+impl EnhancedOptions {
+    /// Reject option values the pipeline cannot work with.
+    pub fn validate(&self) -> Result<()> {
+        if !(self.curve_tolerance.is_finite() && self.curve_tolerance > 0.0) {
+            return Err(anyhow::anyhow!("curve_tolerance must be a positive number"));
+        }
+        if !(self.simplification_tolerance.is_finite() && self.simplification_tolerance >= 0.0) {
+            return Err(anyhow::anyhow!("simplification_tolerance must be a non-negative number"));
+        }
+        if !(0.0..=180.0).contains(&self.corner_threshold) {
+            return Err(anyhow::anyhow!("corner_threshold must be between 0 and 180 degrees"));
+        }
+        if !(self.centerline_max_width.is_finite() && self.centerline_max_width > 0.0) {
+            return Err(anyhow::anyhow!("centerline_max_width must be a positive number"));
+        }
+        if !(self.line_merge_tolerance.is_finite() && self.line_merge_tolerance >= 0.0) {
+            return Err(anyhow::anyhow!("line_merge_tolerance must be a non-negative number"));
+        }
+        if !(self.kmeans_epsilon.is_finite() && self.kmeans_epsilon >= 0.0) {
+            return Err(anyhow::anyhow!("kmeans_epsilon must be a non-negative number"));
+        }
+        if let Some(value) = &self.preserve_aspect_ratio
+            && !is_preserve_aspect_ratio(value)
+        {
+            return Err(anyhow::anyhow!(
+                "preserve_aspect_ratio must be `none` or x{{Min,Mid,Max}}Y{{Min,Mid,Max}}, optionally followed by `meet` or `slice`"
+            ));
+        }
+        if !(self.gap_stroke_width.is_finite() && self.gap_stroke_width > 0.0) {
+            return Err(anyhow::anyhow!("gap_stroke_width must be a positive number"));
+        }
+        if let RenderMode::Stroke { width } = self.render_mode
+            && !(width.is_finite() && width > 0.0)
+        {
+            return Err(anyhow::anyhow!("render_mode stroke width must be a positive number"));
+        }
+        if self.max_colors == 0 {
+            return Err(anyhow::anyhow!("max_colors must be at least 1"));
+        }
+        if self.fixed_palette.as_ref().is_some_and(|colors| colors.is_empty()) {
+            return Err(anyhow::anyhow!("fixed_palette needs at least one color"));
+        }
+        if !(self.fill_holes_below_area.is_finite() && self.fill_holes_below_area >= 0.0) {
+            return Err(anyhow::anyhow!("fill_holes_below_area must be a non-negative number"));
+        }
+        if self.target_max_bytes == Some(0) {
+            return Err(anyhow::anyhow!("target_max_bytes must be at least 1"));
+        }
+        if !(0.0..=1.0).contains(&self.background_min_border_share) {
+            return Err(anyhow::anyhow!("background_min_border_share must be between 0 and 1"));
+        }
+        if let Some(spacing) = self.resample_spacing
+            && !(spacing.is_finite() && spacing > 0.0)
+        {
+            return Err(anyhow::anyhow!("resample_spacing must be a positive number"));
         }
+        if let Some(grid) = self.snap_grid
+            && !(grid.is_finite() && grid > 0.0)
+        {
+            return Err(anyhow::anyhow!("snap_grid must be a positive number"));
+        }
+        if let Some(deg) = self.axis_snap_deg
+            && !(deg > 0.0 && deg < 45.0)
+        {
+            return Err(anyhow::anyhow!("axis_snap_deg must be between 0 and 45 degrees"));
+        }
+        Ok(())
+    }
+
+    /// Number formatting for emitted path data.
+    pub fn path_format(&self) -> PathFormat {
+        // Rounding to `decimal_precision` moves points by up to half a unit anyway
+        let resolution = 0.5 * 10f64.powi(-(self.decimal_precision as i32));
+        PathFormat {
+            precision: self.decimal_precision,
+            smooth_shorthand: self.smooth_shorthand,
+            merge_tolerance: self.line_merge_tolerance.max(resolution),
+        }
+    }
+
+    /// Width of the gap-filling stroke actually drawn around each fill, if
+    /// any.
+    ///
+    /// Healed regions at integer precision are integer-aligned and exactly
+    /// adjacent, so the stroke would only show up as a fat outline.
+    pub fn fill_stroke_width(&self) -> Option<f64> {
+        if !self.gap_stroke || (self.heal_gaps && self.decimal_precision == 0) {
+            None
+        } else {
+            Some(self.gap_stroke_width)
+        }
+    }
+
+    /// The curve-fitting subset of these options (for closed contours).
+    pub fn curve_options(&self) -> CurveOptions {
+        CurveOptions {
+            curve_tolerance: self.curve_tolerance,
+            simplification_tolerance: self.simplification_tolerance,
+            corner_threshold: self.corner_threshold,
+            smooth_window: self.smooth_window,
+            adaptive_tolerance: self.adaptive_tolerance,
+            resample_spacing: self.resample_spacing,
+            axis_snap_deg: self.axis_snap_deg,
+            simplify_closed: self.simplify_closed,
+            loop_guard: self.loop_guard,
+            closed: true,
+        }
+    }
+
+    /// Start an [`EnhancedOptionsBuilder`] from the defaults.
+    pub fn builder() -> EnhancedOptionsBuilder {
+        EnhancedOptionsBuilder::default()
+    }
+}
+
+/// Chainable construction of [`EnhancedOptions`] for its core tracing
+/// settings; unset fields keep their defaults. Anything else can be set on
+/// the built options.
+#[derive(Debug, Clone, Default)]
+pub struct EnhancedOptionsBuilder {
+    options: EnhancedOptions,
+}
+
+impl EnhancedOptionsBuilder {
+    /// Sets [`EnhancedOptions::num_colors`]: 0 for auto, else capped at `max_colors`.
+    pub fn num_colors(mut self, num_colors: usize) -> Self {
+        self.options.num_colors = num_colors;
+        self
+    }
+
+    /// Sets [`EnhancedOptions::curve_tolerance`]: a positive number.
+    pub fn curve_tolerance(mut self, curve_tolerance: f64) -> Self {
+        self.options.curve_tolerance = curve_tolerance;
+        self
+    }
+
+    /// Sets [`EnhancedOptions::simplification_tolerance`]: a non-negative number.
+    pub fn simplification_tolerance(mut self, simplification_tolerance: f64) -> Self {
+        self.options.simplification_tolerance = simplification_tolerance;
+        self
+    }
+
+    /// Sets [`EnhancedOptions::corner_threshold`]: 0..=180 degrees.
+    pub fn corner_threshold(mut self, corner_threshold: f64) -> Self {
+        self.options.corner_threshold = corner_threshold;
+        self
+    }
+
+    /// Sets [`EnhancedOptions::min_region_area`]: in pixels; smaller regions are dropped.
+    pub fn min_region_area(mut self, min_region_area: usize) -> Self {
+        self.options.min_region_area = min_region_area;
+        self
+    }
+
+    /// Sets [`EnhancedOptions::edge_threshold`]: an edge magnitude, 0..=255.
+    pub fn edge_threshold(mut self, edge_threshold: u8) -> Self {
+        self.options.edge_threshold = edge_threshold;
+        self
+    }
+
+    /// Sets [`EnhancedOptions::smoothing_passes`]: majority-vote passes (at least 4 on
+    /// many-color graphics).
+    pub fn smoothing_passes(mut self, smoothing_passes: usize) -> Self {
+        self.options.smoothing_passes = smoothing_passes;
+        self
+    }
+
+    /// Sets [`EnhancedOptions::smooth_window`]: boundary points averaged around each point.
+    pub fn smooth_window(mut self, smooth_window: usize) -> Self {
+        self.options.smooth_window = smooth_window;
+        self
+    }
+
+    /// Sets [`EnhancedOptions::preprocess`].
+    pub fn preprocess(mut self, preprocess: bool) -> Self {
+        self.options.preprocess = preprocess;
+        self
+    }
+
+    /// Sets [`EnhancedOptions::recolor`].
+    pub fn recolor(mut self, recolor: bool) -> Self {
+        self.options.recolor = recolor;
+        self
+    }
+
+    /// Finish the options, rejecting values [`EnhancedOptions::validate`]
+    /// does not accept.
+    pub fn build(self) -> Result<EnhancedOptions> {
+        self.options.validate()?;
+        Ok(self.options)
+    }
+}
+
+/// Smooth, simplify and Bézier-fit an existing polyline (e.g. from another
+/// tracer) and return it as SVG path data. No image is involved.
+pub fn fit_polyline_to_svg_path(points: &[Point], options: &CurveOptions) -> String {
+    bezier_to_svg_path(&fit_polyline(points, options), options.closed)
+}
+
+/// Vectorize a single binary mask (e.g. a segmentation result) as one path
+/// of the given color. Only the largest traced outline and the holes inside
+/// it are kept; smaller disconnected pieces are ignored.
+pub fn vectorize_mask(
+    mask: &[bool],
+    width: usize,
+    height: usize,
+    color: (u8, u8, u8, u8),
+    options: &CurveOptions,
+) -> EnhancedPath {
+    let contours = marching_squares_contours(mask, width, height);
+    let options = CurveOptions { closed: true, ..options.clone() };
+    let fit = |contour: &Vec<Point>, clockwise: bool| {
+        let mut curves = fit_polyline(contour, &options);
+        orient_curves(&mut curves, clockwise);
+        curves
+    };
+    let outline = contours
+        .iter()
+        .filter(|c| !is_hole(c))
+        .max_by(|a, b| polygon_area(a).total_cmp(&polygon_area(b)));
+    let holes = match outline {
+        Some(outline) => contours
+            .iter()
+            .filter(|c| is_hole(c) && point_in_ring(&c[0], outline))
+            .map(|c| fit(c, false))
+            .filter(|curves| !curves.is_empty())
+            .collect(),
+        None => Vec::new(),
+    };
+    EnhancedPath {
+        curves: outline.map(|c| fit(c, true)).unwrap_or_default(),
+        color,
+        quantized: color,
+        area: mask.iter().filter(|&&m| m).count(),
+        svg_override: None,
+        holes,
+        primitive: None,
+    }
+}
+
+/// Fit an open polyline (e.g. a line's centerline) as a stroke: the ends
+/// stay where the input starts and stops, and no closing segment is added.
+pub fn stroke_polyline(
+    points: &[Point],
+    color: (u8, u8, u8, u8),
+    width: f64,
+    options: &CurveOptions,
+) -> EnhancedStroke {
+    EnhancedStroke {
+        curves: fit_polyline(points, &CurveOptions { closed: false, ..options.clone() }),
+        color,
+        width,
+    }
+}
+
+/// The two colors of a near-binary image: every pixel (bar cut-out ones)
+/// lies within `tolerance` per channel of one of two representatives.
+/// Returns the mean color of each group, the larger group first; `None`
+/// for one-color images or when a third color shows up.
+fn binary_palette(image_data: &ImageData, tolerance: u8, alpha_cutoff: u8) -> Option<[rgb::RGBA8; 2]> {
+    let near = |a: &rgb::RGBA8, b: &rgb::RGBA8| {
+        a.r.abs_diff(b.r) <= tolerance
+            && a.g.abs_diff(b.g) <= tolerance
+            && a.b.abs_diff(b.b) <= tolerance
+            && a.a.abs_diff(b.a) <= tolerance
+    };
+    // (representative, channel sums, pixel count) per group
+    let mut groups: Vec<(rgb::RGBA8, [u64; 4], u64)> = Vec::with_capacity(2);
+    for p in &image_data.pixels {
+        if alpha_cutoff > 0 && p.a == 0 {
+            continue;
+        }
+        let group = match groups.iter().position(|(rep, _, _)| near(rep, p)) {
+            Some(i) => i,
+            None if groups.len() < 2 => {
+                groups.push((*p, [0; 4], 0));
+                groups.len() - 1
+            }
+            None => return None,
+        };
+        let (_, sum, count) = &mut groups[group];
+        sum[0] += p.r as u64;
+        sum[1] += p.g as u64;
+        sum[2] += p.b as u64;
+        sum[3] += p.a as u64;
+        *count += 1;
+    }
+    if groups.len() < 2 {
+        return None;
+    }
+    groups.sort_by_key(|g| std::cmp::Reverse(g.2));
+    let mean = |(_, sum, n): &(rgb::RGBA8, [u64; 4], u64)| {
+        rgb::RGBA8::new((sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8, (sum[3] / n) as u8)
+    };
+    Some([mean(&groups[0]), mean(&groups[1])])
+}
+
+/// Pixel coordinates of each palette index, in row-major order, from one
+/// pass over the index image (rather than hashing every pixel's color);
+/// `excluded` pixels belong to no group. Each worker groups a run of pixels
+/// on its own and the runs are concatenated in order.
+fn group_by_palette_index(
+    indices: &[usize],
+    palette_len: usize,
+    width: usize,
+    excluded: impl Fn(usize) -> bool + Sync,
+) -> Vec<Vec<(usize, usize)>> {
+    crate::threads::install(|| {
+        indices
+            .par_iter()
+            .enumerate()
+            .fold(
+                || vec![Vec::new(); palette_len],
+                |mut groups, (idx, &index)| {
+                    if !excluded(idx) {
+                        groups[index].push((idx % width, idx / width));
+                    }
+                    groups
+                },
+            )
+            .reduce(
+                || vec![Vec::new(); palette_len],
+                |mut groups, run| {
+                    for (group, pixels) in groups.iter_mut().zip(run) {
+                        group.extend(pixels);
+                    }
+                    groups
+                },
+            )
+    })
+}
+
+/// Minimum contour area for `color` under [`EnhancedOptions::contrast_min_area`]:
+/// `base` shrunk by the luminance difference from `background`, never below
+/// one pixel.
+fn contrast_min_area(base: f64, color: (u8, u8, u8, u8), background: (u8, u8, u8, u8)) -> f64 {
+    let luma = |c: (u8, u8, u8, u8)| 0.299 * c.0 as f64 + 0.587 * c.1 as f64 + 0.114 * c.2 as f64;
+    let contrast = (luma(color) - luma(background)).abs() / 255.0;
+    (base * (1.0 - contrast)).max(1.0)
+}
+
+/// Mean edge magnitude under the points of `contour`.
+fn mean_edge_strength(contour: &[Point], edges: &EdgeMap) -> f64 {
+    if contour.is_empty() || edges.data.is_empty() {
+        return 0.0;
+    }
+    let (w, h) = (edges.width as usize, edges.height as usize);
+    let sum: f64 = contour
+        .iter()
+        .map(|p| {
+            let x = (p.x.round().max(0.0) as usize).min(w - 1);
+            let y = (p.y.round().max(0.0) as usize).min(h - 1);
+            edges.data[y * w + x] as f64
+        })
+        .sum();
+    sum / contour.len() as f64
+}
+
+/// Smoothing window for a contour with the given mean edge magnitude: the
+/// full `window` on flat boundaries, shrinking to none on the strongest.
+fn edge_adaptive_window(window: usize, strength: f64) -> usize {
+    (window as f64 * (1.0 - strength / 255.0)).round() as usize
+}
+
+/// Smooth → corner detection → Visvalingam-Whyatt → Bézier fit.
+fn fit_polyline(points: &[Point], options: &CurveOptions) -> Vec<BezierCurve> {
+    let smoothed = smooth_with_corners(points, options.smooth_window, options.corner_threshold);
+    let corners = detect_corners(&smoothed, options.corner_threshold);
+    let tol = options.simplification_tolerance;
+    let mut simplified = if options.closed && options.simplify_closed {
+        visvalingam_whyatt_closed(&smoothed, tol * tol, &corners)
+    } else {
+        visvalingam_whyatt(&smoothed, tol * tol, &corners)
+    };
+    if let Some(deg) = options.axis_snap_deg {
+        simplified = snap_axis_aligned(&simplified, deg, options.closed);
+    }
+    if let Some(spacing) = options.resample_spacing {
+        simplified = resample_even(&simplified, spacing, options.closed);
+    }
+    BezierFitter::new(options.curve_tolerance)
+        .with_adaptive_tolerance(options.adaptive_tolerance)
+        .with_loop_guard(options.loop_guard)
+        .fit_path(&simplified, options.closed)
+}
+
+/// Counters collected while vectorizing.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConversionStats {
+    /// Contours dropped for being below the minimum area (or collapsing
+    /// during simplification)
+    pub discarded_regions: usize,
+    /// Total pixel area of the dropped contours
+    pub discarded_area: f64,
+    /// K-means palette refinement iterations actually run
+    pub kmeans_iterations: usize,
+    /// Filled paths produced
+    pub path_count: usize,
+    /// Bézier curves across every path outline and hole
+    pub curve_count: usize,
+    /// Distinct fill colors among the paths (background excluded)
+    pub distinct_colors: usize,
+    /// Size of the rendered SVG; only filled in by
+    /// [`vectorize_enhanced_with_stats`], the batch converters and a
+    /// `target_max_bytes` search, 0 otherwise
+    pub svg_bytes: usize,
+    /// Curve tolerance the paths were fitted with (raised from the
+    /// configured one by a `target_max_bytes` search)
+    pub curve_tolerance: f64,
+    /// Quantized background color the image was traced against; `None` for
+    /// stats not filled in by a conversion, such as the defaults
+    pub background_color: Option<(u8, u8, u8, u8)>,
+}
+
+impl ConversionStats {
+    /// Share of the image covered by discarded regions (0.0–1.0).
+    pub fn discarded_fraction(&self, width: u32, height: u32) -> f64 {
+        let total = width as f64 * height as f64;
+        if total > 0.0 { self.discarded_area / total } else { 0.0 }
     }
 }
 
@@ -71,6 +898,22 @@ pub struct EnhancedVectorData {
     pub height: u32,
     pub background_color: (u8, u8, u8, u8),
     pub paths: Vec<EnhancedPath>,
+    pub stats: ConversionStats,
+    /// Pixel count of every display color, background included, largest first
+    pub coverage: Vec<((u8, u8, u8, u8), usize)>,
+    /// Outer boundary of all foreground pixels, one closed subpath per
+    /// island; empty unless `silhouette_outline` is set
+    pub silhouette: Vec<BezierCurve>,
+    /// Open stroked paths (e.g. line centerlines), drawn above the fills
+    pub strokes: Vec<EnhancedStroke>,
+}
+
+/// An open path drawn as a stroke rather than filled.
+#[derive(Debug, Clone)]
+pub struct EnhancedStroke {
+    pub curves: Vec<BezierCurve>,
+    pub color: (u8, u8, u8, u8),
+    pub width: f64,
 }
 
 /// A vectorized path with Bézier curves.
@@ -78,9 +921,27 @@ pub struct EnhancedVectorData {
 pub struct EnhancedPath {
     pub curves: Vec<BezierCurve>,
     pub color: (u8, u8, u8, u8),
+    /// Quantized color the path was traced from, before any recoloring
+    pub quantized: (u8, u8, u8, u8),
     pub area: usize,
-    /// Pre-built SVG path data for thin stripe rects (bypasses bezier_to_svg_path).
+    /// Pre-built SVG path data for thin stripe rects and raw contours
+    /// (bypasses bezier_to_svg_path).
     pub svg_override: Option<String>,
+    /// Closed rings cut out of the shape, wound against `curves`, written
+    /// after it in the same `d`
+    pub holes: Vec<Vec<BezierCurve>>,
+    /// Circle or ellipse written as its own element; `curves` is empty
+    pub primitive: Option<Primitive>,
+}
+
+impl EnhancedPath {
+    /// Trade the primitive for curves tracing it, so the path can share a
+    /// `d` with other subpaths.
+    fn flatten_primitive(&mut self) {
+        if let Some(primitive) = self.primitive.take() {
+            self.curves.extend(primitive.to_curves());
+        }
+    }
 }
 
 /// Run the enhanced vectorization pipeline.
@@ -92,140 +953,661 @@ pub fn vectorize_enhanced(
     image_data: &ImageData,
     options: &EnhancedOptions,
 ) -> Result<EnhancedVectorData> {
-    let width = image_data.width as usize;
-    let height = image_data.height as usize;
-    let pixel_count = width * height;
-    let is_small = pixel_count < 10_000;
+    let prepared = prepare_enhanced(image_data, options)?;
+    Ok(finish_for_options(&prepared, options, None))
+}
 
-    // Detect if image has many colors (photos, gradients)
-    let n_colors = count_distinct_colors(image_data);
-    let is_many_colors = n_colors > 16;
+/// Pipeline milestones reported by [`vectorize_enhanced_with_progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// Colors are quantized; tracing starts
+    QuantizeDone,
+    /// This many regions (colors, or components with `split_components`)
+    /// were traced and are about to be fitted
+    RegionsFound(usize),
+    /// `(done, total)`: another region finished fitting. Regions are fitted
+    /// in parallel, so `done` counts completions rather than naming a region.
+    PathFitted(usize, usize),
+}
 
-    // Determine target color count
-    let target_colors = if options.num_colors > 0 {
-        options.num_colors
-    } else if is_many_colors {
-        adaptive_color_count(image_data)
-    } else {
-        n_colors.min(64)
-    };
+/// Progress sink; called from the worker threads as well, hence `Sync`.
+type Progress<'a> = Option<&'a (dyn Fn(ProgressEvent) + Sync)>;
 
-    // Optional preprocessing (bilateral filter for photos)
-    let preprocessed = if options.preprocess && is_many_colors {
-        let opts = PreprocessOptions::photo();
-        preprocess(image_data, &opts)?
-    } else {
-        image_data.clone()
-    };
+/// (display color, quantized color, pixel count, contours) of one traced
+/// color or component.
+type ColorContours = ((u8, u8, u8, u8), (u8, u8, u8, u8), usize, Vec<Vec<Point>>);
 
-    // Edge detection + edge-aware quantization (k-means++ with perceptual distance)
-    let edges = detect_edges_sobel(&preprocessed);
-    // Detect if image is a photo (continuous tones) vs complex graphic (many distinct colors).
-    // Photos: many colors, smooth gradients → fewer smoothing passes, bilateral preprocess.
-    // Complex graphics: many colors, sharp edges, thin features → more smoothing passes.
-    let is_photo = is_many_colors && n_colors > 1000;
-    // More smoothing passes for non-photo images to merge thin stripes/artifacts.
-    // Edge-aware smoothing preserves strong-edge shapes while merging weak-edge thin features.
-    let smooth_passes = if !is_photo && is_many_colors {
-        options.smoothing_passes.max(4)
-    } else {
-        options.smoothing_passes
+/// [`vectorize_enhanced`], calling `progress` at each [`ProgressEvent`].
+pub fn vectorize_enhanced_with_progress(
+    image_data: &ImageData,
+    options: &EnhancedOptions,
+    progress: &(dyn Fn(ProgressEvent) + Sync),
+) -> Result<EnhancedVectorData> {
+    let prepared = prepare_with_progress(image_data, options, Some(progress))?;
+    Ok(finish_for_options(&prepared, options, Some(progress)))
+}
+
+/// Attempts [`finish_for_options`] makes at meeting `target_max_bytes`.
+const MAX_BUDGET_ATTEMPTS: usize = 10;
+/// Largest multiple of the configured tolerances tried for a byte budget.
+const MAX_BUDGET_SCALE: f64 = 64.0;
+
+/// Finish with the curve settings of `options`, coarsening them to meet
+/// `target_max_bytes` when set: the tolerances double until the SVG fits,
+/// then bisect between the last miss and the first fit.
+fn finish_for_options(prepared: &PreparedImage, options: &EnhancedOptions, progress: Progress) -> EnhancedVectorData {
+    let curve_options = options.curve_options();
+    let Some(budget) = options.target_max_bytes else {
+        return finish_with_progress(prepared, &curve_options, progress);
+    };
+    let attempt = |scale: f64| {
+        let scaled = CurveOptions {
+            curve_tolerance: curve_options.curve_tolerance * scale,
+            simplification_tolerance: curve_options.simplification_tolerance * scale,
+            ..curve_options.clone()
+        };
+        let mut data = finish_with_progress(prepared, &scaled, progress);
+        data.stats.svg_bytes = generate_enhanced_svg_with_options(&data, options).len();
+        data
     };
-    let (quantized, _indices, _palette) = quantize_edge_aware(
-        &preprocessed,
-        target_colors,
-        &edges,
-        options.edge_threshold,
-        smooth_passes,
-    );
 
-    // Group pixels by quantized color for region assignment
-    let mut color_pixels: HashMap<(u8, u8, u8, u8), Vec<(usize, usize)>> = HashMap::new();
-    for y in 0..height {
-        for x in 0..width {
-            let p = quantized.pixels[y * width + x];
-            let key = (p.r, p.g, p.b, p.a);
-            color_pixels.entry(key).or_default().push((x, y));
+    let mut best = attempt(1.0);
+    let (mut miss, mut fit) = (1.0, None);
+    let mut attempts = 1;
+    while best.stats.svg_bytes > budget && attempts < MAX_BUDGET_ATTEMPTS && miss < MAX_BUDGET_SCALE {
+        let data = attempt(miss * 2.0);
+        attempts += 1;
+        if data.stats.svg_bytes <= budget {
+            fit = Some(miss * 2.0);
+        } else {
+            miss *= 2.0;
         }
+        best = data;
     }
-
-    // Build a mapping from quantized color → average original color for display
-    let recolor_map: HashMap<(u8, u8, u8, u8), (u8, u8, u8, u8)> = if options.recolor && is_many_colors {
-        let mut map = HashMap::new();
-        for (&qcolor, pixels) in &color_pixels {
-            let mut sr: u64 = 0;
-            let mut sg: u64 = 0;
-            let mut sb: u64 = 0;
-            let mut sa: u64 = 0;
-            for &(x, y) in pixels {
-                let p = image_data.pixels[y * width + x];
-                sr += p.r as u64;
-                sg += p.g as u64;
-                sb += p.b as u64;
-                sa += p.a as u64;
+    if let Some(mut fit) = fit {
+        while attempts < MAX_BUDGET_ATTEMPTS {
+            let scale = (miss + fit) / 2.0;
+            let data = attempt(scale);
+            attempts += 1;
+            if data.stats.svg_bytes <= budget {
+                fit = scale;
+                best = data;
+            } else {
+                miss = scale;
             }
-            let n = pixels.len() as u64;
-            map.insert(qcolor, ((sr / n) as u8, (sg / n) as u8, (sb / n) as u8, (sa / n) as u8));
         }
-        map
-    } else {
-        HashMap::new()
-    };
+    }
+    best
+}
 
-    // Sort colors by pixel count (largest area first for proper z-order)
-    let mut color_list: Vec<_> = color_pixels.into_iter().collect();
-    color_list.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+/// [`vectorize_enhanced`], also rendering the SVG with `options` to fill in
+/// [`ConversionStats::svg_bytes`]. The stats are a copy of `data.stats`.
+pub fn vectorize_enhanced_with_stats(
+    image_data: &ImageData,
+    options: &EnhancedOptions,
+) -> Result<(EnhancedVectorData, ConversionStats)> {
+    let mut data = vectorize_enhanced(image_data, options)?;
+    data.stats.svg_bytes = generate_enhanced_svg_with_options(&data, options).len();
+    let stats = data.stats.clone();
+    Ok((data, stats))
+}
 
-    // Background detection using border pixels of quantized image.
-    // Use quantized color directly (not recolored) — recolored averages can produce
-    // unexpected dark colors for photos where the border region spans diverse originals.
-    let bg_quantized = detect_background_color(&quantized);
-    let background_color = bg_quantized;
+/// The expensive first half of [`vectorize_enhanced`]: quantized colors and
+/// their traced contours, ready for [`finish`] to smooth, simplify and fit.
+///
+/// Prepare once and finish as often as the curve settings change, e.g. while
+/// a user drags a tolerance slider.
+#[derive(Debug, Clone)]
+pub struct PreparedImage {
+    /// Options the image was prepared with; `finish` reads the non-curve
+    /// settings (raw contours, rectangle detection, hole filling) from here
+    options: EnhancedOptions,
+    width: u32,
+    height: u32,
+    /// Size to scale the result to, for `preview_downscale`
+    output_size: Option<(u32, u32)>,
+    background_color: (u8, u8, u8, u8),
+    coverage: Vec<((u8, u8, u8, u8), usize)>,
+    kmeans_iterations: usize,
+    is_small: bool,
+    is_many_colors: bool,
+    /// (display color, quantized color, pixel count, contours) per
+    /// non-background color, or
+    /// per connected component of one with `split_components`. Outer
+    /// boundaries wind counter-clockwise on screen and holes clockwise (see
+    /// [`is_hole`]); fitted outlines are wound the other way round
+    color_contours: Vec<ColorContours>,
+    /// Pixel count of the region each contour of `color_contours` bounds
+    contour_areas: Vec<Vec<usize>>,
+    /// Mean edge magnitude (0–255) along each contour of `color_contours`,
+    /// when `edge_adaptive_smoothing` is set
+    edge_strength: Vec<Vec<f64>>,
+    /// Outer foreground contours, when `silhouette_outline` is set
+    silhouette_contours: Vec<Vec<Point>>,
+    /// (display color, centerline) for thin strokes, when `centerline` is set
+    centerlines: Vec<((u8, u8, u8, u8), Centerline)>,
+}
 
-    let w_f = width as f64;
-    let h_f = height as f64;
-    let fitter = BezierFitter::new(options.curve_tolerance);
-    // For photos (many colors), use higher simplification tolerance to reduce SVG size
-    let simp_tol = if is_small {
-        options.simplification_tolerance.min(0.5)
-    } else if is_many_colors {
-        options.simplification_tolerance * 2.0
-    } else {
-        options.simplification_tolerance
+impl PreparedImage {
+    /// Width of the vector output.
+    pub fn width(&self) -> u32 {
+        self.output_size.map_or(self.width, |s| s.0)
+    }
+
+    /// Height of the vector output.
+    pub fn height(&self) -> u32 {
+        self.output_size.map_or(self.height, |s| s.1)
+    }
+
+    /// Smallest area a traced contour of `color` needs to be emitted.
+    fn min_contour_area(&self, color: (u8, u8, u8, u8), contour: &[Point]) -> f64 {
+        // Minimum polygon area: larger for photos to skip tiny noise regions
+        let min_poly_area = if self.is_many_colors { 20.0 } else { 8.0 };
+        if self.options.fill_holes_below_area > 0.0 && is_hole(contour) {
+            self.options.fill_holes_below_area
+        } else if self.options.contrast_min_area {
+            contrast_min_area(min_poly_area, color, self.background_color)
+        } else {
+            min_poly_area
+        }
+    }
+
+    /// Whether a traced contour of `color` is large enough to emit.
+    fn keeps_contour(&self, color: (u8, u8, u8, u8), contour: &[Point]) -> bool {
+        contour.len() >= 4 && polygon_area(contour) >= self.min_contour_area(color, contour)
+    }
+}
+
+/// A traced region as plain polygon rings, for GIS/CAD interop.
+#[derive(Debug, Clone)]
+pub struct ColoredPolygon {
+    pub color: (u8, u8, u8, u8),
+    /// Outer boundary ring, counter-clockwise on screen
+    pub exterior: Vec<Point>,
+    /// Rings of the holes cut out of `exterior`, clockwise on screen
+    pub holes: Vec<Vec<Point>>,
+}
+
+impl ColoredPolygon {
+    /// Covered area: the exterior minus the holes.
+    pub fn net_area(&self) -> f64 {
+        net_polygon_area(&self.exterior, &self.holes)
+    }
+}
+
+/// Quantize and trace like [`vectorize_enhanced`], but return the raw
+/// marching-squares rings as polygons instead of smoothing and fitting them.
+/// Each hole is attached to the smallest same-color exterior containing it.
+pub fn extract_polygons(image_data: &ImageData, options: &EnhancedOptions) -> Result<Vec<ColoredPolygon>> {
+    let prepared = prepare_enhanced(image_data, options)?;
+    let scale = |ring: &[Point]| -> Vec<Point> {
+        match prepared.output_size {
+            Some((w, h)) => {
+                let sx = w as f64 / prepared.width as f64;
+                let sy = h as f64 / prepared.height as f64;
+                ring.iter().map(|p| Point { x: p.x * sx, y: p.y * sy }).collect()
+            }
+            None => ring.to_vec(),
+        }
     };
-    // Minimum polygon area: larger for photos to skip tiny noise regions
-    let min_poly_area = if is_many_colors { 20.0 } else { 8.0 };
 
-    // For each color: build binary mask → marching squares → smooth → simplify → Bézier fit
-    // Collect (display_color, pixel_count, contours) tuples for parallel processing
-    let color_contours: Vec<((u8, u8, u8, u8), usize, Vec<Vec<Point>>)> = color_list
-        .iter()
-        .filter(|(color, _)| *color != bg_quantized)
-        .map(|(color, pixels)| {
-            let mut mask = vec![false; pixel_count];
-            for &(x, y) in pixels {
-                mask[y * width + x] = true;
+    let mut polygons = Vec::new();
+    for (color, _, _, contours) in &prepared.color_contours {
+        let kept: Vec<&Vec<Point>> = contours.iter().filter(|c| prepared.keeps_contour(*color, c)).collect();
+        let (holes, exteriors): (Vec<_>, Vec<_>) = kept.into_iter().partition(|c| is_hole(c));
+        let first = polygons.len();
+        polygons.extend(exteriors.iter().map(|ring| {
+            let mut exterior = scale(ring);
+            ensure_ccw(&mut exterior);
+            ColoredPolygon { color: *color, exterior, holes: Vec::new() }
+        }));
+        for hole in holes {
+            let owner = exteriors
+                .iter()
+                .enumerate()
+                .filter(|(_, ring)| point_in_ring(&hole[0], ring))
+                .min_by(|a, b| polygon_area(a.1).total_cmp(&polygon_area(b.1)));
+            if let Some((i, _)) = owner {
+                let mut hole = scale(hole);
+                ensure_ccw(&mut hole);
+                hole.reverse();
+                polygons[first + i].holes.push(hole);
             }
-            let contours = marching_squares_contours(&mask, width, height);
-            // Use recolored color for display if available
-            let display_color = recolor_map.get(color).copied().unwrap_or(*color);
-            (display_color, pixels.len(), contours)
-        })
-        .collect();
+        }
+    }
+    Ok(polygons)
+}
 
-    // Parallel: for each contour, smooth → simplify → Bézier fit
-    let mut enhanced_paths: Vec<EnhancedPath> = color_contours
-        .par_iter()
-        .flat_map(|(color, pixel_count, contours)| {
-            let mut paths = Vec::new();
+/// Even-odd ray-casting test.
+fn point_in_ring(p: &Point, ring: &[Point]) -> bool {
+    let mut inside = false;
+    let mut j = ring.len() - 1;
+    for i in 0..ring.len() {
+        let (a, b) = (&ring[i], &ring[j]);
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
 
-            for contour in contours {
-                if contour.len() < 4 {
-                    continue;
-                }
-                if polygon_area(contour) < min_poly_area {
-                    continue;
-                }
+/// Load-side work of the pipeline: alpha handling, quantization, background
+/// detection and marching-squares tracing. See [`PreparedImage`].
+pub fn prepare_enhanced(image_data: &ImageData, options: &EnhancedOptions) -> Result<PreparedImage> {
+    prepare_with_progress(image_data, options, None)
+}
+
+fn prepare_with_progress(image_data: &ImageData, options: &EnhancedOptions, progress: Progress) -> Result<PreparedImage> {
+    if let Some(max_size) = options.preview_downscale
+        && image_data.width.max(image_data.height) > max_size
+    {
+        let small = downscale_to_fit(image_data, max_size);
+        let full_options = EnhancedOptions { preview_downscale: None, ..options.clone() };
+        let mut prepared = prepare_with_progress(&small, &full_options, progress)?;
+        prepared.output_size = Some((image_data.width, image_data.height));
+        return Ok(prepared);
+    }
+
+    let flattened;
+    let image_data = if options.flatten_alpha {
+        let (r, g, b, a) = detect_background_color(image_data);
+        flattened = flatten_alpha(image_data, rgb::RGBA8::new(r, g, b, a));
+        &flattened
+    } else {
+        image_data
+    };
+    // Keyed transparency (e.g. a PNG tRNS entry) is already binary: treat
+    // its transparent pixels as cut out rather than as a color
+    let alpha_cutoff = if options.alpha_cutoff == 0 && has_cutout_transparency(image_data) {
+        1
+    } else {
+        options.alpha_cutoff
+    };
+    let binarized;
+    let image_data = if alpha_cutoff > 0 {
+        binarized = binarize_alpha(image_data, alpha_cutoff);
+        &binarized
+    } else {
+        image_data
+    };
+
+    let width = image_data.width as usize;
+    let height = image_data.height as usize;
+    let pixel_count = width * height;
+    let is_small = pixel_count < 10_000;
+
+    // Near-binary sources (scans, line art) skip preprocessing and k-means:
+    // every pixel snaps to the nearer of their two colors
+    let binary = match options.binary_tolerance {
+        Some(tolerance) if options.pinned_colors.is_empty() && options.fixed_palette.is_none() => {
+            binary_palette(image_data, tolerance, alpha_cutoff)
+        }
+        _ => None,
+    };
+
+    // Detect if image has many colors (photos, gradients)
+    let n_colors = count_distinct_colors(image_data);
+    let is_many_colors = n_colors > 16 && binary.is_none();
+
+    let (edges, palette, mut indices, kmeans_iterations) = if let Some([a, b]) = binary {
+        let edges = if options.edge_adaptive_smoothing {
+            detect_edges_sobel(image_data)
+        } else {
+            EdgeMap { width: image_data.width, height: image_data.height, data: Vec::new() }
+        };
+        let indices = image_data
+            .pixels
+            .iter()
+            .map(|p| (perceptual_dist_sq(p, &b) < perceptual_dist_sq(p, &a)) as usize)
+            .collect();
+        (edges, vec![a, b], indices, 0)
+    } else {
+        // Determine target color count
+        let target_colors = if let Some(fixed) = &options.fixed_palette {
+            fixed.len()
+        } else if options.num_colors > 0 {
+            options.num_colors
+        } else if let Some(max_colors) = options.elbow_max_colors {
+            elbow_color_count_seeded(image_data, max_colors, options.seed)
+        } else if is_many_colors {
+            adaptive_color_count(image_data)
+        } else {
+            n_colors.min(64)
+        }
+        .min(options.max_colors);
+
+        // Optional preprocessing (bilateral filter for photos)
+        let preprocessed = if options.preprocess && is_many_colors {
+            let opts = PreprocessOptions::photo();
+            preprocess(image_data, &opts)?
+        } else {
+            image_data.clone()
+        };
+
+        // Edge detection + edge-aware quantization (k-means++ with perceptual distance)
+        let edges = detect_edges_sobel(&preprocessed);
+        // Detect if image is a photo (continuous tones) vs complex graphic (many distinct colors).
+        // Photos: many colors, smooth gradients → fewer smoothing passes, bilateral preprocess.
+        // Complex graphics: many colors, sharp edges, thin features → more smoothing passes.
+        let is_photo = is_many_colors && n_colors > 1000;
+        // More smoothing passes for non-photo images to merge thin stripes/artifacts.
+        // Edge-aware smoothing preserves strong-edge shapes while merging weak-edge thin features.
+        let smooth_passes = if !is_photo && is_many_colors {
+            options.smoothing_passes.max(4)
+        } else {
+            options.smoothing_passes
+        };
+        let kmeans = KmeansOptions {
+            max_iterations: options.kmeans_iterations,
+            epsilon: options.kmeans_epsilon,
+            histogram: options.histogram_kmeans,
+            color_space: options.color_space,
+            dither: options.dither,
+            seed: options.seed,
+            pinned: options.pinned_colors.iter().map(|&(r, g, b)| rgb::RGBA8::new(r, g, b, 255)).collect(),
+            fixed_palette: options
+                .fixed_palette
+                .as_ref()
+                .map(|colors| colors.iter().map(|&(r, g, b)| rgb::RGBA8::new(r, g, b, 255)).collect()),
+        };
+        let (_quantized, indices, palette, kmeans_iterations) = quantize_edge_aware_with_kmeans(
+            &preprocessed,
+            target_colors,
+            &edges,
+            options.edge_threshold,
+            smooth_passes,
+            &kmeans,
+        );
+        (edges, palette, indices, kmeans_iterations)
+    };
+
+    // Duplicate palette entries (a pinned color k-means found as well) share
+    // one index, so each color is a single region
+    let first_index: Vec<usize> =
+        palette.iter().enumerate().map(|(i, c)| palette[..i].iter().position(|d| d == c).unwrap_or(i)).collect();
+    for index in &mut indices {
+        *index = first_index[*index];
+    }
+    if let Some(progress) = progress {
+        progress(ProgressEvent::QuantizeDone);
+    }
+    let to_pixels = |indices: &[usize]| indices.iter().map(|&i| palette[i]).collect();
+    let mut quantized = ImageData { width: image_data.width, height: image_data.height, pixels: to_pixels(&indices) };
+
+    // Pixels cut away by alpha_cutoff belong to no region
+    let excluded = |idx: usize| alpha_cutoff > 0 && image_data.pixels[idx].a == 0;
+
+    // Background detection using border pixels of quantized image.
+    // Use quantized color directly (not recolored) — recolored averages can produce
+    // unexpected dark colors for photos where the border region spans diverse originals.
+    // Cut-away pixels count as transparent, so a cut-out border leaves no background.
+    let bg_quantized = if alpha_cutoff > 0 {
+        let pixels = quantized
+            .pixels
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| if excluded(i) { rgb::RGBA8::new(0, 0, 0, 0) } else { p })
+            .collect();
+        detect_background_color_with_fallback(
+            &ImageData { width: quantized.width, height: quantized.height, pixels },
+            options.background_min_border_share,
+        )
+    } else {
+        detect_background_color_with_fallback(&quantized, options.background_min_border_share)
+    };
+    let background_color = bg_quantized;
+
+    if options.heal_gaps {
+        let (r, g, b, a) = bg_quantized;
+        if let Some(bg_index) = palette.iter().position(|&c| c == rgb::RGBA8::new(r, g, b, a)) {
+            heal_gaps(&mut indices, width, height, bg_index);
+            quantized.pixels = to_pixels(&indices);
+        }
+    }
+
+    // Minimum polygon area: larger for photos to skip tiny noise regions
+    let min_poly_area = if is_many_colors { 20.0 } else { 8.0 };
+
+    if options.merge_small_into_neighbor {
+        merge_small_regions(&mut indices, width, height, min_poly_area as usize, |a, b| {
+            perceptual_dist_sq(&palette[a], &palette[b])
+        });
+        quantized.pixels = to_pixels(&indices);
+    }
+
+    // Group pixels by palette index for region assignment
+    let color_pixels: Vec<(_, Vec<(usize, usize)>)> =
+        group_by_palette_index(&indices, palette.len(), width, excluded)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, pixels)| !pixels.is_empty())
+            .map(|(i, pixels)| ((palette[i].r, palette[i].g, palette[i].b, palette[i].a), pixels))
+            .collect();
+
+    // Build a mapping from quantized color → average original color for display
+    // A fixed palette is reproduced exactly, so it is never recolored
+    let recolor = options.recolor && is_many_colors && options.fixed_palette.is_none();
+    let recolor_map: HashMap<_, _> = if recolor {
+        let mut map = HashMap::new();
+        for &(qcolor, ref pixels) in &color_pixels {
+            let (r, g, b, a) = qcolor;
+            if a == 255 && options.pinned_colors.contains(&(r, g, b)) {
+                continue;
+            }
+            let mut sr: u64 = 0;
+            let mut sg: u64 = 0;
+            let mut sb: u64 = 0;
+            let mut sa: u64 = 0;
+            for &(x, y) in pixels {
+                let p = image_data.pixels[y * width + x];
+                sr += p.r as u64;
+                sg += p.g as u64;
+                sb += p.b as u64;
+                sa += p.a as u64;
+            }
+            let n = pixels.len() as u64;
+            map.insert(qcolor, ((sr / n) as u8, (sg / n) as u8, (sb / n) as u8, (sa / n) as u8));
+        }
+        map
+    } else {
+        HashMap::new()
+    };
+
+    // Sort colors by pixel count (largest area first for proper z-order)
+    let mut color_list = color_pixels;
+    color_list.sort_by_key(|c| std::cmp::Reverse(c.1.len()));
+
+    let mut coverage: Vec<((u8, u8, u8, u8), usize)> = Vec::new();
+    for (color, pixels) in &color_list {
+        let display = if *color == bg_quantized {
+            background_color
+        } else {
+            recolor_map.get(color).copied().unwrap_or(*color)
+        };
+        match coverage.iter_mut().find(|(c, _)| *c == display) {
+            Some(entry) => entry.1 += pixels.len(),
+            None => coverage.push((display, pixels.len())),
+        }
+    }
+    coverage.sort_by_key(|c| std::cmp::Reverse(c.1));
+
+    let mut silhouette_contours = Vec::new();
+    if options.silhouette_outline.is_some() {
+        let bg = rgb::RGBA8::new(bg_quantized.0, bg_quantized.1, bg_quantized.2, bg_quantized.3);
+        let mask: Vec<bool> = (0..pixel_count)
+            .map(|i| !excluded(i) && quantized.pixels[i] != bg)
+            .collect();
+        silhouette_contours = marching_squares_contours(&mask, width, height)
+            .into_iter()
+            .filter(|contour| !is_hole(contour) && polygon_area(contour) >= min_poly_area)
+            .collect();
+    }
+
+    // For each color: build binary mask → marching squares → smooth → simplify → Bézier fit
+    // Colors are traced in parallel; collect (display_color, color, pixel_count, contours)
+    // tuples in color order for the fitting stage
+    let traced: Vec<_> = crate::threads::install(|| {
+        color_list
+            .par_iter()
+            .filter(|(color, _)| *color != bg_quantized)
+            .map(|(color, pixels)| {
+                let mut mask = vec![false; pixel_count];
+                for &(x, y) in pixels {
+                    mask[y * width + x] = true;
+                }
+                if options.mask_smoothing > 0 {
+                    mask = majority_smooth(&mask, width, height, options.mask_smoothing);
+                }
+                // Use recolored color for display if available
+                let display_color = recolor_map.get(color).copied().unwrap_or(*color);
+                let lines = if options.centerline {
+                    extract_centerlines(&mut mask, width, height, options.centerline_max_width)
+                } else {
+                    Vec::new()
+                };
+                let components = if options.split_components {
+                    component_contours(&mask, width, height)
+                } else {
+                    vec![(pixels.len(), marching_squares_contours(&mask, width, height))]
+                };
+                let areas = contour_pixel_counts(&mask, width, height, &components);
+                (display_color, *color, components, areas, lines)
+            })
+            .collect()
+    });
+    let mut color_contours: Vec<ColorContours> = Vec::new();
+    let mut contour_areas = Vec::new();
+    let mut centerlines = Vec::new();
+    for (display_color, color, components, areas, lines) in traced {
+        centerlines.extend(lines.into_iter().map(|line| (display_color, line)));
+        color_contours.extend(components.into_iter().map(|(count, contours)| (display_color, color, count, contours)));
+        contour_areas.extend(areas);
+    }
+
+    let edge_strength = if options.edge_adaptive_smoothing {
+        color_contours
+            .iter()
+            .map(|(_, _, _, contours)| contours.iter().map(|c| mean_edge_strength(c, &edges)).collect())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    if let Some(progress) = progress {
+        progress(ProgressEvent::RegionsFound(color_contours.len()));
+    }
+    Ok(PreparedImage {
+        options: options.clone(),
+        width: image_data.width,
+        height: image_data.height,
+        output_size: None,
+        background_color,
+        coverage,
+        kmeans_iterations,
+        is_small,
+        is_many_colors,
+        color_contours,
+        contour_areas,
+        edge_strength,
+        silhouette_contours,
+        centerlines,
+    })
+}
+
+/// Cheap second half of [`vectorize_enhanced`]: smooth, simplify and
+/// Bézier-fit the contours of `prepared` with `curve_options`.
+pub fn finish(prepared: &PreparedImage, curve_options: &CurveOptions) -> EnhancedVectorData {
+    finish_with_progress(prepared, curve_options, None)
+}
+
+fn finish_with_progress(prepared: &PreparedImage, curve_options: &CurveOptions, progress: Progress) -> EnhancedVectorData {
+    crate::threads::install(|| {
+        let data = finish_on_pool(prepared, curve_options, progress);
+        match prepared.output_size {
+            Some((width, height)) => {
+                scale_vector_data(data, width, height, &prepared.options.path_format())
+            }
+            None => data,
+        }
+    })
+}
+
+fn finish_on_pool(prepared: &PreparedImage, curve_options: &CurveOptions, progress: Progress) -> EnhancedVectorData {
+    let options = &prepared.options;
+    let (is_small, is_many_colors) = (prepared.is_small, prepared.is_many_colors);
+    let w_f = prepared.width as f64;
+    let h_f = prepared.height as f64;
+    let path_format = options.path_format();
+    let fitter = BezierFitter::new(curve_options.curve_tolerance)
+        .with_adaptive_tolerance(curve_options.adaptive_tolerance)
+        .with_loop_guard(curve_options.loop_guard);
+    // For photos (many colors), use higher simplification tolerance to reduce SVG size
+    let simp_tol = if is_small {
+        curve_options.simplification_tolerance.min(0.5)
+    } else if is_many_colors {
+        curve_options.simplification_tolerance * 2.0
+    } else {
+        curve_options.simplification_tolerance
+    };
+    let closed_curves = CurveOptions { closed: true, ..curve_options.clone() };
+    let mut silhouette: Vec<BezierCurve> = prepared
+        .silhouette_contours
+        .iter()
+        .flat_map(|contour| fit_polyline(contour, &closed_curves))
+        .collect();
+    for curve in &mut silhouette {
+        for p in [&mut curve.control1, &mut curve.control2] {
+            p.x = p.x.clamp(0.0, w_f);
+            p.y = p.y.clamp(0.0, h_f);
+        }
+    }
+
+    // Parallel: for each contour, smooth → simplify → Bézier fit
+    let fitted = std::sync::atomic::AtomicUsize::new(0);
+    let mut per_color: Vec<(usize, Vec<EnhancedPath>, ConversionStats)> = prepared
+        .color_contours
+        .par_iter()
+        .enumerate()
+        .map(|(i, (color, quantized, pixel_count, contours))| {
+            let mut paths = Vec::new();
+            // Index into `contours` of the contour each path was traced from
+            let mut sources = Vec::new();
+            // Primitives are single elements and cannot cut holes
+            let encloses_hole =
+                |outline: &[Point]| contours.iter().any(|c| is_hole(c) && point_in_ring(&c[0], outline));
+            let mut stats = ConversionStats::default();
+            let mut discard = |contour: &[Point]| {
+                stats.discarded_regions += 1;
+                stats.discarded_area += polygon_area(contour);
+            };
+
+            for (j, contour) in contours.iter().enumerate() {
+                let min_area = prepared.min_contour_area(*color, contour);
+                if contour.len() < 4 || polygon_area(contour) < min_area {
+                    discard(contour);
+                    continue;
+                }
+
+                let area = prepared.contour_areas.get(i).and_then(|a| a.get(j)).copied().unwrap_or(*pixel_count);
+                let clockwise = !is_hole(contour);
+                if options.raw_contours {
+                    let mut ring = contour.clone();
+                    if (signed_polygon_area(&ring) > 0.0) != clockwise {
+                        ring.reverse();
+                    }
+                    sources.push(j);
+                    paths.push(EnhancedPath {
+                        curves: Vec::new(),
+                        color: *color,
+                        quantized: *quantized,
+                        area,
+                        svg_override: Some(path_format.polyline_path(&ring, true)),
+                        holes: Vec::new(),
+                        primitive: None,
+                    });
+                    continue;
+                }
 
                 // Fast path: thin stripe contours (height or width < 2px) →
                 // emit as simple rectangle directly (bypass Bézier fitter which collapses thin shapes).
@@ -238,30 +1620,93 @@ pub fn vectorize_enhanced(
                     let x1 = if cb_w < 2.0 { x0 + cb_w.ceil().max(1.0) as i64 } else { cb_max_x.round() as i64 };
                     let y1 = if cb_h < 2.0 { y0 + cb_h.ceil().max(1.0) as i64 } else { cb_max_y.round() as i64 };
                     // Emit direct SVG rect path (bypasses bezier_to_svg_path collinear merge)
-                    let svg = format!("M{x0},{y0}L{x1},{y0}L{x1},{y1}L{x0},{y1}Z");
+                    let svg = rect_ring(x0, y0, x1, y1, clockwise);
+                    sources.push(j);
+                    paths.push(EnhancedPath {
+                        curves: Vec::new(),
+                        color: *color,
+                        quantized: *quantized,
+                        area,
+                        svg_override: Some(svg),
+                        holes: Vec::new(),
+                        primitive: None,
+                    });
+                    continue;
+                }
+
+                if options.detect_primitives
+                    && clockwise
+                    && !encloses_hole(contour)
+                    && let Some(primitive) = rect_primitive(contour)
+                {
+                    sources.push(j);
+                    paths.push(EnhancedPath {
+                        curves: Vec::new(),
+                        color: *color,
+                        quantized: *quantized,
+                        area,
+                        svg_override: None,
+                        holes: Vec::new(),
+                        primitive: Some(primitive),
+                    });
+                    continue;
+                }
+
+                if options.detect_rects && let Some(svg) = rect_path(contour) {
+                    sources.push(j);
                     paths.push(EnhancedPath {
                         curves: Vec::new(),
                         color: *color,
-                        area: *pixel_count,
+                        quantized: *quantized,
+                        area,
                         svg_override: Some(svg),
+                        holes: Vec::new(),
+                        primitive: None,
                     });
                     continue;
                 }
 
                 // Smooth with corner preservation (enhanced)
-                let smoothed = smooth_with_corners(
-                    contour,
-                    options.smooth_window,
-                    options.corner_threshold,
-                );
+                let smooth_window = match prepared.edge_strength.get(i).and_then(|s| s.get(j)) {
+                    Some(&strength) => edge_adaptive_window(curve_options.smooth_window, strength),
+                    None => curve_options.smooth_window,
+                };
+                let smoothed = smooth_with_corners(contour, smooth_window, curve_options.corner_threshold);
 
                 // Detect corners for Visvalingam-Whyatt (enhanced)
-                let corners = detect_corners(&smoothed, options.corner_threshold);
+                let corners = detect_corners(&smoothed, curve_options.corner_threshold);
 
                 // Visvalingam-Whyatt simplification with corner preservation (enhanced)
-                let simplified = visvalingam_whyatt(&smoothed, simp_tol * simp_tol, &corners);
+                let simplified = if curve_options.simplify_closed {
+                    visvalingam_whyatt_closed(&smoothed, simp_tol * simp_tol, &corners)
+                } else {
+                    visvalingam_whyatt(&smoothed, simp_tol * simp_tol, &corners)
+                };
+                let simplified = match curve_options.axis_snap_deg {
+                    Some(deg) => snap_axis_aligned(&simplified, deg, true),
+                    None => simplified,
+                };
 
                 if simplified.len() < 3 {
+                    discard(contour);
+                    continue;
+                }
+
+                if options.detect_primitives
+                    && clockwise
+                    && !encloses_hole(contour)
+                    && let Some(primitive) = fit_primitive(&simplified, PRIMITIVE_TOLERANCE)
+                {
+                    sources.push(j);
+                    paths.push(EnhancedPath {
+                        curves: Vec::new(),
+                        color: *color,
+                        quantized: *quantized,
+                        area,
+                        svg_override: None,
+                        holes: Vec::new(),
+                        primitive: Some(primitive),
+                    });
                     continue;
                 }
 
@@ -283,12 +1728,19 @@ pub fn vectorize_enhanced(
                 // Without this, duplicate points at image corners break the fitter's angle detection.
                 let snapped = dedup_consecutive(&snapped, 0.5);
 
-                if snapped.len() < 3 || polygon_area(&snapped) < min_poly_area {
+                if snapped.len() < 3 || polygon_area(&snapped) < min_area {
+                    discard(contour);
                     continue;
                 }
 
+                let snapped = match curve_options.resample_spacing {
+                    Some(spacing) => resample_even(&snapped, spacing, true),
+                    None => snapped,
+                };
+
                 // Cubic Bézier fitting with internal corner detection (enhanced)
                 let mut curves = fitter.fit_path(&snapped, true);
+                orient_curves(&mut curves, clockwise);
 
                 // Clamp control points to image bounds (prevents bulging corners)
                 for curve in &mut curves {
@@ -298,29 +1750,409 @@ pub fn vectorize_enhanced(
                     curve.control2.y = curve.control2.y.clamp(0.0, h_f);
                 }
 
-                if !curves.is_empty() {
+                if curves.is_empty() {
+                    discard(contour);
+                } else {
+                    sources.push(j);
                     paths.push(EnhancedPath {
                         curves,
                         color: *color,
-                        area: *pixel_count,
+                        quantized: *quantized,
+                        area,
                         svg_override: None,
+                        holes: Vec::new(),
+                        primitive: None,
                     });
                 }
             }
 
-            paths
+            let (mut paths, orphans) = attach_holes(paths, &sources, contours);
+            for j in orphans {
+                stats.discarded_regions += 1;
+                stats.discarded_area += polygon_area(&contours[j]);
+            }
+            if options.split_components {
+                paths = merge_component_paths(paths);
+            }
+            if let Some(progress) = progress {
+                let done = fitted.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+                progress(ProgressEvent::PathFitted(done, prepared.color_contours.len()));
+            }
+            (*pixel_count, paths, stats)
         })
         .collect();
+    // Back-to-front layering, largest regions first unless configured otherwise
+    options.z_order.sort(&mut per_color, |(pixel_count, _, _)| *pixel_count);
 
-    // Sort: largest regions first (back-to-front layering)
-    enhanced_paths.sort_unstable_by(|a, b| b.area.cmp(&a.area));
+    let mut enhanced_paths = Vec::new();
+    let mut stats = ConversionStats {
+        kmeans_iterations: prepared.kmeans_iterations,
+        curve_tolerance: curve_options.curve_tolerance,
+        ..Default::default()
+    };
+    for (_, paths, color_stats) in per_color {
+        enhanced_paths.extend(paths);
+        stats.discarded_regions += color_stats.discarded_regions;
+        stats.discarded_area += color_stats.discarded_area;
+    }
+    stats.path_count = enhanced_paths.len();
+    stats.curve_count = enhanced_paths
+        .iter()
+        .map(|p| p.curves.len() + p.holes.iter().map(Vec::len).sum::<usize>())
+        .sum();
+    stats.distinct_colors = enhanced_paths.iter().map(|p| p.color).collect::<std::collections::HashSet<_>>().len();
+    stats.background_color = Some(prepared.background_color);
 
-    Ok(EnhancedVectorData {
-        width: image_data.width,
-        height: image_data.height,
-        background_color,
+    EnhancedVectorData {
+        width: prepared.width,
+        height: prepared.height,
+        background_color: prepared.background_color,
         paths: enhanced_paths,
-    })
+        stats,
+        coverage: prepared.coverage.clone(),
+        silhouette,
+        strokes: prepared
+            .centerlines
+            .iter()
+            .map(|(color, line)| stroke_polyline(&line.points, *color, line.width, curve_options))
+            .collect(),
+    }
+}
+
+/// Stretch vector data traced on a downscaled image to `width`x`height`.
+fn scale_vector_data(
+    mut data: EnhancedVectorData,
+    width: u32,
+    height: u32,
+    format: &PathFormat,
+) -> EnhancedVectorData {
+    let sx = width as f64 / data.width as f64;
+    let sy = height as f64 / data.height as f64;
+    let area_scale = sx * sy;
+    for path in &mut data.paths {
+        for curve in path.curves.iter_mut().chain(path.holes.iter_mut().flatten()) {
+            for p in [&mut curve.start, &mut curve.control1, &mut curve.control2, &mut curve.end] {
+                p.x *= sx;
+                p.y *= sy;
+            }
+        }
+        if let Some(ref d) = path.svg_override {
+            path.svg_override = Some(scale_path_data(d, sx, sy, format));
+        }
+        path.primitive = path.primitive.map(|primitive| primitive.scaled(sx, sy));
+        path.area = (path.area as f64 * area_scale).round() as usize;
+    }
+    for curve in &mut data.silhouette {
+        for p in [&mut curve.start, &mut curve.control1, &mut curve.control2, &mut curve.end] {
+            p.x *= sx;
+            p.y *= sy;
+        }
+    }
+    for stroke in &mut data.strokes {
+        for curve in &mut stroke.curves {
+            for p in [&mut curve.start, &mut curve.control1, &mut curve.control2, &mut curve.end] {
+                p.x *= sx;
+                p.y *= sy;
+            }
+        }
+        stroke.width *= (sx + sy) / 2.0;
+    }
+    for entry in &mut data.coverage {
+        entry.1 = (entry.1 as f64 * area_scale).round() as usize;
+    }
+    data.stats.discarded_area *= area_scale;
+    data.width = width;
+    data.height = height;
+    data
+}
+
+/// Scale the `x,y` pairs of `M`/`L`/`Z` path data as written by
+/// [`PathFormat::polyline_path`] and the rectangle fast paths.
+fn scale_path_data(d: &str, sx: f64, sy: f64, format: &PathFormat) -> String {
+    map_path_data(d, format, |v, is_x| v * if is_x { sx } else { sy })
+}
+
+/// Rewrite the `x,y` numbers of `M`/`L`/`Z` path data with `f(value, is_x)`.
+fn map_path_data(d: &str, format: &PathFormat, f: impl Fn(f64, bool) -> f64) -> String {
+    let mut out = String::with_capacity(d.len());
+    let mut number = String::new();
+    let mut is_x = true;
+    let mut flush = |number: &mut String, out: &mut String| {
+        if let Ok(v) = number.parse::<f64>() {
+            out.push_str(&format.num(f(v, is_x)));
+            is_x = !is_x;
+        }
+        number.clear();
+    };
+    for c in d.chars() {
+        if c.is_ascii_digit() || c == '.' || c == '-' {
+            number.push(c);
+        } else {
+            flush(&mut number, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut number, &mut out);
+    out
+}
+
+/// Round every coordinate (end and control points alike) to the nearest
+/// multiple of `grid`. Curves whose ends land on the same grid point are
+/// dropped: they no longer span a segment, and the neighbors still meet.
+fn snap_vector_data(data: &EnhancedVectorData, grid: f64, format: &PathFormat) -> EnhancedVectorData {
+    let snap = |v: f64| (v / grid).round() * grid;
+    let snap_curves = |curves: &[BezierCurve]| -> Vec<BezierCurve> {
+        curves
+            .iter()
+            .filter_map(|curve| {
+                let mut c = curve.clone();
+                for p in [&mut c.start, &mut c.control1, &mut c.control2, &mut c.end] {
+                    p.x = snap(p.x);
+                    p.y = snap(p.y);
+                }
+                (c.start.x != c.end.x || c.start.y != c.end.y).then_some(c)
+            })
+            .collect()
+    };
+    EnhancedVectorData {
+        paths: data
+            .paths
+            .iter()
+            .map(|path| EnhancedPath {
+                curves: snap_curves(&path.curves),
+                holes: path.holes.iter().map(|hole| snap_curves(hole)).collect(),
+                svg_override: path.svg_override.as_ref().map(|d| map_path_data(d, format, |v, _| snap(v))),
+                primitive: path.primitive.map(|primitive| primitive.map(snap)),
+                ..path.clone()
+            })
+            .collect(),
+        silhouette: snap_curves(&data.silhouette),
+        strokes: data
+            .strokes
+            .iter()
+            .map(|stroke| EnhancedStroke { curves: snap_curves(&stroke.curves), ..stroke.clone() })
+            .collect(),
+        stats: data.stats.clone(),
+        coverage: data.coverage.clone(),
+        ..*data
+    }
+}
+
+/// Pixel count and contours of each 8-connected component of `mask`, traced
+/// one component at a time (on its bounding box) so each keeps its own
+/// exterior and holes.
+fn component_contours(mask: &[bool], width: usize, height: usize) -> Vec<(usize, Vec<Vec<Point>>)> {
+    let labels: Vec<usize> = mask.iter().map(|&inside| inside as usize).collect();
+    let mut visited = vec![false; mask.len()];
+    let mut components = Vec::new();
+    for start in 0..mask.len() {
+        if !mask[start] || visited[start] {
+            continue;
+        }
+        let (sx, sy) = ((start % width) as u32, (start / width) as u32);
+        let pixels = flood_fill_by_index(sx, sy, width as u32, height as u32, &labels, 1, &mut visited);
+        let (mut x0, mut y0, mut x1, mut y1) = (sx, sy, sx, sy);
+        for &(x, y) in &pixels {
+            (x0, y0, x1, y1) = (x0.min(x), y0.min(y), x1.max(x), y1.max(y));
+        }
+        let (crop_w, crop_h) = ((x1 - x0 + 1) as usize, (y1 - y0 + 1) as usize);
+        let mut crop = vec![false; crop_w * crop_h];
+        for &(x, y) in &pixels {
+            crop[(y - y0) as usize * crop_w + (x - x0) as usize] = true;
+        }
+        let contours = marching_squares_contours(&crop, crop_w, crop_h)
+            .into_iter()
+            .map(|contour| {
+                contour
+                    .into_iter()
+                    .map(|p| Point { x: p.x + x0 as f64, y: p.y + y0 as f64 })
+                    .collect()
+            })
+            .collect();
+        components.push((pixels.len(), contours));
+    }
+    components
+}
+
+/// Pixel count of the 4-connected region of `mask` each contour of each
+/// `(pixel count, contours)` component bounds (for a hole, the region around
+/// it), labeling the mask once for all of them. Every contour point lies
+/// midway between an inside and an outside pixel centre, which names the
+/// region; a contour with no such point off the image border gets its
+/// component's count.
+fn contour_pixel_counts(
+    mask: &[bool],
+    width: usize,
+    height: usize,
+    components: &[(usize, Vec<Vec<Point>>)],
+) -> Vec<Vec<usize>> {
+    // 4-connected, as marching squares separates diagonal neighbours
+    let mut labels = vec![usize::MAX; mask.len()];
+    let mut counts = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..mask.len() {
+        if !mask[start] || labels[start] != usize::MAX {
+            continue;
+        }
+        let label = counts.len();
+        let mut count = 0;
+        labels[start] = label;
+        stack.push(start);
+        while let Some(i) = stack.pop() {
+            count += 1;
+            let (x, y) = (i % width, i / width);
+            let neighbours = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < width).then(|| i + 1),
+                (y > 0).then(|| i - width),
+                (y + 1 < height).then(|| i + width),
+            ];
+            for n in neighbours.into_iter().flatten() {
+                if mask[n] && labels[n] == usize::MAX {
+                    labels[n] = label;
+                    stack.push(n);
+                }
+            }
+        }
+        counts.push(count);
+    }
+
+    let inside_label = |p: &Point| {
+        [(-0.5, 0.0), (0.5, 0.0), (0.0, -0.5), (0.0, 0.5)].into_iter().find_map(|(dx, dy)| {
+            let (cx, cy) = (p.x + dx, p.y + dy);
+            if cx < 0.0 || cy < 0.0 || cx.fract() != 0.5 || cy.fract() != 0.5 {
+                return None;
+            }
+            let (px, py) = (cx as usize, cy as usize);
+            let i = py * width + px;
+            (px < width && py < height && mask[i]).then(|| labels[i])
+        })
+    };
+    components
+        .iter()
+        .map(|(total, contours)| {
+            contours
+                .iter()
+                .map(|contour| contour.iter().find_map(inside_label).map_or(*total, |label| counts[label]))
+                .collect()
+        })
+        .collect()
+}
+
+/// Fold the paths traced from one component into a single path.
+fn merge_component_paths(paths: Vec<EnhancedPath>) -> Vec<EnhancedPath> {
+    let mut iter = paths.into_iter();
+    let Some(mut merged) = iter.next() else {
+        return Vec::new();
+    };
+    for mut path in iter {
+        merged.flatten_primitive();
+        path.flatten_primitive();
+        merged.curves.extend(path.curves);
+        merged.holes.extend(path.holes);
+        merged.area += path.area;
+        if let Some(svg) = path.svg_override {
+            merged.svg_override.get_or_insert_with(String::new).push_str(&svg);
+        }
+    }
+    vec![merged]
+}
+
+/// Move each hole path into `holes` of the smallest outline path whose
+/// source contour contains it (the pre-built data of a hole joins the
+/// outline's). `sources[k]` is the index into `contours` of path `k`.
+/// Holes whose outline was dropped go too; their contour indices are
+/// returned alongside the paths.
+fn attach_holes(
+    paths: Vec<EnhancedPath>,
+    sources: &[usize],
+    contours: &[Vec<Point>],
+) -> (Vec<EnhancedPath>, Vec<usize>) {
+    let ring = |k: usize| &contours[sources[k]];
+    let outlines: Vec<_> = (0..paths.len())
+        .filter(|&k| !is_hole(ring(k)))
+        .map(|k| (k, bounds_from_points(ring(k)), polygon_area(ring(k))))
+        .collect();
+
+    let mut slots: Vec<Option<EnhancedPath>> = paths.into_iter().map(Some).collect();
+    let mut orphans = Vec::new();
+    for k in 0..slots.len() {
+        if !is_hole(ring(k)) {
+            continue;
+        }
+        let probe = &ring(k)[0];
+        let owner = outlines
+            .iter()
+            .filter(|(_, (x0, y0, x1, y1), _)| probe.x >= *x0 && probe.x <= *x1 && probe.y >= *y0 && probe.y <= *y1)
+            .filter(|(o, _, _)| point_in_ring(probe, ring(*o)))
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(o, _, _)| *o);
+        let hole = slots[k].take().expect("each hole is visited once");
+        match owner.and_then(|o| slots[o].as_mut()) {
+            Some(outline) => {
+                outline.flatten_primitive();
+                if !hole.curves.is_empty() {
+                    outline.holes.push(hole.curves);
+                }
+                if let Some(svg) = hole.svg_override {
+                    outline.svg_override.get_or_insert_with(String::new).push_str(&svg);
+                }
+            }
+            None => orphans.push(sources[k]),
+        }
+    }
+    (slots.into_iter().flatten().collect(), orphans)
+}
+
+/// Reverse `curves` unless their outline already winds clockwise on screen
+/// (positive shoelace area with y pointing down) as `clockwise` asks:
+/// simplification can flip a thin ring inside out.
+fn orient_curves(curves: &mut [BezierCurve], clockwise: bool) {
+    let outline: Vec<Point> = curves.iter().map(|c| c.start.clone()).collect();
+    if (signed_polygon_area(&outline) > 0.0) == clockwise {
+        return;
+    }
+    curves.reverse();
+    for curve in curves.iter_mut() {
+        std::mem::swap(&mut curve.start, &mut curve.end);
+        std::mem::swap(&mut curve.control1, &mut curve.control2);
+    }
+}
+
+/// Rectangle path data, clockwise on screen or against it.
+fn rect_ring(x0: i64, y0: i64, x1: i64, y1: i64, clockwise: bool) -> String {
+    if clockwise {
+        format!("M{x0},{y0}L{x1},{y0}L{x1},{y1}L{x0},{y1}Z")
+    } else {
+        format!("M{x0},{y0}L{x0},{y1}L{x1},{y1}L{x1},{y0}Z")
+    }
+}
+
+/// `<rect>` for a contour whose area is within [`RECT_AREA_TOLERANCE`] of
+/// its bounding box's, with corners rounded to whole pixels.
+fn rect_primitive(contour: &[Point]) -> Option<Primitive> {
+    let (min_x, min_y, max_x, max_y) = bounds_from_points(contour);
+    let (x, y) = (min_x.round(), min_y.round());
+    let (width, height) = (max_x.round() - x, max_y.round() - y);
+    let box_area = width * height;
+    if box_area <= 0.0 || (polygon_area(contour) - box_area).abs() > box_area * RECT_AREA_TOLERANCE {
+        return None;
+    }
+    Some(Primitive::Rect { x, y, width, height })
+}
+
+/// Straight-line path for a contour that is essentially an axis-aligned
+/// rectangle, with corners rounded to whole pixels: clockwise for an
+/// outline, counter-clockwise for a hole.
+fn rect_path(contour: &[Point]) -> Option<String> {
+    let (min_x, min_y, max_x, max_y) = bounds_from_points(contour);
+    let box_area = (max_x - min_x) * (max_y - min_y);
+    if box_area <= 0.0 || polygon_area(contour) < box_area * RECT_FILL_RATIO {
+        return None;
+    }
+    let (x0, y0) = (min_x.round() as i64, min_y.round() as i64);
+    let (x1, y1) = (max_x.round() as i64, max_y.round() as i64);
+    Some(rect_ring(x0, y0, x1, y1, !is_hole(contour)))
 }
 
 /// Remove consecutive near-duplicate points (distance < threshold).
@@ -399,6 +2231,17 @@ fn inject_image_corners(points: &[Point], w: f64, h: f64) -> Vec<Point> {
 
 /// Compute signed polygon area (Shoelace formula).
 fn polygon_area(points: &[Point]) -> f64 {
+    signed_polygon_area(points).abs()
+}
+
+/// Area of a polygon with holes: the exterior's area minus each hole's,
+/// whatever the winding of the rings.
+pub fn net_polygon_area(exterior: &[Point], holes: &[Vec<Point>]) -> f64 {
+    polygon_area(exterior) - holes.iter().map(|h| polygon_area(h)).sum::<f64>()
+}
+
+/// Shoelace area keeping the sign of the winding direction.
+fn signed_polygon_area(points: &[Point]) -> f64 {
     let n = points.len();
     if n < 3 {
         return 0.0;
@@ -409,42 +2252,128 @@ fn polygon_area(points: &[Point]) -> f64 {
         area += points[i].x * points[j].y;
         area -= points[j].x * points[i].y;
     }
-    (area / 2.0).abs()
+    area / 2.0
 }
 
-/// Generate SVG string from enhanced vector data.
-/// Uses gap-filling strokes and consecutive same-color path grouping.
-pub fn generate_enhanced_svg(data: &EnhancedVectorData) -> String {
-    let curve_count: usize = data.paths.iter().map(|p| p.curves.len()).sum();
+/// Marching squares traces outer boundaries with negative shoelace area and
+/// holes with positive area.
+fn is_hole(contour: &[Point]) -> bool {
+    signed_polygon_area(contour) > 0.0
+}
+
+/// Reverse `points` unless they wind counter-clockwise on screen (negative
+/// shoelace area with y pointing down), as traced outer boundaries do.
+fn ensure_ccw(points: &mut [Point]) {
+    if signed_polygon_area(points) > 0.0 {
+        points.reverse();
+    }
+}
+
+/// Generate SVG string from enhanced vector data with default options.
+/// Uses gap-filling strokes and consecutive same-color path grouping.
+pub fn generate_enhanced_svg(data: &EnhancedVectorData) -> String {
+    generate_enhanced_svg_with_options(data, &EnhancedOptions::default())
+}
+
+/// Generate SVG string from enhanced vector data, honoring the output
+/// settings in `options` (coordinate precision, gap stroke).
+pub fn generate_enhanced_svg_with_options(data: &EnhancedVectorData, options: &EnhancedOptions) -> String {
+    render_enhanced_svg(data, options, true)
+}
+
+/// Shared SVG writer; `with_background` controls the full-size background rect.
+fn render_enhanced_svg(data: &EnhancedVectorData, options: &EnhancedOptions, with_background: bool) -> String {
+    let path_format = options.path_format();
+    let snapped;
+    let data = match options.snap_grid {
+        Some(grid) => {
+            snapped = snap_vector_data(data, grid, &path_format);
+            &snapped
+        }
+        None => data,
+    };
+    let stroke_width = options.fill_stroke_width();
+    let stroke_style = match options.stroke_linecap {
+        StrokeLinecap::Butt => format!("stroke-linejoin=\"{}\"", options.stroke_linejoin.as_str()),
+        cap => format!(
+            "stroke-linejoin=\"{}\" stroke-linecap=\"{}\"",
+            options.stroke_linejoin.as_str(),
+            cap.as_str()
+        ),
+    };
+    let curve_count: usize = data.paths.iter().map(|p| p.curves.len() + p.holes.iter().map(Vec::len).sum::<usize>()).sum();
     let mut svg = String::with_capacity(200 + curve_count * 80);
+    let current_color = options.monochrome_current_color
+        && data.paths.first().is_some_and(|first| data.paths.iter().all(|p| p.color == first.color));
+    let with_background = with_background && !current_color && options.render_mode == RenderMode::Fill;
+
+    if options.wrap_html {
+        svg.push_str("<!DOCTYPE html>\n<html>\n<body>\n");
+    }
+    let legend = if options.emit_legend { palette_legend(data) } else { Vec::new() };
+    let swatch = legend_swatch_size(data.height);
+    let per_row = (data.width / swatch).max(1) as usize;
+    let legend_height = legend.len().div_ceil(per_row) as u32 * swatch;
+    let mut root_attributes = Vec::new();
+    if let Some(value) = &options.preserve_aspect_ratio {
+        root_attributes.push(("preserveAspectRatio".to_string(), value.clone()));
+    }
+    root_attributes.extend(options.root_attributes.iter().cloned());
+    svg.push_str(&svg_root_tag(data.width, data.height + legend_height, &root_attributes));
+    if options.embed_provenance {
+        svg.push_str(&provenance_comment(options));
+    }
+    if options.emit_metadata {
+        svg.push_str(&format!("  <!-- img2svg {}; paths={} -->\n", env!("CARGO_PKG_VERSION"), data.paths.len()));
+    }
 
-    svg.push_str(&format!(
-        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">
-"#,
-        data.width, data.height, data.width, data.height
-    ));
+    let shapes = if options.dedup_shapes { SharedShapes::find(&data.paths, &path_format) } else { SharedShapes::default() };
+    if !shapes.defs.is_empty() {
+        svg.push_str("  <defs>\n");
+        for (id, d) in shapes.defs.iter().enumerate() {
+            let d = if options.collapse_path_commands { collapse_path_commands(d) } else { d.clone() };
+            let fill_rule = if options.fill_rule == FillRule::EvenOdd && d.matches('M').count() > 1 {
+                format!(" fill-rule=\"{}\"", options.fill_rule.as_str())
+            } else {
+                String::new()
+            };
+            svg.push_str(&format!("    <path id=\"shape{}\"{} d=\"{}\"/>\n", id, fill_rule, d));
+        }
+        svg.push_str("  </defs>\n");
+    }
 
-    // Background rect
-    let bg = data.background_color;
-    let bg_hex = format!("#{:02x}{:02x}{:02x}", bg.0, bg.1, bg.2);
-    svg.push_str(&format!(
-        "  <rect width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
-        data.width, data.height, bg_hex
-    ));
+    // Background rect, left out when the background is fully transparent
+    if with_background && data.background_color.3 > 0 {
+        let bg = data.background_color;
+        let bg_hex = format!("#{:02x}{:02x}{:02x}", bg.0, bg.1, bg.2);
+        svg.push_str(&format!(
+            "  <rect width=\"{}\" height=\"{}\" fill=\"{}\"{}/>\n",
+            data.width, data.height, bg_hex, opacity_attr("fill-opacity", bg.3)
+        ));
+    }
 
-    // Group consecutive same-color paths
-    let groups = group_by_color(&data.paths);
+    // Group consecutive same-color paths, unless each component keeps its own element
+    let groups = group_by_color(&data.paths, !options.split_components, options.merge_all_same_color);
 
     for group in &groups {
-        let color_hex = &group.color_hex;
+        let color_hex = if current_color { "currentColor" } else { &group.color_hex };
+        let fill_opacity = opacity_attr("fill-opacity", group.alpha);
 
-        // Collect subpath data
+        // Collect subpath data; primitives get elements of their own
         let mut path_data = String::new();
+        let mut primitives = Vec::new();
+        let mut uses = Vec::new();
         for path in &group.paths {
+            if let Some(primitive) = &path.primitive {
+                primitives.push(primitive);
+                continue;
+            }
             // Use pre-built SVG for thin stripe rects
             if let Some(ref svg) = path.svg_override {
                 path_data.push_str(svg);
-                continue;
+                if path.curves.is_empty() {
+                    continue;
+                }
             }
             // Skip degenerate subpaths (zero-area in both dimensions)
             if !path.curves.is_empty() {
@@ -453,45 +2382,466 @@ pub fn generate_enhanced_svg(data: &EnhancedVectorData) -> String {
                     continue;
                 }
             }
-            path_data.push_str(&bezier_to_svg_path(&path.curves, true));
+            if let Some(placed) = shapes.place(path) {
+                uses.push(placed);
+                continue;
+            }
+            path_data.push_str(&path_format.bezier_path(&path.curves, true));
+            for hole in &path.holes {
+                path_data.push_str(&path_format.bezier_path(hole, true));
+            }
+        }
+
+        if options.collapse_path_commands {
+            path_data = collapse_path_commands(&path_data);
+        }
+
+        let mut area_attr = String::new();
+        if options.emit_area_attr || options.emit_metadata {
+            let area: usize = group.paths.iter().map(|p| p.area).sum();
+            area_attr.push_str(&format!(" data-area=\"{}\"", area));
+        }
+        if options.emit_metadata {
+            let mut quant: Vec<String> = Vec::new();
+            for (r, g, b, _) in group.paths.iter().map(|p| p.quantized) {
+                let hex = format!("#{:02x}{:02x}{:02x}", r, g, b);
+                if !quant.contains(&hex) {
+                    quant.push(hex);
+                }
+            }
+            area_attr.push_str(&format!(" data-quant=\"{}\"", quant.join(" ")));
+        }
+        let mut elements = Vec::new();
+        if !path_data.is_empty() {
+            let polygon = if options.prefer_polygons { polygon_points(&path_data) } else { None };
+            let mut extra_attrs = area_attr.clone();
+            if options.fill_rule == FillRule::EvenOdd && path_data.matches('M').count() > 1 {
+                extra_attrs.push_str(&format!(" fill-rule=\"{}\"", options.fill_rule.as_str()));
+            }
+            elements.push(match polygon {
+                Some(points) => ("polygon", extra_attrs, format!("points=\"{}\"", points)),
+                None => ("path", extra_attrs, format!("d=\"{}\"", path_data)),
+            });
+        }
+        for primitive in primitives {
+            elements.push((primitive.tag(), area_attr.clone(), primitive.attributes(&path_format)));
+        }
+        for (id, x, y) in uses {
+            let geometry =
+                format!("href=\"#shape{}\" x=\"{}\" y=\"{}\"", id, path_format.num(x), path_format.num(y));
+            elements.push(("use", area_attr.clone(), geometry));
         }
 
-        if path_data.is_empty() {
-            continue;
+        for (tag, extra_attrs, geometry) in elements {
+            if let RenderMode::Stroke { width } = options.render_mode {
+                // Outline only; thin-stripe rects in the path data are stroked too
+                svg.push_str(&format!(
+                    "  <{}{} fill=\"none\" stroke=\"{}\"{} stroke-width=\"{}\" {} {}/>\n",
+                    tag,
+                    extra_attrs,
+                    color_hex,
+                    opacity_attr("stroke-opacity", group.alpha),
+                    path_format.num(width),
+                    stroke_style,
+                    geometry
+                ));
+                continue;
+            }
+            match stroke_width {
+                // Gap-filling stroke matching fill color
+                Some(width) => svg.push_str(&format!(
+                    "  <{}{} fill=\"{}\"{} stroke=\"{}\"{} stroke-width=\"{}\" {} {}/>\n",
+                    tag,
+                    extra_attrs,
+                    color_hex,
+                    fill_opacity,
+                    color_hex,
+                    opacity_attr("stroke-opacity", group.alpha),
+                    path_format.num(width),
+                    stroke_style,
+                    geometry
+                )),
+                None => svg.push_str(&format!(
+                    "  <{}{} fill=\"{}\"{} {}/>\n",
+                    tag, extra_attrs, color_hex, fill_opacity, geometry
+                )),
+            }
         }
+    }
+
+    let finish_path = |d: String| if options.collapse_path_commands { collapse_path_commands(&d) } else { d };
+    for stroke in &data.strokes {
+        let color = if current_color {
+            "currentColor".to_string()
+        } else {
+            format!("#{:02x}{:02x}{:02x}", stroke.color.0, stroke.color.1, stroke.color.2)
+        };
+        svg.push_str(&format!(
+            "  <path fill=\"none\" stroke=\"{}\"{} stroke-width=\"{}\" {} d=\"{}\"/>\n",
+            color,
+            opacity_attr("stroke-opacity", stroke.color.3),
+            path_format.num(stroke.width),
+            stroke_style,
+            finish_path(path_format.bezier_path(&stroke.curves, false))
+        ));
+    }
 
-        // Gap-filling stroke matching fill color
+    if let Some((width, (r, g, b))) = options.silhouette_outline
+        && !data.silhouette.is_empty()
+    {
         svg.push_str(&format!(
-            "  <path fill=\"{}\" stroke=\"{}\" stroke-width=\"0.5\" stroke-linejoin=\"round\" d=\"{}\"/>\n",
-            color_hex, color_hex, path_data
+            "  <path class=\"silhouette\" fill=\"none\" stroke=\"#{:02x}{:02x}{:02x}\" stroke-width=\"{}\" {} d=\"{}\"/>\n",
+            r, g, b, path_format.num(width), stroke_style, finish_path(path_format.bezier_path(&data.silhouette, true))
         ));
     }
 
+    if !legend.is_empty() {
+        svg.push_str("  <g class=\"legend\">\n");
+        for (i, (hex, area)) in legend.iter().enumerate() {
+            let x = (i % per_row) as u32 * swatch;
+            let y = data.height + (i / per_row) as u32 * swatch;
+            svg.push_str(&format!(
+                "    <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"><title>{} ({} px)</title></rect>\n",
+                x, y, swatch, swatch, hex, hex, area
+            ));
+        }
+        svg.push_str("  </g>\n");
+    }
+
     svg.push_str("</svg>");
+    if options.wrap_html {
+        svg.push_str("\n</body>\n</html>\n");
+    }
+    if options.minify {
+        svg = minify_svg(&svg);
+    }
     svg
 }
 
+/// Markup with each line's indentation and the line breaks dropped. Every
+/// line the renderer writes starts or ends at a tag boundary.
+fn minify_svg(svg: &str) -> String {
+    svg.lines().map(str::trim_start).collect()
+}
+
+/// Merge every path of the same color into one, concatenating curves (each
+/// original contour stays its own subpath) and pre-built path data.
+///
+/// Paths come out in order of each color's first appearance. The merged
+/// path's `area` is the sum of its inputs'.
+pub fn merge_paths_by_color(paths: &[EnhancedPath]) -> Vec<EnhancedPath> {
+    let mut merged: Vec<EnhancedPath> = Vec::new();
+    for path in paths {
+        match merged.iter_mut().find(|m| m.color == path.color) {
+            Some(target) => {
+                let mut path = path.clone();
+                path.flatten_primitive();
+                target.flatten_primitive();
+                target.curves.extend(path.curves.iter().cloned());
+                target.holes.extend(path.holes.iter().cloned());
+                target.area += path.area;
+                if let Some(ref svg) = path.svg_override {
+                    target.svg_override.get_or_insert_with(String::new).push_str(svg);
+                }
+            }
+            None => merged.push(path.clone()),
+        }
+    }
+    merged
+}
+
+/// Colors used in the output as `#rrggbb` hex with their pixel coverage,
+/// largest first. Totals include the background and add up to the image area
+/// (less any pixels excluded by `alpha_cutoff`).
+pub fn palette_legend(data: &EnhancedVectorData) -> Vec<(String, usize)> {
+    let mut legend: Vec<(String, usize)> = Vec::new();
+    for &((r, g, b, _), area) in &data.coverage {
+        let hex = format!("#{:02x}{:02x}{:02x}", r, g, b);
+        match legend.iter_mut().find(|(h, _)| *h == hex) {
+            Some(entry) => entry.1 += area,
+            None => legend.push((hex, area)),
+        }
+    }
+    legend.sort_by_key(|l| std::cmp::Reverse(l.1));
+    legend
+}
+
+/// Side length of a legend swatch for an image of the given height.
+fn legend_swatch_size(height: u32) -> u32 {
+    (height / 10).clamp(8, 32)
+}
+
+/// Convert path data made of a single closed `M…L…Z` ring into a
+/// `<polygon>` points list. Returns None for curves or multiple subpaths.
+fn polygon_points(d: &str) -> Option<String> {
+    let body = d.strip_prefix('M')?.strip_suffix('Z')?;
+    if body.contains(|c: char| c.is_ascii_alphabetic() && c != 'L') {
+        return None;
+    }
+    let points: Vec<&str> = body.split('L').collect();
+    if points.len() < 3 {
+        return None;
+    }
+    Some(points.join(" "))
+}
+
+/// `<!-- img2svg <version>; source: <file>; options: <json> -->` line.
+pub(crate) fn provenance_comment(options: &EnhancedOptions) -> String {
+    let mut text = format!("img2svg {}", env!("CARGO_PKG_VERSION"));
+    if let Some(path) = &options.source_path {
+        let source = if options.provenance_full_path {
+            path.display().to_string()
+        } else {
+            path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+        };
+        text.push_str(&format!("; source: {}", source));
+    }
+    let summary = serde_json::to_string(options).unwrap_or_default();
+    text.push_str(&format!("; options: {}", summary));
+    // A comment may not contain "--"
+    while text.contains("--") {
+        text = text.replace("--", "- -");
+    }
+    format!("  <!-- {} -->\n", text)
+}
+
+/// Opening `<svg>` tag with the built-in attributes merged with `extra`.
+pub(crate) fn svg_root_tag(width: u32, height: u32, extra: &[(String, String)]) -> String {
+    let mut attrs: Vec<(String, String)> = vec![
+        ("xmlns".to_string(), "http://www.w3.org/2000/svg".to_string()),
+        ("width".to_string(), width.to_string()),
+        ("height".to_string(), height.to_string()),
+        ("viewBox".to_string(), format!("0 0 {} {}", width, height)),
+    ];
+    for (name, value) in extra {
+        match attrs.iter_mut().find(|(n, _)| n == name) {
+            Some(existing) => existing.1 = value.clone(),
+            None => attrs.push((name.clone(), value.clone())),
+        }
+    }
+
+    let mut tag = String::from("<svg");
+    for (name, value) in &attrs {
+        tag.push_str(&format!(" {}=\"{}\"", name, escape_attr(value)));
+    }
+    tag.push_str(">\n");
+    tag
+}
+
+/// Whether `value` is a valid `preserveAspectRatio`: `none` or an
+/// `x{Min,Mid,Max}Y{Min,Mid,Max}` alignment, optionally followed by `meet`
+/// or `slice`.
+fn is_preserve_aspect_ratio(value: &str) -> bool {
+    let mut tokens = value.split_ascii_whitespace();
+    let align = tokens.next().unwrap_or("");
+    let align_ok = align == "none"
+        || ["xMin", "xMid", "xMax"]
+            .iter()
+            .any(|x| align.strip_prefix(x).is_some_and(|y| ["YMin", "YMid", "YMax"].contains(&y)));
+    let meet_ok = tokens.next().is_none_or(|m| m == "meet" || m == "slice");
+    align_ok && meet_ok && tokens.next().is_none()
+}
+
+/// Escape a string for use inside a double-quoted XML attribute.
+fn escape_attr(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
 /// Write enhanced SVG to a file.
 pub fn write_enhanced_svg(
     data: &EnhancedVectorData,
     output_path: &std::path::Path,
 ) -> Result<()> {
-    let svg = generate_enhanced_svg(data);
-    let mut file = std::fs::File::create(output_path)?;
-    file.write_all(svg.as_bytes())?;
+    write_enhanced_svg_with_options(data, output_path, &EnhancedOptions::default())
+}
+
+/// Write enhanced SVG to a file, honoring the output settings in `options`.
+pub fn write_enhanced_svg_with_options(
+    data: &EnhancedVectorData,
+    output_path: &std::path::Path,
+    options: &EnhancedOptions,
+) -> Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(output_path)?);
+    write_enhanced_svg_to_writer_with_options(data, &mut file, options)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Write enhanced SVG to any sink (stdout, a buffer, a gzip encoder, ...).
+pub fn write_enhanced_svg_to_writer<W: Write>(data: &EnhancedVectorData, w: &mut W) -> Result<()> {
+    write_enhanced_svg_to_writer_with_options(data, w, &EnhancedOptions::default())
+}
+
+/// Write enhanced SVG to any sink, honoring the output settings in `options`.
+pub fn write_enhanced_svg_to_writer_with_options<W: Write>(
+    data: &EnhancedVectorData,
+    w: &mut W,
+    options: &EnhancedOptions,
+) -> Result<()> {
+    if cfg!(debug_assertions) {
+        crate::validation::validate_geometry(data)?;
+    }
+    w.write_all(generate_enhanced_svg_with_options(data, options).as_bytes())?;
+    Ok(())
+}
+
+/// Write one SVG per path color into `dir` as `<stem>_rrggbb.svg`, for
+/// screen-printing or vinyl-cutting workflows.
+///
+/// Each file has a transparent background and only that color's paths. The
+/// background color is treated as the substrate and gets no file of its own.
+/// Returns the written paths in order of each color's first appearance.
+pub fn write_color_separations(
+    data: &EnhancedVectorData,
+    dir: &std::path::Path,
+    stem: &str,
+) -> Result<Vec<std::path::PathBuf>> {
+    write_color_separations_with_options(data, dir, stem, &EnhancedOptions::default())
+}
+
+/// [`write_color_separations`], honoring the output settings in `options`.
+pub fn write_color_separations_with_options(
+    data: &EnhancedVectorData,
+    dir: &std::path::Path,
+    stem: &str,
+    options: &EnhancedOptions,
+) -> Result<Vec<std::path::PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for merged in merge_paths_by_color(&data.paths) {
+        if merged.curves.is_empty() && merged.svg_override.is_none() && merged.primitive.is_none() {
+            continue;
+        }
+        let (r, g, b, _) = merged.color;
+        let plate = EnhancedVectorData {
+            paths: vec![merged],
+            stats: ConversionStats::default(),
+            coverage: Vec::new(),
+            silhouette: Vec::new(),
+            strokes: Vec::new(),
+            ..*data
+        };
+        let path = dir.join(format!("{}_{:02x}{:02x}{:02x}.svg", stem, r, g, b));
+        std::fs::write(&path, render_enhanced_svg(&plate, options, false))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Write gzip-compressed enhanced SVG (`.svgz`) to a file.
+pub fn write_enhanced_svgz(data: &EnhancedVectorData, output_path: &std::path::Path) -> Result<()> {
+    write_enhanced_svgz_with_options(data, output_path, &EnhancedOptions::default())
+}
+
+/// Write gzip-compressed enhanced SVG to a file, honoring the output
+/// settings in `options`.
+pub fn write_enhanced_svgz_with_options(
+    data: &EnhancedVectorData,
+    output_path: &std::path::Path,
+    options: &EnhancedOptions,
+) -> Result<()> {
+    let file = std::io::BufWriter::new(std::fs::File::create(output_path)?);
+    let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+    write_enhanced_svg_to_writer_with_options(data, &mut encoder, options)?;
+    encoder.finish()?.flush()?;
     Ok(())
 }
 
+/// Shapes drawn more than once, for `dedup_shapes`.
+///
+/// A shape is a path moved so its bounding box starts at the origin. Shapes
+/// are matched on their path data at whole-pixel precision, so copies that
+/// differ by sub-pixel amounts still share one definition.
+#[derive(Default)]
+struct SharedShapes {
+    /// Whole-pixel path data of each shared shape → index into `defs`
+    ids: HashMap<String, usize>,
+    /// Path data of each shared shape, from its first copy
+    defs: Vec<String>,
+    format: PathFormat,
+}
+
+impl SharedShapes {
+    fn find(paths: &[EnhancedPath], format: &PathFormat) -> Self {
+        let mut shapes = SharedShapes { format: format.clone(), ..Default::default() };
+        let mut seen: HashMap<String, (usize, String)> = HashMap::new();
+        for path in paths {
+            let Some((shape, _, _)) = normalized_shape(path) else { continue };
+            let key = shapes.key(&shape);
+            seen.entry(key.clone()).or_insert_with(|| (0, shape_path_data(&shape, format))).0 += 1;
+            if let Some((count, d)) = seen.get(&key)
+                && *count == 2
+            {
+                shapes.ids.insert(key, shapes.defs.len());
+                shapes.defs.push(d.clone());
+            }
+        }
+        shapes
+    }
+
+    fn key(&self, shape: &EnhancedPath) -> String {
+        shape_path_data(shape, &PathFormat { precision: 0, ..self.format.clone() })
+    }
+
+    /// `(shape index, x, y)` to draw `path` with, if its shape is shared.
+    fn place(&self, path: &EnhancedPath) -> Option<(usize, f64, f64)> {
+        if self.defs.is_empty() {
+            return None;
+        }
+        let (shape, x, y) = normalized_shape(path)?;
+        self.ids.get(&self.key(&shape)).map(|&id| (id, x, y))
+    }
+}
+
+/// `path` moved so its bounding box starts at the origin, with the offset
+/// it was moved by. None for paths with pre-built path data or a primitive.
+fn normalized_shape(path: &EnhancedPath) -> Option<(EnhancedPath, f64, f64)> {
+    if path.curves.is_empty() || path.svg_override.is_some() || path.primitive.is_some() {
+        return None;
+    }
+    let (min_x, min_y, _, _) = curve_bounds(&path.curves);
+    let mut shape = path.clone();
+    for curve in shape.curves.iter_mut().chain(shape.holes.iter_mut().flatten()) {
+        for p in [&mut curve.start, &mut curve.control1, &mut curve.control2, &mut curve.end] {
+            p.x -= min_x;
+            p.y -= min_y;
+        }
+    }
+    Some((shape, min_x, min_y))
+}
+
+/// Outline and holes of `path` as one path data string.
+fn shape_path_data(path: &EnhancedPath, format: &PathFormat) -> String {
+    let mut d = format.bezier_path(&path.curves, true);
+    for hole in &path.holes {
+        d.push_str(&format.bezier_path(hole, true));
+    }
+    d
+}
+
 struct ColorGroup {
     color_hex: String,
+    alpha: u8,
     paths: Vec<EnhancedPath>,
 }
 
-fn group_by_color(paths: &[EnhancedPath]) -> Vec<ColorGroup> {
+/// Group same-color paths into shared elements: consecutive runs with `merge`,
+/// and with `global` too every later path of a color already seen.
+fn group_by_color(paths: &[EnhancedPath], merge: bool, global: bool) -> Vec<ColorGroup> {
     let mut groups: Vec<ColorGroup> = Vec::new();
 
     for path in paths {
-        if path.curves.is_empty() && path.svg_override.is_none() {
+        if path.curves.is_empty() && path.svg_override.is_none() && path.primitive.is_none() {
             continue;
         }
 
@@ -500,16 +2850,24 @@ fn group_by_color(paths: &[EnhancedPath]) -> Vec<ColorGroup> {
             path.color.0, path.color.1, path.color.2
         );
 
-        // Only merge with immediately preceding group if same color
-        if let Some(last) = groups.last_mut() {
-            if last.color_hex == color_hex {
-                last.paths.push(path.clone());
-                continue;
-            }
+        // Merge with the immediately preceding group if same color, or any
+        // earlier one when grouping globally
+        let same_color = |g: &ColorGroup| g.color_hex == color_hex && g.alpha == path.color.3;
+        let target = if !merge {
+            None
+        } else if global {
+            groups.iter_mut().find(|g| same_color(g))
+        } else {
+            groups.last_mut().filter(|g| same_color(g))
+        };
+        if let Some(target) = target {
+            target.paths.push(path.clone());
+            continue;
         }
 
         groups.push(ColorGroup {
             color_hex,
+            alpha: path.color.3,
             paths: vec![path.clone()],
         });
     }
@@ -554,7 +2912,7 @@ mod tests {
 
     fn make_test_image(w: u32, h: u32) -> ImageData {
         let mut pixels = Vec::new();
-        for y in 0..h {
+        for _y in 0..h {
             for x in 0..w {
                 if x < w / 2 {
                     pixels.push(RGBA8::new(200, 0, 0, 255));
@@ -599,6 +2957,174 @@ mod tests {
         assert!(svg.contains("stroke-width=\"0.5\""));
     }
 
+    fn make_seam_image() -> ImageData {
+        // White border, red block and blue block split by a 1px white seam
+        let (w, h) = (24u32, 16u32);
+        let mut pixels = Vec::new();
+        for y in 0..h {
+            for x in 0..w {
+                let inside_y = (2..14).contains(&y);
+                if inside_y && (2..12).contains(&x) {
+                    pixels.push(RGBA8::new(200, 0, 0, 255));
+                } else if inside_y && (13..22).contains(&x) {
+                    pixels.push(RGBA8::new(0, 0, 200, 255));
+                } else {
+                    pixels.push(RGBA8::new(255, 255, 255, 255));
+                }
+            }
+        }
+        ImageData { width: w, height: h, pixels }
+    }
+
+    #[test]
+    fn test_heal_gaps_regions_share_boundary() {
+        let img = make_seam_image();
+        let bounds_of = |data: &EnhancedVectorData, color: (u8, u8, u8, u8)| {
+            let curves: Vec<BezierCurve> = data
+                .paths
+                .iter()
+                .filter(|p| p.color == color)
+                .flat_map(|p| p.curves.clone())
+                .collect();
+            curve_bounds(&curves)
+        };
+        let gap = |heal: bool| {
+            let options = EnhancedOptions {
+                num_colors: 3,
+                preprocess: false,
+                heal_gaps: heal,
+                ..Default::default()
+            };
+            let data = vectorize_enhanced(&img, &options).unwrap();
+            let red = bounds_of(&data, (200, 0, 0, 255));
+            let blue = bounds_of(&data, (0, 0, 200, 255));
+            blue.0 - red.2
+        };
+
+        assert!(gap(false) > 0.5, "seam should be visible without healing");
+        assert!(gap(true).abs() < 0.01, "healed regions should share the seam edge, gap {}", gap(true));
+    }
+
+    #[test]
+    fn test_raw_contours_keep_more_points() {
+        let img = make_seam_image();
+        let point_count = |raw: bool| {
+            let options = EnhancedOptions {
+                num_colors: 3,
+                preprocess: false,
+                raw_contours: raw,
+                ..Default::default()
+            };
+            let svg = generate_enhanced_svg(&vectorize_enhanced(&img, &options).unwrap());
+            svg.split("d=\"").skip(1).map(|d| d.matches(',').count()).sum::<usize>()
+        };
+        let raw = point_count(true);
+        let fitted = point_count(false);
+        assert!(raw > fitted, "raw {} should exceed fitted {}", raw, fitted);
+    }
+
+    #[test]
+    fn test_flatten_alpha_yields_pink_over_white() {
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 20 * 20];
+        for y in 5..15 {
+            for x in 5..15 {
+                pixels[y * 20 + x] = RGBA8::new(255, 0, 0, 128);
+            }
+        }
+        let img = ImageData { width: 20, height: 20, pixels };
+        let options = EnhancedOptions {
+            num_colors: 2,
+            preprocess: false,
+            flatten_alpha: true,
+            ..Default::default()
+        };
+        let svg = generate_enhanced_svg(&vectorize_enhanced(&img, &options).unwrap());
+        assert!(svg.contains("<rect width=\"20\" height=\"20\" fill=\"#ffffff\"/>"));
+        assert!(svg.contains("<path fill=\"#ff7f7f\""), "expected pink fill in {}", svg);
+    }
+
+    #[test]
+    fn test_fit_polyline_noisy_circle() {
+        let n = 200;
+        let points: Vec<Point> = (0..n)
+            .map(|i| {
+                let a = i as f64 / n as f64 * std::f64::consts::TAU;
+                let r = 50.0 + 0.6 * (i as f64 * 7.3).sin();
+                Point { x: 60.0 + r * a.cos(), y: 60.0 + r * a.sin() }
+            })
+            .collect();
+        let d = fit_polyline_to_svg_path(&points, &CurveOptions::default());
+        assert!(d.starts_with('M'));
+        assert!(d.ends_with('Z'));
+        let commands = d.matches(['C', 'L']).count();
+        assert!(commands > 0 && commands < 40, "{} commands: {}", commands, d);
+        assert!(d.len() < points.len() * 8, "d string not compact: {} bytes", d.len());
+    }
+
+    #[test]
+    fn test_integer_precision_healed_output_has_no_gap_stroke() {
+        let img = make_seam_image();
+        let options = EnhancedOptions {
+            num_colors: 3,
+            preprocess: false,
+            heal_gaps: true,
+            decimal_precision: 0,
+            ..Default::default()
+        };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert!(svg.contains("<path"));
+        assert!(!svg.contains("stroke-width=\"0.5\""));
+        for d in svg.split(" d=\"").skip(1) {
+            let d = &d[..d.find('"').unwrap()];
+            assert!(!d.contains('.'), "integer precision should print no decimals: {}", d);
+        }
+
+        // Fractional precision keeps the anti-seam stroke
+        let options = EnhancedOptions { decimal_precision: 2, ..options };
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert!(svg.contains("stroke-width=\"0.5\""));
+    }
+
+    #[test]
+    fn test_dedup_shapes_reuses_identical_squares() {
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 50 * 30];
+        for y in 5..15 {
+            for x in 5..15 {
+                pixels[y * 50 + x] = RGBA8::new(200, 0, 0, 255);
+                pixels[(y + 10) * 50 + x + 30] = RGBA8::new(200, 0, 0, 255);
+            }
+        }
+        let image = ImageData { width: 50, height: 30, pixels };
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, dedup_shapes: true, ..Default::default() };
+        let data = vectorize_enhanced(&image, &options).unwrap();
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+
+        assert_eq!(svg.matches("<defs>").count(), 1, "{}", svg);
+        assert_eq!(svg.matches("<path id=").count(), 1, "{}", svg);
+        assert_eq!(svg.matches("<use ").count(), 2, "{}", svg);
+        assert!(svg.contains("fill=\"#c80000\""));
+        crate::validation::validate_svg_wellformed(&svg).unwrap();
+
+        let plain = generate_enhanced_svg(&data);
+        assert!(!plain.contains("<use") && !plain.contains("<defs>"));
+    }
+
+    #[test]
+    fn test_gap_stroke_width_and_toggle() {
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, gap_stroke_width: 1.5, ..Default::default() };
+        let data = vectorize_enhanced(&make_test_image(20, 20), &options).unwrap();
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert!(svg.contains("stroke-width=\"1.5\""), "{}", svg);
+
+        let options = EnhancedOptions { gap_stroke: false, ..options };
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert!(svg.contains("<path"));
+        assert!(!svg.contains("stroke="), "{}", svg);
+
+        assert!(EnhancedOptions { gap_stroke_width: 0.0, ..Default::default() }.validate().is_err());
+    }
+
     #[test]
     fn test_enhanced_options_default() {
         let opts = EnhancedOptions::default();
@@ -609,6 +3135,33 @@ mod tests {
         assert!(opts.recolor);
     }
 
+    #[test]
+    fn test_enhanced_options_builder() {
+        let opts = EnhancedOptions::builder()
+            .num_colors(6)
+            .curve_tolerance(1.5)
+            .simplification_tolerance(0.5)
+            .corner_threshold(45.0)
+            .min_region_area(4)
+            .edge_threshold(30)
+            .smoothing_passes(1)
+            .smooth_window(5)
+            .preprocess(false)
+            .recolor(false)
+            .build()
+            .unwrap();
+        assert_eq!(opts.num_colors, 6);
+        assert_eq!(opts.curve_tolerance, 1.5);
+        assert_eq!(opts.simplification_tolerance, 0.5);
+        assert_eq!(opts.corner_threshold, 45.0);
+        assert_eq!((opts.min_region_area, opts.edge_threshold), (4, 30));
+        assert_eq!((opts.smoothing_passes, opts.smooth_window), (1, 5));
+        assert!(!opts.preprocess && !opts.recolor);
+
+        assert!(EnhancedOptions::builder().curve_tolerance(0.0).build().is_err());
+        assert!(EnhancedOptions::builder().corner_threshold(270.0).build().is_err());
+    }
+
     #[test]
     fn test_enhanced_vectorize_gradient() {
         // Gradient image
@@ -765,8 +3318,11 @@ mod tests {
                     end: Point { x: 3.0, y: 0.0 },
                 }],
                 color: (255, 0, 0, 255),
+                quantized: (255, 0, 0, 255),
                 area: 100,
                 svg_override: None,
+                holes: Vec::new(),
+                primitive: None,
             },
             EnhancedPath {
                 curves: vec![BezierCurve {
@@ -776,8 +3332,11 @@ mod tests {
                     end: Point { x: 3.0, y: 0.0 },
                 }],
                 color: (255, 0, 0, 255), // same color
+                quantized: (255, 0, 0, 255),
                 area: 50,
                 svg_override: None,
+                holes: Vec::new(),
+                primitive: None,
             },
             EnhancedPath {
                 curves: vec![BezierCurve {
@@ -787,13 +3346,1470 @@ mod tests {
                     end: Point { x: 3.0, y: 0.0 },
                 }],
                 color: (0, 0, 255, 255), // different color
+                quantized: (0, 0, 255, 255),
                 area: 80,
                 svg_override: None,
+                holes: Vec::new(),
+                primitive: None,
             },
         ];
-        let groups = group_by_color(&paths);
+        let groups = group_by_color(&paths, true, false);
         assert_eq!(groups.len(), 2); // red group + blue group
         assert_eq!(groups[0].paths.len(), 2); // two red paths merged
         assert_eq!(groups[1].paths.len(), 1);
     }
+
+    #[test]
+    fn test_z_order_modes() {
+        let scan_order = [50, 200, 10, 80];
+        let paths: Vec<EnhancedPath> = scan_order
+            .iter()
+            .map(|&area| EnhancedPath {
+                curves: Vec::new(),
+                color: (0, 0, 0, 255),
+                quantized: (0, 0, 0, 255),
+                area,
+                svg_override: Some("M0 0L1 0L1 1Z".to_string()),
+                holes: Vec::new(),
+                primitive: None,
+            })
+            .collect();
+        for (z_order, expected) in [
+            (ZOrder::LargestFirst, [200, 80, 50, 10]),
+            (ZOrder::SmallestFirst, [10, 50, 80, 200]),
+            (ZOrder::PreserveScan, scan_order),
+        ] {
+            let mut sorted = paths.clone();
+            z_order.sort(&mut sorted, |p| p.area);
+            let areas: Vec<usize> = sorted.iter().map(|p| p.area).collect();
+            assert_eq!(areas, expected, "{:?}", z_order);
+        }
+
+        // Through the pipeline: a small blue square over a large red one
+        let (w, h) = (40usize, 40usize);
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); w * h];
+        for y in 4..36 {
+            for x in 4..36 {
+                let inner = (14..22).contains(&x) && (14..22).contains(&y);
+                pixels[y * w + x] = if inner { RGBA8::new(0, 0, 200, 255) } else { RGBA8::new(200, 0, 0, 255) };
+            }
+        }
+        let img = ImageData { width: w as u32, height: h as u32, pixels };
+        let options = EnhancedOptions { num_colors: 3, preprocess: false, ..Default::default() };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert_eq!(data.paths.first().map(|p| p.color), Some((200, 0, 0, 255)));
+        let options = EnhancedOptions { z_order: ZOrder::SmallestFirst, ..options };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert_eq!(data.paths.first().map(|p| p.color), Some((0, 0, 200, 255)));
+    }
+
+    #[test]
+    fn test_z_order_keeps_colors_together() {
+        // Red and blue blocks whose sizes alternate between the colors
+        let (w, h) = (60u32, 20u32);
+        let pixels = (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                let red = ((2..14).contains(&x) && (2..14).contains(&y)) || ((40..46).contains(&x) && (4..10).contains(&y));
+                let blue = ((18..28).contains(&x) && (4..14).contains(&y)) || ((50..58).contains(&x) && (10..18).contains(&y));
+                match (red, blue) {
+                    (true, _) => RGBA8::new(200, 0, 0, 255),
+                    (_, true) => RGBA8::new(0, 0, 200, 255),
+                    _ => RGBA8::new(255, 255, 255, 255),
+                }
+            })
+            .collect();
+        let img = ImageData { width: w, height: h, pixels };
+        let options = EnhancedOptions { num_colors: 3, preprocess: false, ..Default::default() };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        let colors: Vec<_> = data.paths.iter().map(|p| p.color).collect();
+        assert_eq!(colors, [(200, 0, 0, 255), (200, 0, 0, 255), (0, 0, 200, 255), (0, 0, 200, 255)]);
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert_eq!(svg.matches("<path").count(), 2, "{}", svg);
+    }
+
+    #[test]
+    fn test_tiny_specks_are_counted_as_discarded() {
+        // White canvas with a solid block and a field of 2×2 specks
+        let (w, h) = (48usize, 48usize);
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); w * h];
+        for y in 4..20 {
+            for x in 4..20 {
+                pixels[y * w + x] = RGBA8::new(0, 0, 0, 255);
+            }
+        }
+        for y in (26..46).step_by(5) {
+            for x in (4..46).step_by(5) {
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    pixels[(y + dy) * w + x + dx] = RGBA8::new(0, 0, 0, 255);
+                }
+            }
+        }
+        let img = ImageData { width: w as u32, height: h as u32, pixels };
+        let options = EnhancedOptions {
+            num_colors: 2,
+            preprocess: false,
+            ..Default::default()
+        };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert!(!data.paths.is_empty(), "the solid block should survive");
+        assert!(data.stats.discarded_regions > 0);
+        assert!(data.stats.discarded_area > 0.0);
+        assert!(data.stats.discarded_fraction(data.width, data.height) < 1.0);
+    }
+
+    #[test]
+    fn test_root_attributes_merge_into_svg_tag() {
+        let data = vectorize_enhanced(&make_test_image(20, 20), &EnhancedOptions {
+            num_colors: 2,
+            preprocess: false,
+            ..Default::default()
+        })
+        .unwrap();
+        let options = EnhancedOptions {
+            root_attributes: vec![
+                ("class".to_string(), "logo \"dark\"".to_string()),
+                ("preserveAspectRatio".to_string(), "xMidYMid meet".to_string()),
+                ("width".to_string(), "100%".to_string()),
+            ],
+            ..Default::default()
+        };
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        let root = &svg[..svg.find('>').unwrap()];
+        assert!(root.contains(r#"class="logo &quot;dark&quot;""#), "{}", root);
+        assert!(root.contains(r#"preserveAspectRatio="xMidYMid meet""#));
+        assert!(root.contains(r#"width="100%""#));
+        assert!(!root.contains(r#"width="20""#));
+        assert!(!svg.contains("<html>"));
+    }
+
+    #[test]
+    fn test_preserve_aspect_ratio() {
+        let data = vectorize_enhanced(&make_test_image(20, 20), &EnhancedOptions {
+            num_colors: 2,
+            preprocess: false,
+            ..Default::default()
+        })
+        .unwrap();
+        let root = |options: &EnhancedOptions| {
+            let svg = generate_enhanced_svg_with_options(&data, options);
+            svg[..svg.find('>').unwrap()].to_string()
+        };
+
+        let options = EnhancedOptions {
+            preserve_aspect_ratio: Some("xMidYMid meet".to_string()),
+            ..Default::default()
+        };
+        assert!(options.validate().is_ok());
+        assert!(root(&options).contains(r#"preserveAspectRatio="xMidYMid meet""#));
+        assert!(!root(&EnhancedOptions::default()).contains("preserveAspectRatio"));
+
+        for valid in ["none", "xMinYMax", "xMaxYMin slice"] {
+            assert!(is_preserve_aspect_ratio(valid), "{}", valid);
+        }
+        for invalid in ["", "center", "xMidYMid stretch", "xMidYMid meet slice", "ymidxmid"] {
+            let options = EnhancedOptions { preserve_aspect_ratio: Some(invalid.to_string()), ..Default::default() };
+            assert!(options.validate().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_wrap_html() {
+        let data = vectorize_enhanced(&make_test_image(20, 20), &EnhancedOptions {
+            num_colors: 2,
+            preprocess: false,
+            ..Default::default()
+        })
+        .unwrap();
+        let options = EnhancedOptions { wrap_html: true, ..Default::default() };
+        let html = generate_enhanced_svg_with_options(&data, &options);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<body>\n<svg "));
+        assert!(html.trim_end().ends_with("</html>"));
+        assert!(html.contains("</svg>\n</body>"));
+    }
+
+    #[test]
+    fn test_pinned_color_reproduced_exactly() {
+        // Gray gradient with a #cc0000 block inside a lighter, antialiased
+        // red rim: the rim pulls an unpinned centroid (and its recolor)
+        // off the brand color
+        let (w, h) = (60u32, 40u32);
+        let mut pixels = Vec::new();
+        for y in 0..h {
+            for x in 0..w {
+                let in_block = (20..50).contains(&x) && (10..30).contains(&y);
+                let in_rim = (17..53).contains(&x) && (7..33).contains(&y);
+                pixels.push(if in_block {
+                    RGBA8::new(204, 0, 0, 255)
+                } else if in_rim {
+                    let t = ((x * 7 + y * 3) % 20) as u8;
+                    RGBA8::new(225 + t, 40 + t, 40 + t, 255)
+                } else {
+                    let v = 100 + (x * 2) as u8;
+                    RGBA8::new(v, v, v, 255)
+                });
+            }
+        }
+        let img = ImageData { width: w, height: h, pixels };
+        let options = EnhancedOptions { num_colors: 2, ..Default::default() };
+
+        let plain = generate_enhanced_svg(&vectorize_enhanced(&img, &options).unwrap());
+        assert!(!plain.contains("#cc0000"));
+
+        let pinned = EnhancedOptions { pinned_colors: vec![(204, 0, 0)], ..options };
+        let svg = generate_enhanced_svg(&vectorize_enhanced(&img, &pinned).unwrap());
+        assert!(svg.contains("fill=\"#cc0000\""), "pinned color missing:\n{}", svg);
+    }
+
+    #[test]
+    fn test_binary_scan_skips_kmeans() {
+        // Scan-like page: noisy off-white paper with noisy near-black lines
+        let (w, h) = (200u32, 120u32);
+        let pixels = (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                let noise = ((i * 37 + (i / 7) * 11) % 12) as u8;
+                if (20..180).contains(&x) && y % 20 >= 10 && y % 20 < 14 {
+                    RGBA8::new(10 + noise, 12 + noise, 8 + noise, 255)
+                } else {
+                    RGBA8::new(240 + noise, 238 + noise, 236 + noise, 255)
+                }
+            })
+            .collect();
+        let img = ImageData { width: w, height: h, pixels };
+
+        let data = vectorize_enhanced(&img, &EnhancedOptions::default()).unwrap();
+        assert_eq!(data.stats.kmeans_iterations, 0);
+        assert!(data.paths.len() >= 5, "expected one path per line, got {}", data.paths.len());
+        let mut colors: Vec<_> = data.paths.iter().map(|p| p.color).collect();
+        colors.push(data.background_color);
+        colors.sort();
+        colors.dedup();
+        assert_eq!(colors.len(), 2, "{:?}", colors);
+        assert!(data.background_color.0 > 200);
+
+        // A third color rules the fast path out
+        let mut pixels = img.pixels.clone();
+        pixels[0] = RGBA8::new(128, 128, 128, 255);
+        assert!(binary_palette(&ImageData { pixels, ..img }, 16, 0).is_none());
+    }
+
+    #[test]
+    fn test_index_grouping_matches_color_hashing() {
+        use crate::enhanced_quantizer::quantize_enhanced;
+        let (w, h) = (48usize, 32usize);
+        let pixels = (0..w * h)
+            .map(|i| RGBA8::new((i * 7 % 256) as u8, (i / w * 8) as u8, (i % w * 5) as u8, 255))
+            .collect();
+        let img = ImageData { width: w as u32, height: h as u32, pixels };
+        let (quantized, indices, palette) = quantize_enhanced(&img, 12);
+        let excluded = |idx: usize| idx.is_multiple_of(11);
+
+        let mut by_color: HashMap<_, Vec<(usize, usize)>> = HashMap::new();
+        for y in 0..h {
+            for x in 0..w {
+                if !excluded(y * w + x) {
+                    let p = quantized.pixels[y * w + x];
+                    by_color.entry((p.r, p.g, p.b, p.a)).or_default().push((x, y));
+                }
+            }
+        }
+
+        let by_index = group_by_palette_index(&indices, palette.len(), w, excluded);
+        let mut from_index: HashMap<_, Vec<(usize, usize)>> = HashMap::new();
+        for (i, pixels) in by_index.into_iter().enumerate() {
+            let c = palette[i];
+            from_index.entry((c.r, c.g, c.b, c.a)).or_default().extend(pixels);
+        }
+        from_index.retain(|_, pixels| !pixels.is_empty());
+        for pixels in from_index.values_mut() {
+            pixels.sort_by_key(|&(x, y)| (y, x));
+        }
+        assert_eq!(from_index, by_color);
+    }
+
+    #[test]
+    fn test_max_colors_caps_palette() {
+        let pixels = (0..64 * 64).map(|i| RGBA8::new((i % 64 * 4) as u8, (i / 64 * 4) as u8, 128, 255)).collect();
+        let img = ImageData { width: 64, height: 64, pixels };
+        let options = EnhancedOptions { num_colors: 32, max_colors: 3, preprocess: false, ..Default::default() };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert!(data.coverage.len() <= 3, "{} colors", data.coverage.len());
+        assert!(EnhancedOptions { max_colors: 0, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_merge_small_into_neighbor() {
+        // A 2×2 speck of a slightly lighter blue inside a blue block on white
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 30 * 30];
+        for y in 5..25 {
+            for x in 5..25 {
+                pixels[y * 30 + x] = RGBA8::new(0, 0, 200, 255);
+            }
+        }
+        for y in 12..14 {
+            for x in 12..14 {
+                pixels[y * 30 + x] = RGBA8::new(40, 40, 230, 255);
+            }
+        }
+        let img = ImageData { width: 30, height: 30, pixels };
+        let coverage = |merge: bool| {
+            let options = EnhancedOptions {
+                num_colors: 3,
+                preprocess: false,
+                merge_small_into_neighbor: merge,
+                ..Default::default()
+            };
+            vectorize_enhanced(&img, &options).unwrap().coverage
+        };
+        let count = |coverage: &[(_, usize)], color| {
+            coverage.iter().find(|(c, _)| *c == color).map_or(0, |c| c.1)
+        };
+
+        let plain = coverage(false);
+        assert_eq!(count(&plain, (40, 40, 230, 255)), 4);
+        assert_eq!(count(&plain, (0, 0, 200, 255)), 396);
+
+        let merged = coverage(true);
+        assert_eq!(count(&merged, (40, 40, 230, 255)), 0);
+        assert_eq!(count(&merged, (0, 0, 200, 255)), 400);
+    }
+
+    #[test]
+    fn test_kmeans_iterations_reported_in_stats() {
+        let options = EnhancedOptions {
+            num_colors: 2,
+            preprocess: false,
+            kmeans_iterations: 30,
+            kmeans_epsilon: 0.5,
+            // Two flat colors would take the binary path and skip k-means
+            binary_tolerance: None,
+            ..Default::default()
+        };
+        let data = vectorize_enhanced(&make_test_image(20, 20), &options).unwrap();
+        assert!((1..30).contains(&data.stats.kmeans_iterations));
+    }
+
+    #[test]
+    fn test_vectorize_enhanced_with_stats() {
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 40 * 40];
+        for y in 5..20 {
+            for x in 5..20 {
+                pixels[y * 40 + x] = RGBA8::new(200, 0, 0, 255);
+                pixels[(y + 15) * 40 + x + 15] = RGBA8::new(0, 0, 200, 255);
+            }
+        }
+        let image = ImageData { width: 40, height: 40, pixels };
+        let options = EnhancedOptions { num_colors: 3, preprocess: false, ..Default::default() };
+        let (data, stats) = vectorize_enhanced_with_stats(&image, &options).unwrap();
+
+        assert!(data.paths.iter().all(|p| p.holes.is_empty()));
+        assert_eq!(stats.curve_count, data.paths.iter().map(|p| p.curves.len()).sum::<usize>());
+        assert_eq!(stats.path_count, data.paths.len());
+        assert_eq!(stats.distinct_colors, 2);
+        assert_eq!(stats.background_color, Some((255, 255, 255, 255)));
+        assert_eq!(stats.svg_bytes, generate_enhanced_svg_with_options(&data, &options).len());
+        assert_eq!(data.stats.svg_bytes, stats.svg_bytes);
+    }
+
+    #[test]
+    fn test_progress_events() {
+        let events = std::sync::Mutex::new(Vec::new());
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+        let record = |event| events.lock().unwrap().push(event);
+        let data = vectorize_enhanced_with_progress(&make_test_image(20, 20), &options, &record).unwrap();
+        assert!(!data.paths.is_empty());
+
+        let events = events.into_inner().unwrap();
+        assert_eq!(events.first(), Some(&ProgressEvent::QuantizeDone));
+        let regions = events.iter().find_map(|e| match e {
+            ProgressEvent::RegionsFound(n) => Some(*n),
+            _ => None,
+        });
+        assert_eq!(regions, Some(1));
+        assert_eq!(events.last(), Some(&ProgressEvent::PathFitted(1, 1)));
+    }
+
+    #[test]
+    fn test_seed_makes_output_reproducible() {
+        let pixels = (0..40 * 40).map(|i| RGBA8::new((i % 40 * 6) as u8, (i / 40 * 6) as u8, 90, 255)).collect();
+        let img = ImageData { width: 40, height: 40, pixels };
+        let svg = |seed| {
+            let options = EnhancedOptions { num_colors: 5, preprocess: false, seed: Some(seed), ..Default::default() };
+            generate_enhanced_svg_with_options(&vectorize_enhanced(&img, &options).unwrap(), &options)
+        };
+        assert_eq!(svg(7), svg(7));
+        assert_ne!(svg(7), svg(8));
+    }
+
+    #[test]
+    fn test_parallel_tracing_matches_serial_on_large_image() {
+        // 512x512 with 32 flat colors in 64 px tiles
+        let (w, h) = (512usize, 512usize);
+        let pixels = (0..w * h)
+            .map(|i| {
+                let tile = ((i / w / 64) * 8 + (i % w / 64)) % 32;
+                RGBA8::new((tile * 8) as u8, (255 - tile * 7) as u8, ((tile * 53) % 256) as u8, 255)
+            })
+            .collect();
+        let img = ImageData { width: w as u32, height: h as u32, pixels };
+
+        let indices: Vec<usize> = (0..w * h).map(|i| ((i / w / 64) * 8 + (i % w / 64)) % 32).collect();
+        let mut serial = vec![Vec::new(); 32];
+        for (idx, &index) in indices.iter().enumerate() {
+            serial[index].push((idx % w, idx / w));
+        }
+        assert_eq!(group_by_palette_index(&indices, 32, w, |_| false), serial);
+
+        let options = EnhancedOptions {
+            num_colors: 32,
+            preprocess: false,
+            seed: Some(1),
+            smoothing_passes: 0,
+            ..Default::default()
+        };
+        let parallel = prepare_enhanced(&img, &options).unwrap();
+        // A local single-thread pool: threads::install runs on the current
+        // pool unless set_thread_count was called, which tests must not do
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let single = pool.install(|| prepare_enhanced(&img, &options)).unwrap();
+
+        let regions = |p: &PreparedImage| p.color_contours.iter().map(|(_, _, _, c)| c.len()).collect::<Vec<_>>();
+        assert!(parallel.color_contours.len() > 1);
+        assert_eq!(regions(&parallel), regions(&single));
+    }
+
+    #[test]
+    fn test_target_max_bytes_coarsens_until_it_fits() {
+        // Wavy bands of five colors: long curved boundaries and no primitive
+        // shapes, large enough that simplification is not capped
+        let (w, h) = (128usize, 128usize);
+        let colors = [(200, 0, 0), (0, 120, 0), (0, 0, 200), (230, 180, 0), (120, 0, 160)];
+        let pixels = (0..w * h)
+            .map(|i| {
+                let (x, y) = ((i % w) as f64, (i / w) as f64);
+                let band = ((x / 9.0).sin() * 6.0 + (y / 7.0).cos() * 5.0 + x * 0.3 + y * 0.2) / 8.0;
+                let (r, g, b) = colors[band.rem_euclid(colors.len() as f64) as usize];
+                RGBA8::new(r, g, b, 255)
+            })
+            .collect();
+        let img = ImageData { width: w as u32, height: h as u32, pixels };
+        let options = EnhancedOptions { num_colors: 6, preprocess: false, seed: Some(3), ..Default::default() };
+        let full = generate_enhanced_svg_with_options(&vectorize_enhanced(&img, &options).unwrap(), &options).len();
+
+        let target = full * 4 / 5;
+        let options = EnhancedOptions { target_max_bytes: Some(target), ..options };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert!(svg.len() <= target, "{} bytes for a {} byte target (untuned {})", svg.len(), target, full);
+        assert_eq!(data.stats.svg_bytes, svg.len());
+        assert!(data.stats.curve_tolerance > options.curve_tolerance);
+
+        // A generous budget keeps the configured tolerance
+        let options = EnhancedOptions { target_max_bytes: Some(full), ..options };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert_eq!(data.stats.curve_tolerance, options.curve_tolerance);
+        assert_eq!(data.stats.svg_bytes, full);
+    }
+
+    #[test]
+    fn test_fill_holes_below_area() {
+        // Red block on white with a single white pixel inside it
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 20 * 20];
+        for y in 2..18 {
+            for x in 2..18 {
+                pixels[y * 20 + x] = RGBA8::new(200, 0, 0, 255);
+            }
+        }
+        pixels[10 * 20 + 10] = RGBA8::new(255, 255, 255, 255);
+        let img = ImageData { width: 20, height: 20, pixels };
+        let red_paths = |threshold: f64| {
+            let options = EnhancedOptions {
+                num_colors: 2,
+                preprocess: false,
+                fill_holes_below_area: threshold,
+                ..Default::default()
+            };
+            let data = vectorize_enhanced(&img, &options).unwrap();
+            data.paths.iter().filter(|p| p.color == (200, 0, 0, 255)).map(|p| 1 + p.holes.len()).sum::<usize>()
+        };
+
+        assert_eq!(red_paths(0.1), 2, "hole above the threshold should be kept");
+        assert_eq!(red_paths(4.0), 1, "hole below the threshold should be filled");
+    }
+
+    #[test]
+    fn test_fill_rule_cuts_ring_hole() {
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 40 * 40];
+        for y in 8..32 {
+            for x in 8..32 {
+                if !(16..24).contains(&x) || !(16..24).contains(&y) {
+                    pixels[y * 40 + x] = RGBA8::new(200, 0, 0, 255);
+                }
+            }
+        }
+        let img = ImageData { width: 40, height: 40, pixels };
+        // Winding number of the red rings (curve start points) around `p`
+        let winding = |data: &EnhancedVectorData, p: (f64, f64)| -> i32 {
+            let mut total = 0;
+            let red = data.paths.iter().filter(|path| path.color == (200, 0, 0, 255));
+            for curves in red.flat_map(|path| std::iter::once(&path.curves).chain(&path.holes)) {
+                let ring: Vec<Point> = curves.iter().map(|c| c.start.clone()).collect();
+                for k in 0..ring.len() {
+                    let (a, b) = (&ring[k], &ring[(k + 1) % ring.len()]);
+                    if (a.y <= p.1) != (b.y <= p.1) {
+                        let x = a.x + (p.1 - a.y) / (b.y - a.y) * (b.x - a.x);
+                        if x > p.0 {
+                            total += if b.y > a.y { 1 } else { -1 };
+                        }
+                    }
+                }
+            }
+            total
+        };
+
+        for rule in [FillRule::NonZero, FillRule::EvenOdd] {
+            let options = EnhancedOptions { num_colors: 2, preprocess: false, fill_rule: rule, ..Default::default() };
+            let data = vectorize_enhanced(&img, &options).unwrap();
+            let svg = generate_enhanced_svg_with_options(&data, &options);
+            assert_eq!(svg.contains("fill-rule=\"evenodd\""), rule == FillRule::EvenOdd);
+            assert!(!svg.contains("fill-rule=\"nonzero\""));
+
+            let filled = |n: i32| if rule == FillRule::NonZero { n != 0 } else { n % 2 != 0 };
+            assert!(filled(winding(&data, (12.0, 20.0))), "{:?}: ring body should be filled", rule);
+            assert!(!filled(winding(&data, (20.0, 20.0))), "{:?}: hole should be empty", rule);
+            assert!(!filled(winding(&data, (4.0, 20.0))), "{:?}: outside should be empty", rule);
+        }
+    }
+
+    #[test]
+    fn test_square_ring_mask_has_opposite_windings() {
+        let (w, h) = (30, 30);
+        let mut mask = vec![false; w * h];
+        for y in 5..25 {
+            for x in 5..25 {
+                mask[y * w + x] = !(12..18).contains(&x) || !(12..18).contains(&y);
+            }
+        }
+        let path = vectorize_mask(&mask, w, h, (200, 0, 0, 255), &CurveOptions::default());
+        assert!(!path.curves.is_empty());
+        assert_eq!(path.holes.len(), 1);
+
+        let ring_area = |curves: &[BezierCurve]| {
+            signed_polygon_area(&curves.iter().map(|c| c.start.clone()).collect::<Vec<_>>())
+        };
+        assert!(ring_area(&path.curves) > 0.0, "outline should wind clockwise");
+        assert!(ring_area(&path.holes[0]) < 0.0, "hole should wind counter-clockwise");
+
+        let data = EnhancedVectorData {
+            width: w as u32,
+            height: h as u32,
+            background_color: (255, 255, 255, 255),
+            paths: vec![path],
+            stats: ConversionStats::default(),
+            coverage: Vec::new(),
+            silhouette: Vec::new(),
+            strokes: Vec::new(),
+        };
+        let svg = generate_enhanced_svg(&data);
+        let d_start = svg.find(" d=\"").expect("path element") + 4;
+        let d = &svg[d_start..d_start + svg[d_start..].find('"').unwrap()];
+        assert_eq!(d.matches('M').count(), 2, "{}", d);
+        assert!(svg.contains("fill-rule=\"evenodd\""));
+        crate::validation::validate_svg_wellformed(&svg).unwrap();
+    }
+
+    #[test]
+    fn test_embed_provenance_comment() {
+        let img = make_seam_image();
+        let options = EnhancedOptions {
+            num_colors: 3,
+            preprocess: false,
+            embed_provenance: true,
+            source_path: Some(PathBuf::from("/home/someone/art/logo.png")),
+            root_attributes: vec![("data-x".to_string(), "a--b".to_string())],
+            ..Default::default()
+        };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        let start = svg.find("<!-- ").expect("provenance comment");
+        let comment = &svg[start + 5..svg[start..].find(" -->").unwrap() + start];
+        assert!(comment.contains(&format!("img2svg {}", env!("CARGO_PKG_VERSION"))));
+        assert!(comment.contains("source: logo.png"), "{}", comment);
+        assert!(!comment.contains("/home/someone"));
+        assert!(comment.contains("\"num_colors\":3"), "{}", comment);
+        assert!(comment.contains("\"fill_rule\":\"evenodd\""));
+        assert!(!comment.contains("--"));
+        crate::validation::validate_svg_wellformed(&svg).unwrap();
+
+        let full = EnhancedOptions { provenance_full_path: true, ..options.clone() };
+        let svg = generate_enhanced_svg_with_options(&data, &full);
+        assert!(svg.contains("source: /home/someone/art/logo.png"));
+
+        let svg = generate_enhanced_svg_with_options(&data, &EnhancedOptions { embed_provenance: false, ..options });
+        assert!(!svg.contains("<!--"));
+    }
+
+    #[test]
+    fn test_split_components_emits_one_path_per_blob() {
+        // Two solid red blocks and a red ring around a white hole
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 40 * 20];
+        for y in 4..16 {
+            for x in 2..10 {
+                pixels[y * 40 + x] = RGBA8::new(200, 0, 0, 255);
+            }
+            for x in 14..22 {
+                pixels[y * 40 + x] = RGBA8::new(200, 0, 0, 255);
+            }
+            for x in 26..38 {
+                if !(30..34).contains(&x) || !(8..12).contains(&y) {
+                    pixels[y * 40 + x] = RGBA8::new(200, 0, 0, 255);
+                }
+            }
+        }
+        let img = ImageData { width: 40, height: 20, pixels };
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+        let red = |svg: &str| svg.matches("fill=\"#c80000\"").count();
+
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert_eq!(red(&generate_enhanced_svg_with_options(&data, &options)), 1);
+        // Merged on output, but each traced region counts only its own pixels
+        // (in scan order, as the color is layered as a whole)
+        let areas: Vec<usize> = data.paths.iter().filter(|p| p.color == (200, 0, 0, 255)).map(|p| p.area).collect();
+        assert_eq!(areas, [96, 96, 128]);
+
+        let split = EnhancedOptions { split_components: true, ..options };
+        let data = vectorize_enhanced(&img, &split).unwrap();
+        let red_paths: Vec<_> = data.paths.iter().filter(|p| p.color == (200, 0, 0, 255)).collect();
+        assert_eq!(red_paths.len(), 3);
+        let format = split.path_format();
+        let subpaths: Vec<usize> = red_paths
+            .iter()
+            .map(|p| {
+                let d = format.bezier_path(&p.curves, true) + p.svg_override.as_deref().unwrap_or("");
+                d.matches('M').count() + p.holes.len()
+            })
+            .collect();
+        assert_eq!(subpaths.iter().filter(|&&n| n == 2).count(), 1, "ring keeps its hole: {:?}", subpaths);
+        assert_eq!(red(&generate_enhanced_svg_with_options(&data, &split)), 3);
+        // Each blob keeps its own pixel count, largest first
+        let areas: Vec<usize> = red_paths.iter().map(|p| p.area).collect();
+        assert_eq!(areas, [128, 96, 96]);
+    }
+
+    #[test]
+    fn test_centerline_strokes_plus_shape() {
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 41 * 41];
+        for i in 4..37 {
+            for j in 19..22 {
+                pixels[j * 41 + i] = RGBA8::new(200, 0, 0, 255);
+                pixels[i * 41 + j] = RGBA8::new(200, 0, 0, 255);
+            }
+        }
+        let img = ImageData { width: 41, height: 41, pixels };
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert!(data.strokes.is_empty());
+        assert!(data.paths.iter().any(|p| p.color == (200, 0, 0, 255)));
+
+        let options = EnhancedOptions { centerline: true, ..options };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert_eq!(data.strokes.len(), 2);
+        assert!(data.strokes.iter().all(|s| s.color == (200, 0, 0, 255) && (s.width - 3.0).abs() < 1.0));
+        assert!(!data.paths.iter().any(|p| p.color == (200, 0, 0, 255)), "no filled outline expected");
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert_eq!(svg.matches("fill=\"none\" stroke=\"#c80000\"").count(), 2);
+    }
+
+    #[test]
+    fn test_palette_legend_covers_image() {
+        let img = make_seam_image();
+        let options = EnhancedOptions {
+            num_colors: 3,
+            preprocess: false,
+            emit_legend: true,
+            ..Default::default()
+        };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        let legend = palette_legend(&data);
+        let total: usize = legend.iter().map(|(_, area)| area).sum();
+        assert_eq!(total, 24 * 16);
+        assert_eq!(legend[0].0, "#ffffff");
+        assert!(legend.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert!(svg.contains("<g class=\"legend\">"));
+        assert!(svg.contains("fill=\"#c80000\"><title>#c80000"));
+        assert!(svg.contains("viewBox=\"0 0 24 24\""), "legend strip should extend the canvas");
+    }
+
+    #[test]
+    fn test_prefer_polygons_for_rectangle() {
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 30 * 30];
+        for y in 6..24 {
+            for x in 5..25 {
+                pixels[y * 30 + x] = RGBA8::new(0, 0, 200, 255);
+            }
+        }
+        let img = ImageData { width: 30, height: 30, pixels };
+        let options = EnhancedOptions {
+            num_colors: 2,
+            preprocess: false,
+            prefer_polygons: true,
+            ..Default::default()
+        };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert!(svg.contains("<polygon fill=\"#0000c8\""), "expected a polygon in {}", svg);
+        assert!(!svg.contains("<path"));
+
+        let svg = generate_enhanced_svg(&data);
+        assert!(!svg.contains("<polygon"));
+    }
+
+    #[test]
+    fn test_collapse_path_commands_shrinks_output() {
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 30 * 30];
+        for y in 6..24 {
+            for x in 5..25 {
+                pixels[y * 30 + x] = RGBA8::new(0, 0, 200, 255);
+            }
+        }
+        let img = ImageData { width: 30, height: 30, pixels };
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, raw_contours: true, ..Default::default() };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        let plain = generate_enhanced_svg_with_options(&data, &options);
+        let collapsed = generate_enhanced_svg_with_options(
+            &data,
+            &EnhancedOptions { collapse_path_commands: true, ..options.clone() },
+        );
+        assert!(collapsed.len() < plain.len(), "{} vs {}", collapsed, plain);
+        crate::validation::validate_svg_wellformed(&collapsed).unwrap();
+    }
+
+    #[test]
+    fn test_detect_primitives_collapses_disc_to_circle() {
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 100 * 100];
+        for y in 0..100 {
+            for x in 0..100 {
+                let (dx, dy) = (x as f64 + 0.5 - 50.0, y as f64 + 0.5 - 50.0);
+                if dx.hypot(dy) <= 40.0 {
+                    pixels[y * 100 + x] = RGBA8::new(200, 0, 0, 255);
+                }
+            }
+        }
+        let img = ImageData { width: 100, height: 100, pixels };
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, detect_primitives: true, ..Default::default() };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        let red: Vec<_> = data.paths.iter().filter(|p| p.color == (200, 0, 0, 255)).collect();
+        assert_eq!(red.len(), 1);
+        let primitive = red[0].primitive.expect("disc should be recognized");
+        let element = primitive.to_svg(&options.path_format());
+        assert!(element.contains("<circle"), "{}", element);
+        let Primitive::Circle { cx, cy, r } = primitive else { unreachable!() };
+        assert!((cx - 50.0).abs() < 1.0 && (cy - 50.0).abs() < 1.0 && (r - 40.0).abs() < 1.5, "{}", element);
+
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert!(!svg.contains("<path"), "{}", svg);
+        crate::validation::validate_svg_wellformed(&svg).unwrap();
+
+        // Off by default
+        let data = vectorize_enhanced(&img, &EnhancedOptions { detect_primitives: false, ..options }).unwrap();
+        assert!(data.paths.iter().all(|p| p.primitive.is_none()));
+    }
+
+    #[test]
+    fn test_detect_primitives_emits_rect_element() {
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 40 * 30];
+        for y in 10..20 {
+            for x in 10..30 {
+                pixels[y * 40 + x] = RGBA8::new(0, 0, 200, 255);
+            }
+        }
+        let img = ImageData { width: 40, height: 30, pixels };
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, detect_primitives: true, ..Default::default() };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        let blue: Vec<_> = data.paths.iter().filter(|p| p.color == (0, 0, 200, 255)).collect();
+        assert_eq!(blue.len(), 1);
+        assert_eq!(blue[0].primitive, Some(Primitive::Rect { x: 10.0, y: 10.0, width: 20.0, height: 10.0 }));
+
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert!(svg.contains("<rect fill=\"#0000c8\""), "{}", svg);
+        assert!(svg.contains("x=\"10\" y=\"10\" width=\"20\" height=\"10\""), "{}", svg);
+        assert!(!svg.contains("<path"));
+    }
+
+    #[test]
+    fn test_detect_rects_emits_exact_rectangle() {
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 30 * 30];
+        for y in 6..24 {
+            for x in 5..25 {
+                pixels[y * 30 + x] = RGBA8::new(0, 0, 200, 255);
+            }
+        }
+        let img = ImageData { width: 30, height: 30, pixels };
+        let options = EnhancedOptions {
+            num_colors: 2,
+            preprocess: false,
+            detect_rects: true,
+            ..Default::default()
+        };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert_eq!(data.paths.len(), 1);
+        let d = data.paths[0].svg_override.as_deref().unwrap();
+        // Exact pixel edges of the block, clockwise
+        assert_eq!(d, "M5,6L25,6L25,24L5,24Z");
+        assert!(data.paths[0].curves.is_empty());
+
+        // A circle is not a rectangle
+        assert!(rect_path(&(0..32).map(|i| {
+            let a = i as f64 * std::f64::consts::TAU / 32.0;
+            Point { x: 10.0 + 8.0 * a.cos(), y: 10.0 + 8.0 * a.sin() }
+        }).collect::<Vec<_>>()).is_none());
+    }
+
+    #[test]
+    fn test_polygon_points_rejects_curves_and_subpaths() {
+        assert_eq!(polygon_points("M0,0L4,0L4,3Z").as_deref(), Some("0,0 4,0 4,3"));
+        assert!(polygon_points("M0,0C1,1 2,2 3,3L4,0Z").is_none());
+        assert!(polygon_points("M0,0L4,0L4,3ZM1,1L2,1L2,2Z").is_none());
+    }
+
+    #[test]
+    fn test_vectorize_mask_circle() {
+        let (w, h) = (40usize, 40usize);
+        let mask: Vec<bool> = (0..w * h)
+            .map(|i| {
+                let (x, y) = ((i % w) as f64 - 19.5, (i / w) as f64 - 19.5);
+                x * x + y * y < 14.0 * 14.0
+            })
+            .collect();
+        let path = vectorize_mask(&mask, w, h, (10, 20, 30, 255), &CurveOptions::default());
+        assert_eq!(path.color, (10, 20, 30, 255));
+        assert!(!path.curves.is_empty());
+        assert!(bezier_to_svg_path(&path.curves, true).contains('C'));
+        assert_eq!(path.area, mask.iter().filter(|&&m| m).count());
+        // Curve endpoints stay within the circle's bounding box
+        for c in &path.curves {
+            assert!((5.0..=35.0).contains(&c.end.x) && (5.0..=35.0).contains(&c.end.y));
+        }
+
+        let empty = vectorize_mask(&vec![false; w * h], w, h, (0, 0, 0, 255), &CurveOptions::default());
+        assert!(empty.curves.is_empty());
+    }
+
+    #[test]
+    fn test_alpha_cutoff_drops_faint_pixels() {
+        // Transparent canvas with a red band whose alpha ramps 0..64 left to right
+        let (w, h) = (40usize, 20usize);
+        let mut pixels = vec![RGBA8::new(0, 0, 0, 0); w * h];
+        for y in 4..16 {
+            for x in 4..36 {
+                pixels[y * w + x] = RGBA8::new(220, 0, 0, ((x - 4) * 2) as u8);
+            }
+        }
+        let img = ImageData { width: w as u32, height: h as u32, pixels };
+        let red_min_x = |cutoff: u8| {
+            let options = EnhancedOptions {
+                num_colors: 2,
+                preprocess: false,
+                alpha_cutoff: cutoff,
+                ..Default::default()
+            };
+            let data = vectorize_enhanced(&img, &options).unwrap();
+            let curves: Vec<BezierCurve> = data
+                .paths
+                .iter()
+                .filter(|p| p.color.0 > 150)
+                .flat_map(|p| p.curves.clone())
+                .collect();
+            assert!(!curves.is_empty());
+            curve_bounds(&curves).0
+        };
+
+        assert!(red_min_x(0) < 5.0);
+        // alpha reaches 32 at x = 20
+        let clipped = red_min_x(32);
+        assert!((19.0..21.0).contains(&clipped), "faint pixels kept, region starts at {}", clipped);
+    }
+
+    #[test]
+    fn test_resample_spacing_in_pipeline() {
+        let img = make_seam_image();
+        let options = EnhancedOptions {
+            num_colors: 3,
+            preprocess: false,
+            resample_spacing: Some(1.0),
+            ..Default::default()
+        };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert!(data.paths.iter().any(|p| p.color == (200, 0, 0, 255) && !p.curves.is_empty()));
+    }
+
+    #[test]
+    fn test_stroke_linejoin_miter() {
+        let img = make_test_image(20, 20);
+        let options = EnhancedOptions {
+            num_colors: 4,
+            preprocess: false,
+            stroke_linejoin: StrokeLinejoin::Miter,
+            stroke_linecap: StrokeLinecap::Square,
+            ..Default::default()
+        };
+        let result = vectorize_enhanced(&img, &options).unwrap();
+        let svg = generate_enhanced_svg_with_options(&result, &options);
+        assert!(svg.contains("stroke-linejoin=\"miter\""));
+        assert!(svg.contains("stroke-linecap=\"square\""));
+        assert!(!svg.contains("stroke-linejoin=\"round\""));
+
+        // Defaults keep the round join and leave the cap implicit
+        let svg = generate_enhanced_svg(&result);
+        assert!(svg.contains("stroke-linejoin=\"round\""));
+        assert!(!svg.contains("stroke-linecap"));
+    }
+
+    #[test]
+    fn test_preview_downscale_is_coarser() {
+        // Many small blobs: detail that a 32px preview cannot resolve
+        let (w, h) = (256u32, 256u32);
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); (w * h) as usize];
+        for y in 0..h {
+            for x in 0..w {
+                if (x / 6 + y / 6) % 2 == 0 && (x % 6 < 4) && (y % 6 < 4) {
+                    pixels[(y * w + x) as usize] = RGBA8::new(20, 20, 160, 255);
+                }
+            }
+        }
+        let img = ImageData { width: w, height: h, pixels };
+        let full_options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+        let preview_options = EnhancedOptions { preview_downscale: Some(32), ..full_options.clone() };
+
+        let full = vectorize_enhanced(&img, &full_options).unwrap();
+        let preview = vectorize_enhanced(&img, &preview_options).unwrap();
+        assert!(preview.paths.len() < full.paths.len(), "{} vs {}", preview.paths.len(), full.paths.len());
+
+        // The preview still covers the full canvas
+        assert_eq!((preview.width, preview.height), (w, h));
+        let svg = generate_enhanced_svg_with_options(&preview, &preview_options);
+        assert!(svg.contains("viewBox=\"0 0 256 256\""));
+    }
+
+    #[test]
+    fn test_scale_path_data() {
+        let format = PathFormat::default();
+        assert_eq!(scale_path_data("M1,2L3,4.5Z", 2.0, 4.0, &format), "M2,8L6,18Z");
+    }
+
+    #[test]
+    fn test_silhouette_outline() {
+        // Two touching colored blocks on white: one island, one outline
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 40 * 30];
+        for y in 5..25 {
+            for x in 5..35 {
+                pixels[y * 40 + x] = if x < 20 { RGBA8::new(200, 0, 0, 255) } else { RGBA8::new(0, 0, 200, 255) };
+            }
+        }
+        let img = ImageData { width: 40, height: 30, pixels };
+        let options = EnhancedOptions {
+            num_colors: 3,
+            preprocess: false,
+            silhouette_outline: Some((1.5, (0, 0, 0))),
+            ..Default::default()
+        };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+
+        let outlines: Vec<&str> = svg.lines().filter(|l| l.contains("class=\"silhouette\"")).collect();
+        assert_eq!(outlines.len(), 1, "{}", svg);
+        assert!(outlines[0].contains("stroke=\"#000000\""));
+        assert!(outlines[0].contains("stroke-width=\"1.5\""));
+        assert!(outlines[0].contains("fill=\"none\""));
+        assert_eq!(outlines[0].matches('M').count(), 1);
+        // Drawn above every fill
+        let last_fill = svg.rfind("<path fill=").unwrap();
+        assert!(svg.find("class=\"silhouette\"").unwrap() > last_fill);
+
+        let plain = vectorize_enhanced(&img, &EnhancedOptions { silhouette_outline: None, ..options }).unwrap();
+        assert!(plain.silhouette.is_empty());
+    }
+
+    #[test]
+    fn test_prepare_once_finish_twice() {
+        let (w, h) = (120u32, 120u32);
+        let pixels = (0..w * h)
+            .map(|i| {
+                let (dx, dy) = ((i % w) as f64 - 60.0, (i / w) as f64 - 60.0);
+                if dx * dx + dy * dy < 40.0 * 40.0 { RGBA8::new(0, 120, 0, 255) } else { RGBA8::new(255, 255, 255, 255) }
+            })
+            .collect();
+        let img = ImageData { width: w, height: h, pixels };
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+        let prepared = prepare_enhanced(&img, &options).unwrap();
+        assert_eq!((prepared.width(), prepared.height()), (w, h));
+
+        let point_count = |tolerance: f64| -> usize {
+            let curves = CurveOptions { simplification_tolerance: tolerance, curve_tolerance: 0.2, ..options.curve_options() };
+            finish(&prepared, &curves).paths.iter().map(|p| p.curves.len()).sum()
+        };
+        let fine = point_count(0.2);
+        let coarse = point_count(6.0);
+        assert!(coarse > 0 && fine > coarse, "fine {} vs coarse {}", fine, coarse);
+
+        // Finishing with the prepare-time settings matches the one-shot call
+        let one_shot = vectorize_enhanced(&img, &options).unwrap();
+        let finished = finish(&prepared, &options.curve_options());
+        assert_eq!(generate_enhanced_svg(&finished), generate_enhanced_svg(&one_shot));
+    }
+
+    #[test]
+    fn test_contrast_min_area_keeps_dark_specks() {
+        let (w, h) = (60u32, 60u32);
+        let speck = |x: u32, y: u32, x0: u32| x >= x0 && x < x0 + 2 && (y % 12) >= 5 && (y % 12) < 7;
+        let pixels = (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                if speck(x, y, 15) {
+                    RGBA8::new(0, 0, 0, 255)
+                } else if speck(x, y, 40) {
+                    RGBA8::new(235, 235, 235, 255)
+                } else {
+                    RGBA8::new(255, 255, 255, 255)
+                }
+            })
+            .collect();
+        let img = ImageData { width: w, height: h, pixels };
+        let count = |contrast_min_area: bool, color: (u8, u8, u8, u8)| {
+            let options = EnhancedOptions {
+                num_colors: 3,
+                preprocess: false,
+                smoothing_passes: 0,
+                contrast_min_area,
+                ..Default::default()
+            };
+            let data = vectorize_enhanced(&img, &options).unwrap();
+            data.paths.iter().filter(|p| p.color == color).count()
+        };
+        let black = (0, 0, 0, 255);
+        let light = (235, 235, 235, 255);
+
+        // The uniform threshold drops every speck
+        assert_eq!(count(false, black), 0);
+        assert_eq!(count(false, light), 0);
+        // Contrast weighting rescues the black ones only
+        assert_eq!(count(true, black), 5);
+        assert_eq!(count(true, light), 0);
+    }
+
+    #[test]
+    fn test_extract_polygons_region_with_hole() {
+        let (w, h) = (40u32, 40u32);
+        let pixels = (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                let in_frame = (8..32).contains(&x) && (8..32).contains(&y);
+                let in_hole = (16..24).contains(&x) && (16..24).contains(&y);
+                if in_frame && !in_hole { RGBA8::new(200, 0, 0, 255) } else { RGBA8::new(255, 255, 255, 255) }
+            })
+            .collect();
+        let img = ImageData { width: w, height: h, pixels };
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+        let polygons = extract_polygons(&img, &options).unwrap();
+
+        assert_eq!(polygons.len(), 1);
+        let polygon = &polygons[0];
+        assert_eq!(polygon.color, (200, 0, 0, 255));
+        assert_eq!(polygon.holes.len(), 1);
+        let outer = polygon_area(&polygon.exterior);
+        let inner = polygon_area(&polygon.holes[0]);
+        assert!((polygon.net_area() - (outer - inner)).abs() < 1e-9);
+        let (min_x, _, max_x, _) = bounds_from_points(&polygon.exterior);
+        assert!(min_x < 9.0 && max_x > 31.0);
+        let (hmin_x, _, hmax_x, _) = bounds_from_points(&polygon.holes[0]);
+        assert!(hmin_x > 15.0 && hmax_x < 25.0);
+        assert!(signed_polygon_area(&polygon.exterior) < 0.0);
+        assert!(signed_polygon_area(&polygon.holes[0]) > 0.0);
+    }
+
+    #[test]
+    fn test_ensure_ccw_reverses_clockwise_square() {
+        let clockwise = vec![
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 4.0, y: 0.0 },
+            Point { x: 4.0, y: 4.0 },
+            Point { x: 0.0, y: 4.0 },
+        ];
+        assert!(signed_polygon_area(&clockwise) > 0.0);
+        let mut ring = clockwise;
+        ensure_ccw(&mut ring);
+        assert!(signed_polygon_area(&ring) < 0.0);
+        let xy = |ring: &[Point]| ring.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>();
+        assert_eq!(xy(&ring), [(0.0, 4.0), (4.0, 4.0), (4.0, 0.0), (0.0, 0.0)]);
+
+        // Already counter-clockwise: left alone
+        ensure_ccw(&mut ring);
+        assert_eq!(xy(&ring), [(0.0, 4.0), (4.0, 4.0), (4.0, 0.0), (0.0, 0.0)]);
+    }
+
+    /// A white 40x40 canvas with blue blocks, each `(x0, y0, x1, y1)`.
+    fn blue_blocks(blocks: &[(u32, u32, u32, u32)]) -> ImageData {
+        let (w, h) = (40u32, 40u32);
+        let pixels = (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                let inside = blocks.iter().any(|&(x0, y0, x1, y1)| (x0..x1).contains(&x) && (y0..y1).contains(&y));
+                if inside { RGBA8::new(0, 0, 200, 255) } else { RGBA8::new(255, 255, 255, 255) }
+            })
+            .collect();
+        ImageData { width: w, height: h, pixels }
+    }
+
+    #[test]
+    fn test_emit_area_attr() {
+        let img = blue_blocks(&[(10, 10, 30, 20)]);
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, emit_area_attr: true, ..Default::default() };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert_eq!(data.paths.len(), 1);
+        assert_eq!(data.paths[0].area, 200);
+
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert!(svg.contains("<path data-area=\"200\" fill=\"#0000c8\""), "{}", svg);
+        crate::validation::validate_svg_wellformed(&svg).unwrap();
+        assert!(!generate_enhanced_svg(&data).contains("data-area"));
+
+        // Two blocks of one color share an element, which counts both
+        let img = blue_blocks(&[(10, 10, 30, 20), (10, 26, 20, 36)]);
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        let areas: Vec<usize> = data.paths.iter().map(|p| p.area).collect();
+        assert_eq!(areas, [200, 100]);
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert_eq!(svg.matches("<path").count(), 1, "{}", svg);
+        assert!(svg.contains("<path data-area=\"300\" "), "{}", svg);
+    }
+
+    #[test]
+    fn test_stroke_render_mode() {
+        // A block and a 1px stripe (emitted as a pre-built rect ring)
+        let (w, h) = (40u32, 40u32);
+        let pixels = (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                let block = (8..24).contains(&x) && (8..24).contains(&y);
+                let stripe = (4..36).contains(&x) && y == 30;
+                if block || stripe { RGBA8::new(0, 0, 200, 255) } else { RGBA8::new(255, 255, 255, 255) }
+            })
+            .collect();
+        let img = ImageData { width: w, height: h, pixels };
+        let options = EnhancedOptions {
+            num_colors: 2,
+            preprocess: false,
+            render_mode: RenderMode::Stroke { width: 1.5 },
+            ..Default::default()
+        };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert!(data.paths.iter().any(|p| p.svg_override.is_some()));
+
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert!(svg.contains("fill=\"none\" stroke=\"#0000c8\" stroke-width=\"1.5\""), "{}", svg);
+        assert!(!svg.contains("<rect"), "{}", svg);
+        assert!(!svg.contains("fill=\"#"));
+        crate::validation::validate_svg_wellformed(&svg).unwrap();
+        assert!(generate_enhanced_svg(&data).contains("<rect"));
+
+        let bad = EnhancedOptions { render_mode: RenderMode::Stroke { width: 0.0 }, ..Default::default() };
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_full_bleed_background_falls_back_to_dominant_color() {
+        // Two-color diagonal bleeding off every edge: red owns both corner
+        // triangles and most of the border, blue most of the area
+        let n = 60u32;
+        let pixels = (0..n * n)
+            .map(|i| {
+                let (x, y) = (i % n, i / n);
+                if x + y < 36 || x + y >= 2 * n - 37 { RGBA8::new(200, 0, 0, 255) } else { RGBA8::new(0, 0, 200, 255) }
+            })
+            .collect();
+        let img = ImageData { width: n, height: n, pixels };
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+        assert_eq!(vectorize_enhanced(&img, &options).unwrap().background_color, (200, 0, 0, 255));
+
+        let options = EnhancedOptions { background_min_border_share: 0.7, ..options };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert_eq!(data.background_color, (0, 0, 200, 255));
+        assert!(data.paths.iter().all(|p| p.color == (200, 0, 0, 255)));
+
+        let bad = EnhancedOptions { background_min_border_share: 1.5, ..Default::default() };
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_merge_all_same_color_and_minify() {
+        // Red, blue, red from left to right: z-order interleaves the colors
+        let (w, h) = (60u32, 20u32);
+        let pixels = (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                match (x, (4..16).contains(&y)) {
+                    (4..16, true) => RGBA8::new(200, 0, 0, 255),
+                    (24..36, true) => RGBA8::new(0, 0, 200, 255),
+                    (44..56, true) => RGBA8::new(200, 0, 0, 255),
+                    _ => RGBA8::new(255, 255, 255, 255),
+                }
+            })
+            .collect();
+        let img = ImageData { width: w, height: h, pixels };
+        let options = EnhancedOptions { num_colors: 3, preprocess: false, split_components: true, ..Default::default() };
+        let mut data = vectorize_enhanced(&img, &options).unwrap();
+        // Put the blue square between the two red ones
+        data.paths.sort_by_key(|p| curve_bounds(&p.curves).0 as i64);
+        let options = EnhancedOptions { split_components: false, ..options };
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert_eq!(svg.matches("<path").count(), 3, "{}", svg);
+
+        let options = EnhancedOptions { merge_all_same_color: true, ..options };
+        let merged = generate_enhanced_svg_with_options(&data, &options);
+        assert_eq!(merged.matches("<path").count(), 2, "{}", merged);
+        assert_eq!(merged.matches("fill=\"#c80000\"").count(), 1);
+        crate::validation::validate_svg_wellformed(&merged).unwrap();
+
+        let options = EnhancedOptions { minify: true, ..options };
+        let minified = generate_enhanced_svg_with_options(&data, &options);
+        assert!(!minified.contains('\n') && !minified.contains("  <"), "{}", minified);
+        assert_eq!(minified, merged.lines().map(str::trim_start).collect::<String>());
+        assert!(minified.len() < merged.len());
+        crate::validation::validate_svg_wellformed(&minified).unwrap();
+    }
+
+    #[test]
+    fn test_fixed_palette_limits_fills() {
+        // A smooth two-axis gradient: k-means and recoloring would invent colors
+        let (w, h) = (64u32, 64u32);
+        let pixels = (0..w * h).map(|i| RGBA8::new((i % w * 4) as u8, (i / w * 4) as u8, 128, 255)).collect();
+        let img = ImageData { width: w, height: h, pixels };
+        let palette = vec![(255, 255, 255), (200, 30, 30), (20, 60, 180)];
+        let options = EnhancedOptions { fixed_palette: Some(palette), seed: Some(1), ..Default::default() };
+        let svg = generate_enhanced_svg(&vectorize_enhanced(&img, &options).unwrap());
+
+        let allowed = ["#ffffff", "#c81e1e", "#143cb4"];
+        let fills: Vec<&str> = svg.split("fill=\"").skip(1).map(|rest| &rest[..7]).collect();
+        assert!(fills.len() > 1, "{}", svg);
+        assert!(fills.iter().all(|f| allowed.contains(f)), "{:?}", fills);
+
+        assert!(EnhancedOptions { fixed_palette: Some(Vec::new()), ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_emit_metadata() {
+        let img = blue_blocks(&[(10, 10, 30, 20)]);
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, emit_metadata: true, ..Default::default() };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert!(svg.contains("<path data-area=\"200\" data-quant=\"#0000c8\" fill=\"#0000c8\""), "{}", svg);
+        assert!(svg.contains(&format!("<!-- img2svg {}; paths=1 -->", env!("CARGO_PKG_VERSION"))));
+        crate::validation::validate_svg_wellformed(&svg).unwrap();
+
+        let plain = generate_enhanced_svg_with_options(&data, &EnhancedOptions { emit_metadata: false, ..options.clone() });
+        assert!(!plain.contains("data-area") && !plain.contains("data-quant") && !plain.contains("<!--"));
+
+        // Noise splits the block between two blues, each recolored to the
+        // mean of its pixels; data-quant keeps the palette colors
+        let mut img = blue_blocks(&[(10, 10, 30, 20)]);
+        for (i, p) in img.pixels.iter_mut().enumerate().filter(|(_, p)| p.b == 200) {
+            p.b = 120 + (i * 37 % 80) as u8;
+        }
+        let options = EnhancedOptions { num_colors: 3, seed: Some(1), ..options };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert!(data.paths.iter().any(|p| p.quantized != p.color));
+        let hex = |(r, g, b, _): (u8, u8, u8, u8)| format!("#{:02x}{:02x}{:02x}", r, g, b);
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        for path in &data.paths {
+            assert!(svg.contains(&format!("data-quant=\"{}\"", hex(path.quantized))), "{}", svg);
+            assert!(svg.contains(&format!(" fill=\"{}\"", hex(path.color))), "{}", svg);
+        }
+    }
+
+    #[test]
+    fn test_monochrome_current_color() {
+        let (w, h) = (40u32, 40u32);
+        let pixels = (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                let bar = (8..32).contains(&x) && ((8..14).contains(&y) || (26..32).contains(&y));
+                if bar { RGBA8::new(0, 0, 0, 255) } else { RGBA8::new(255, 255, 255, 255) }
+            })
+            .collect();
+        let img = ImageData { width: w, height: h, pixels };
+        let options = EnhancedOptions {
+            num_colors: 2,
+            preprocess: false,
+            monochrome_current_color: true,
+            ..Default::default()
+        };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        assert_eq!(data.paths.len(), 2);
+
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+        assert!(svg.contains("fill=\"currentColor\""), "{}", svg);
+        assert!(!svg.contains("#000000"));
+        assert!(!svg.contains("<rect"));
+
+        // A second foreground color turns it off
+        let mut two_tone = data;
+        two_tone.paths[1].color = (200, 0, 0, 255);
+        let svg = generate_enhanced_svg_with_options(&two_tone, &options);
+        assert!(!svg.contains("currentColor"));
+        assert!(svg.contains("#000000") && svg.contains("<rect"));
+    }
+
+    #[test]
+    fn test_stroke_polyline_open_s_curve() {
+        let points: Vec<Point> = (0..=60)
+            .map(|i| {
+                let t = i as f64 / 60.0;
+                Point { x: 10.0 + 80.0 * t, y: 50.0 + 25.0 * (t * std::f64::consts::TAU).sin() }
+            })
+            .collect();
+        let stroke = stroke_polyline(&points, (0, 0, 0, 255), 2.0, &CurveOptions::default());
+        let (first, last) = (&stroke.curves[0].start, &stroke.curves.last().unwrap().end);
+        assert!((first.x - 10.0).abs() < 1e-9 && (first.y - 50.0).abs() < 1e-9);
+        assert!((last.x - 90.0).abs() < 1e-9 && (last.y - 50.0).abs() < 1e-6);
+
+        let d = bezier_to_svg_path(&stroke.curves, false);
+        assert!(d.starts_with("M10,50"), "{}", d);
+        assert!(!d.contains('Z'), "{}", d);
+
+        let data = EnhancedVectorData {
+            width: 100,
+            height: 100,
+            background_color: (255, 255, 255, 255),
+            paths: Vec::new(),
+            stats: ConversionStats::default(),
+            coverage: Vec::new(),
+            silhouette: Vec::new(),
+            strokes: vec![stroke],
+        };
+        let svg = generate_enhanced_svg(&data);
+        assert!(svg.contains("<path fill=\"none\" stroke=\"#000000\" stroke-width=\"2\""), "{}", svg);
+        assert!(!svg.contains('Z'));
+    }
+
+    #[test]
+    fn test_snap_grid_rounds_emitted_coordinates() {
+        let (w, h) = (80u32, 60u32);
+        let pixels = (0..w * h)
+            .map(|i| {
+                let (dx, dy) = ((i % w) as f64 - 37.3, (i / w) as f64 - 28.6);
+                if dx * dx + dy * dy < 21.0 * 21.0 { RGBA8::new(0, 90, 160, 255) } else { RGBA8::new(255, 255, 255, 255) }
+            })
+            .collect();
+        let img = ImageData { width: w, height: h, pixels };
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, snap_grid: Some(5.0), ..Default::default() };
+        let data = vectorize_enhanced(&img, &options).unwrap();
+        let svg = generate_enhanced_svg_with_options(&data, &options);
+
+        let mut coords = Vec::new();
+        for chunk in svg.split(" d=\"").skip(1) {
+            let d = &chunk[..chunk.find('"').unwrap()];
+            coords.extend(
+                d.split(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+                    .filter(|t| !t.is_empty())
+                    .map(|t| t.parse::<f64>().unwrap()),
+            );
+        }
+        assert!(coords.len() > 8, "{}", svg);
+        for v in coords {
+            assert_eq!(v % 5.0, 0.0, "{} in {}", v, svg);
+        }
+        assert!(svg.contains('C'), "curves should survive snapping: {}", svg);
+    }
+
+    #[test]
+    fn test_edge_adaptive_smoothing_keeps_crisp_detail() {
+        // Two identical blobs: black (strong edge) and faint gray (weak edge)
+        let (w, h) = (120u32, 60u32);
+        let pixels = (0..w * h)
+            .map(|i| {
+                let (x, y) = ((i % w) as f64, (i / w) as f64);
+                let blob = |cx: f64| {
+                    let (dx, dy) = (x - cx, y - 30.0);
+                    dx * dx + dy * dy < 22.0 * 22.0 + 60.0 * (dx * 0.7).sin()
+                };
+                if blob(30.0) {
+                    RGBA8::new(0, 0, 0, 255)
+                } else if blob(90.0) {
+                    RGBA8::new(235, 235, 235, 255)
+                } else {
+                    RGBA8::new(255, 255, 255, 255)
+                }
+            })
+            .collect();
+        let img = ImageData { width: w, height: h, pixels };
+        let curve_counts = |edge_adaptive_smoothing: bool| {
+            let options = EnhancedOptions {
+                num_colors: 3,
+                preprocess: false,
+                smooth_window: 7,
+                edge_adaptive_smoothing,
+                ..Default::default()
+            };
+            let data = vectorize_enhanced(&img, &options).unwrap();
+            let count = |color: (u8, u8, u8, u8)| -> usize {
+                data.paths.iter().filter(|p| p.color == color).map(|p| p.curves.len()).sum()
+            };
+            (count((0, 0, 0, 255)), count((235, 235, 235, 255)))
+        };
+
+        let (black, gray) = curve_counts(false);
+        assert_eq!(black, gray, "same shape, same smoothing");
+        let (black, gray) = curve_counts(true);
+        assert!(black > gray, "crisp {} vs soft {}", black, gray);
+    }
+
+    #[test]
+    fn test_net_polygon_area_of_ring() {
+        let square = |x0: f64, x1: f64| {
+            vec![Point { x: x0, y: x0 }, Point { x: x1, y: x0 }, Point { x: x1, y: x1 }, Point { x: x0, y: x1 }]
+        };
+        let outer = square(0.0, 10.0);
+        let mut inner = square(3.0, 7.0);
+        assert_eq!(net_polygon_area(&outer, &[inner.clone()]), 100.0 - 16.0);
+        // Hole winding does not matter
+        inner.reverse();
+        assert_eq!(net_polygon_area(&outer, &[inner]), 84.0);
+        assert_eq!(net_polygon_area(&outer, &[]), 100.0);
+    }
+
+    #[test]
+    fn test_merge_paths_by_color() {
+        let square = |x: f64, color: (u8, u8, u8, u8)| {
+            let corners = [(x, 0.0), (x + 4.0, 0.0), (x + 4.0, 4.0), (x, 4.0)];
+            let points: Vec<Point> = corners.iter().map(|&(x, y)| Point { x, y }).collect();
+            EnhancedPath {
+                curves: BezierFitter::new(1.0).fit_path(&points, true),
+                color,
+                quantized: color,
+                area: 16,
+                svg_override: None,
+                holes: Vec::new(),
+                primitive: None,
+            }
+        };
+        let red = (200, 0, 0, 255);
+        let blue = (0, 0, 200, 255);
+        let paths = vec![square(0.0, red), square(10.0, blue), square(20.0, red), square(30.0, red)];
+
+        let merged = merge_paths_by_color(&paths);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].color, red);
+        assert_eq!(merged[1].color, blue);
+        assert_eq!((merged[0].area, merged[1].area), (48, 16));
+        let red_curves: usize = paths.iter().filter(|p| p.color == red).map(|p| p.curves.len()).sum();
+        assert_eq!(merged[0].curves.len(), red_curves);
+        // Each original contour stays a separate subpath
+        assert_eq!(bezier_to_svg_path(&merged[0].curves, true).matches('M').count(), 3);
+    }
 }
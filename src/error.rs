@@ -0,0 +1,22 @@
+//! Typed errors for failures callers may want to match on.
+//!
+//! Library functions return `anyhow::Result`; these variants travel inside
+//! it and can be recovered with `err.downcast_ref::<Img2SvgError>()`.
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum Img2SvgError {
+    /// Vector data holds a coordinate that is not finite or lies far outside
+    /// the canvas
+    #[error("invalid geometry: {0}")]
+    InvalidGeometry(String),
+    /// An option is outside its documented range, from strict
+    /// [`crate::ConversionOptions::normalized`] or from
+    /// [`crate::ConversionOptionsBuilder::build`]
+    #[error("invalid options: {0}")]
+    InvalidOptions(String),
+    /// Generated markup is not well-formed XML
+    #[error("malformed SVG: {0}")]
+    MalformedSvg(String),
+}
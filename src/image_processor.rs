@@ -1,6 +1,10 @@
 pub use anyhow::Result;
 use rgb::RGBA8;
 
+/// Largest palette any quantizer builds; larger color counts are clamped
+/// to it.
+pub const MAX_COLORS: usize = 256;
+
 #[derive(Debug, Clone)]
 pub struct ImageData {
     pub width: u32,
@@ -8,8 +12,320 @@ pub struct ImageData {
     pub pixels: Vec<RGBA8>,
 }
 
+impl ImageData {
+    /// Build an image from tightly packed RGBA bytes, row by row (as in a
+    /// browser canvas `ImageData`). Fails unless `bytes` holds exactly
+    /// `width * height * 4` bytes.
+    pub fn from_rgba(width: u32, height: u32, bytes: &[u8]) -> Result<ImageData> {
+        let expected = (width as usize).checked_mul(height as usize).and_then(|n| n.checked_mul(4));
+        if expected != Some(bytes.len()) {
+            return Err(anyhow::anyhow!(
+                "{} bytes of RGBA data do not match a {}x{} image",
+                bytes.len(), width, height
+            ));
+        }
+        let pixels = bytes.chunks_exact(4).map(|p| RGBA8::new(p[0], p[1], p[2], p[3])).collect();
+        Ok(ImageData { width, height, pixels })
+    }
+
+    /// Copy out the `w`x`h` rectangle whose top-left corner is (`x`, `y`).
+    /// Fails if the rectangle is empty or reaches past the image.
+    pub fn crop(&self, x: u32, y: u32, w: u32, h: u32) -> Result<ImageData> {
+        let fits = |start: u32, len: u32, size: u32| start.checked_add(len).is_some_and(|end| end <= size);
+        if w == 0 || h == 0 || !fits(x, w, self.width) || !fits(y, h, self.height) {
+            return Err(anyhow::anyhow!(
+                "crop {}x{} at ({}, {}) is outside the {}x{} image",
+                w, h, x, y, self.width, self.height
+            ));
+        }
+        let pixels = (y..y + h)
+            .flat_map(|row| {
+                let start = (row * self.width + x) as usize;
+                self.pixels[start..start + w as usize].iter().copied()
+            })
+            .collect();
+        Ok(ImageData { width: w, height: h, pixels })
+    }
+}
+
+/// File extensions (lowercase, without the dot) recognized as input images.
+const SUPPORTED_EXTENSIONS: &[&str] = &[
+    "bmp", "png", "jpg", "jpeg", "gif", "ico", "tiff", "tif", "webp", "pnm", "tga", "dds", "farbfeld",
+];
+
+/// File extensions (lowercase, without the dot) accepted as input, e.g. for
+/// building a file-picker filter.
+pub fn supported_extensions() -> &'static [&'static str] {
+    SUPPORTED_EXTENSIONS
+}
+
+/// Check if a file extension is a supported image format.
+pub fn is_supported_image(path: &std::path::Path) -> bool {
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        SUPPORTED_EXTENSIONS.contains(&ext.to_lowercase().as_str())
+    } else {
+        false
+    }
+}
+
 pub fn load_image(path: &std::path::Path) -> Result<ImageData> {
+    load_image_hinted(path, None)
+}
+
+/// [`load_image`], also returning the source's bits per channel: 16 for
+/// 16-bit PNG or TIFF, 32 for float formats. `ImageData` always holds 8
+/// bits per channel, so anything above 8 lost precision on the way in.
+pub fn load_image_with_bit_depth(path: &std::path::Path) -> Result<(ImageData, u8)> {
+    if let Some(image_data) = decode_jpeg(&std::fs::read(path)?, None)? {
+        return Ok((image_data, 8));
+    }
+    let img = image::open(path)?;
+    let color = img.color();
+    Ok((from_dynamic_image(&img), color.bytes_per_pixel() / color.channel_count() * 8))
+}
+
+/// Load an image that will be shrunk to fit `max_size` anyway.
+///
+/// JPEGs are decoded straight at a reduced DCT scale (1/2, 1/4 or 1/8) when
+/// that still covers the target size, which skips most of the decode work
+/// and memory for very large photos. The result can still be up to twice
+/// the target, so callers resize afterwards as usual; other formats load at
+/// full size.
+pub fn load_image_scaled(path: &std::path::Path, max_size: u32) -> Result<ImageData> {
+    load_image_hinted(path, Some(max_size))
+}
+
+fn load_image_hinted(path: &std::path::Path, max_size: Option<u32>) -> Result<ImageData> {
+    let is_jpeg = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| matches!(e.to_lowercase().as_str(), "jpg" | "jpeg"));
+    if is_jpeg && let Some(image_data) = decode_jpeg(&std::fs::read(path)?, max_size)? {
+        return Ok(image_data);
+    }
     let img = image::open(path)?;
+    Ok(from_dynamic_image(&img))
+}
+
+/// Decode an in-memory encoded image (PNG, JPEG, ...), guessing the format
+/// from its magic bytes.
+pub fn load_image_from_bytes(bytes: &[u8]) -> Result<ImageData> {
+    if let Some(image_data) = decode_jpeg(bytes, None)? {
+        return Ok(image_data);
+    }
+    let img = image::load_from_memory(bytes)?;
+    Ok(from_dynamic_image(&img))
+}
+
+/// Decode a JPEG directly when the `image` crate would get it wrong or do
+/// needless work; None means "use the `image` crate".
+///
+/// Four-channel CMYK is always handled here: the `image` crate assumes every
+/// CMYK JPEG stores inverted ink values, as Adobe writers do. Files without
+/// an Adobe APP14 segment store plain ink values and come out garbled that
+/// way, so the inversion is decided here. YCCK files (Adobe transform 2) are
+/// left to the `image` crate. Gray and RGB JPEGs are handled here only when
+/// `max_size` allows a reduced DCT scale.
+fn decode_jpeg(bytes: &[u8], max_size: Option<u32>) -> Result<Option<ImageData>> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return Ok(None);
+    }
+    let adobe_transform = jpeg_adobe_transform(bytes);
+    if adobe_transform == Some(2) {
+        return Ok(None);
+    }
+
+    let mut decoder = jpeg_decoder::Decoder::new(bytes);
+    decoder.read_info()?;
+    let info = decoder.info().ok_or_else(|| anyhow::anyhow!("JPEG header missing"))?;
+    let (full_w, full_h) = (info.width as u32, info.height as u32);
+    let target = max_size
+        .filter(|&m| full_w > m || full_h > m)
+        .map(|m| fit_within(full_w, full_h, m));
+
+    use jpeg_decoder::PixelFormat;
+    match info.pixel_format {
+        PixelFormat::CMYK32 => {
+            // The decoder's CMYK transform returns 255 minus the stored values
+            decoder.set_color_transform(jpeg_decoder::ColorTransform::CMYK);
+        }
+        PixelFormat::L8 | PixelFormat::RGB24 if target.is_some() => {}
+        _ => return Ok(None),
+    }
+    if let Some((w, h)) = target {
+        decoder.scale(w.min(u16::MAX as u32) as u16, h.min(u16::MAX as u32) as u16)?;
+    }
+    let data = decoder.decode()?;
+    let info = decoder.info().ok_or_else(|| anyhow::anyhow!("JPEG header missing"))?;
+
+    let inverted = adobe_transform.is_some();
+    let pixels: Vec<RGBA8> = match info.pixel_format {
+        PixelFormat::L8 => data.iter().map(|&v| RGBA8::new(v, v, v, 255)).collect(),
+        PixelFormat::RGB24 => data.chunks_exact(3).map(|s| RGBA8::new(s[0], s[1], s[2], 255)).collect(),
+        _ => data
+            .chunks_exact(4)
+            .map(|s| {
+                let ink = |v: u8| if inverted { v as u32 } else { 255 - v as u32 };
+                let k = 255 - ink(s[3]);
+                let ch = |v: u8| ((255 - ink(v)) * k / 255) as u8;
+                RGBA8::new(ch(s[0]), ch(s[1]), ch(s[2]), 255)
+            })
+            .collect(),
+    };
+    let (width, height) = (info.width as u32, info.height as u32);
+    if pixels.len() != (width * height) as usize {
+        return Err(anyhow::anyhow!("JPEG has truncated pixel data"));
+    }
+    Ok(Some(ImageData { width, height, pixels }))
+}
+
+/// Transform flag of the Adobe APP14 segment, if the JPEG has one.
+fn jpeg_adobe_transform(bytes: &[u8]) -> Option<u8> {
+    let mut i = 2;
+    while i + 4 <= bytes.len() {
+        if bytes[i] != 0xFF {
+            return None;
+        }
+        let marker = bytes[i + 1];
+        match marker {
+            0xFF => {
+                i += 1;
+                continue;
+            }
+            // Start of scan / end of image: no more header segments
+            0xDA | 0xD9 => return None,
+            0x01 | 0xD0..=0xD7 => {
+                i += 2;
+                continue;
+            }
+            _ => {}
+        }
+        let len = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+        let payload = bytes.get(i + 4..i + 2 + len)?;
+        if marker == 0xEE && payload.len() >= 12 && payload.starts_with(b"Adobe") {
+            return Some(payload[11]);
+        }
+        i += 2 + len;
+    }
+    None
+}
+
+/// Load every frame of an image file: one entry per page of a multi-page
+/// TIFF or per frame of an animated GIF, a single entry for all other
+/// formats.
+pub fn load_image_frames(path: &std::path::Path) -> Result<Vec<ImageData>> {
+    load_image_frames_hinted(path, None)
+}
+
+/// [`load_image_frames`] with the decode-time size hint of [`load_image_scaled`].
+pub fn load_image_frames_scaled(path: &std::path::Path, max_size: u32) -> Result<Vec<ImageData>> {
+    load_image_frames_hinted(path, Some(max_size))
+}
+
+fn load_image_frames_hinted(path: &std::path::Path, max_size: Option<u32>) -> Result<Vec<ImageData>> {
+    let is_tiff = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| matches!(e.to_lowercase().as_str(), "tif" | "tiff"));
+    if is_tiff {
+        load_tiff_pages(path)
+    } else if is_gif(path) {
+        Ok(load_gif_frames(path)?.into_iter().map(|(frame, _)| frame).collect())
+    } else {
+        Ok(vec![load_image_hinted(path, max_size)?])
+    }
+}
+
+fn is_gif(path: &std::path::Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("gif"))
+}
+
+/// Load the frames of an animation with how long each is shown: every
+/// frame of a GIF, composited onto the full canvas as a viewer would show
+/// it. Other formats give their single image with a zero delay.
+pub fn load_animation_frames(path: &std::path::Path) -> Result<Vec<(ImageData, std::time::Duration)>> {
+    if is_gif(path) {
+        load_gif_frames(path)
+    } else {
+        Ok(vec![(load_image(path)?, std::time::Duration::ZERO)])
+    }
+}
+
+fn load_gif_frames(path: &std::path::Path) -> Result<Vec<(ImageData, std::time::Duration)>> {
+    use image::AnimationDecoder;
+
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let frames = image::codecs::gif::GifDecoder::new(file)?.into_frames().collect_frames()?;
+    Ok(frames
+        .into_iter()
+        .map(|frame| {
+            let delay = std::time::Duration::from(frame.delay());
+            let buffer = frame.into_buffer();
+            let pixels = buffer.pixels().map(|p| RGBA8::new(p[0], p[1], p[2], p[3])).collect();
+            (ImageData { width: buffer.width(), height: buffer.height(), pixels }, delay)
+        })
+        .collect())
+}
+
+fn load_tiff_pages(path: &std::path::Path) -> Result<Vec<ImageData>> {
+    use tiff::decoder::{Decoder, DecodingResult};
+    use tiff::ColorType;
+
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut decoder = Decoder::new(file)?;
+    let mut pages = Vec::new();
+
+    loop {
+        let (width, height) = decoder.dimensions()?;
+        let color_type = decoder.colortype()?;
+        let samples: Vec<u8> = match decoder.read_image()? {
+            DecodingResult::U8(v) => v,
+            DecodingResult::U16(v) => v.into_iter().map(|s| (s >> 8) as u8).collect(),
+            _ => return Err(anyhow::anyhow!("unsupported TIFF sample format ({:?})", color_type)),
+        };
+
+        let to_rgba: fn(&[u8]) -> RGBA8 = match color_type {
+            ColorType::Gray(_) => |s| RGBA8::new(s[0], s[0], s[0], 255),
+            ColorType::GrayA(_) => |s| RGBA8::new(s[0], s[0], s[0], s[1]),
+            ColorType::RGB(_) => |s| RGBA8::new(s[0], s[1], s[2], 255),
+            ColorType::RGBA(_) => |s| RGBA8::new(s[0], s[1], s[2], s[3]),
+            ColorType::CMYK(_) => |s| {
+                let k = 255 - s[3] as u32;
+                let ch = |c: u8| ((255 - c as u32) * k / 255) as u8;
+                RGBA8::new(ch(s[0]), ch(s[1]), ch(s[2]), 255)
+            },
+            other => return Err(anyhow::anyhow!("unsupported TIFF color type {:?}", other)),
+        };
+        let channels = match color_type {
+            ColorType::Gray(_) => 1,
+            ColorType::GrayA(_) => 2,
+            ColorType::RGB(_) => 3,
+            _ => 4,
+        };
+
+        let pixels: Vec<RGBA8> = samples.chunks_exact(channels).map(to_rgba).collect();
+        if pixels.len() != (width * height) as usize {
+            return Err(anyhow::anyhow!("TIFF page {} has truncated pixel data", pages.len()));
+        }
+        pages.push(ImageData { width, height, pixels });
+
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image()?;
+    }
+
+    Ok(pages)
+}
+
+/// Whether some pixels are fully transparent and every other pixel fully
+/// opaque, as decoded from keyed transparency such as a PNG `tRNS` palette
+/// entry.
+pub fn has_cutout_transparency(image_data: &ImageData) -> bool {
+    image_data.pixels.iter().all(|p| p.a == 0 || p.a == 255) && image_data.pixels.iter().any(|p| p.a == 0)
+}
+
+fn from_dynamic_image(img: &image::DynamicImage) -> ImageData {
     let rgba = img.to_rgba8();
 
     let pixels: Vec<RGBA8> = rgba
@@ -17,11 +333,11 @@ pub fn load_image(path: &std::path::Path) -> Result<ImageData> {
         .map(|p| RGBA8::new(p[0], p[1], p[2], p[3]))
         .collect();
 
-    Ok(ImageData {
+    ImageData {
         width: rgba.width(),
         height: rgba.height(),
         pixels,
-    })
+    }
 }
 
 /// Resize image if either dimension exceeds max_size, maintaining aspect ratio.
@@ -32,14 +348,40 @@ pub fn resize_if_needed(image_data: ImageData, max_size: u32) -> ImageData {
         return image_data;
     }
 
-    let scale = (max_size as f64 / w as f64).min(max_size as f64 / h as f64);
-    let new_w = (w as f64 * scale).round() as u32;
-    let new_h = (h as f64 * scale).round() as u32;
-
+    let (new_w, new_h) = fit_within(w, h, max_size);
     eprintln!(
         "  Auto-resizing {}x{} -> {}x{} (max_size={})",
         w, h, new_w, new_h, max_size
     );
+    resize_exact(&image_data, new_w, new_h, image::imageops::FilterType::Lanczos3)
+}
+
+/// Quietly shrink an image so neither dimension exceeds `max_size`, using a
+/// fast triangle filter. Meant for previews; smaller images are returned as is.
+pub fn downscale_to_fit(image_data: &ImageData, max_size: u32) -> ImageData {
+    let (w, h) = (image_data.width, image_data.height);
+    if w <= max_size && h <= max_size {
+        return image_data.clone();
+    }
+    let (new_w, new_h) = fit_within(w, h, max_size);
+    resize_exact(image_data, new_w, new_h, image::imageops::FilterType::Triangle)
+}
+
+/// Dimensions of `w`x`h` scaled down to fit in a `max_size` square.
+fn fit_within(w: u32, h: u32, max_size: u32) -> (u32, u32) {
+    let scale = (max_size as f64 / w as f64).min(max_size as f64 / h as f64);
+    let new_w = ((w as f64 * scale).round() as u32).max(1);
+    let new_h = ((h as f64 * scale).round() as u32).max(1);
+    (new_w, new_h)
+}
+
+fn resize_exact(
+    image_data: &ImageData,
+    new_w: u32,
+    new_h: u32,
+    filter: image::imageops::FilterType,
+) -> ImageData {
+    let (w, h) = (image_data.width, image_data.height);
 
     // Convert to image::RgbaImage for resizing
     let mut rgba_img = image::RgbaImage::new(w, h);
@@ -51,7 +393,7 @@ pub fn resize_if_needed(image_data: ImageData, max_size: u32) -> ImageData {
         }
     }
 
-    let resized = image::imageops::resize(&rgba_img, new_w, new_h, image::imageops::FilterType::Lanczos3);
+    let resized = image::imageops::resize(&rgba_img, new_w, new_h, filter);
 
     let pixels: Vec<RGBA8> = resized
         .pixels()
@@ -66,10 +408,12 @@ pub fn resize_if_needed(image_data: ImageData, max_size: u32) -> ImageData {
 }
 
 /// Median-cut color quantization for better color space coverage.
+/// `num_colors` above [`MAX_COLORS`] is clamped to it; 0 is an error.
 pub fn quantize_colors(image_data: &ImageData, num_colors: usize) -> Result<ImageData> {
     if num_colors == 0 {
         return Err(anyhow::anyhow!("num_colors must be greater than 0"));
     }
+    let num_colors = num_colors.min(MAX_COLORS);
 
     let palette = median_cut(&image_data.pixels, num_colors);
 
@@ -114,7 +458,10 @@ fn median_cut(pixels: &[RGBA8], num_colors: usize) -> Vec<RGBA8> {
 
     let mut boxes: Vec<Vec<(u8, u8, u8)>> = vec![colors];
     while boxes.len() < num_colors {
-        // Find the box with the largest range to split
+        // Find the box with the largest range to split; ties go to the
+        // larger box, then to the earliest. `split_box` orders colors
+        // completely, so the box list (and the palette) depends only on
+        // which colors were sampled, not on their order.
         let mut best_idx = 0;
         let mut best_range = 0u16;
         for (i, b) in boxes.iter().enumerate() {
@@ -155,7 +502,13 @@ pub fn box_max_range(colors: &[(u8, u8, u8)]) -> u16 {
     rr.max(gr).max(br)
 }
 
-pub fn split_box(mut colors: Vec<(u8, u8, u8)>) -> (Vec<(u8, u8, u8)>, Vec<(u8, u8, u8)>) {
+/// The RGB colors of one median-cut box.
+type ColorBox = Vec<(u8, u8, u8)>;
+
+/// Split a box at the median of its widest channel. Colors that tie on that
+/// channel are ordered by the other two, so the halves never depend on the
+/// input order.
+pub fn split_box(mut colors: ColorBox) -> (ColorBox, ColorBox) {
     let (mut rmin, mut rmax) = (255u8, 0u8);
     let (mut gmin, mut gmax) = (255u8, 0u8);
     let (mut bmin, mut bmax) = (255u8, 0u8);
@@ -169,11 +522,11 @@ pub fn split_box(mut colors: Vec<(u8, u8, u8)>) -> (Vec<(u8, u8, u8)>, Vec<(u8,
     let br = bmax - bmin;
 
     if rr >= gr && rr >= br {
-        colors.sort_by_key(|c| c.0);
+        colors.sort_by_key(|&(r, g, b)| (r, g, b));
     } else if gr >= br {
-        colors.sort_by_key(|c| c.1);
+        colors.sort_by_key(|&(r, g, b)| (g, r, b));
     } else {
-        colors.sort_by_key(|c| c.2);
+        colors.sort_by_key(|&(r, g, b)| (b, r, g));
     }
 
     let mid = colors.len() / 2;
@@ -196,6 +549,7 @@ pub fn box_average(colors: &[(u8, u8, u8)]) -> RGBA8 {
 }
 
 #[cfg(test)]
+#[allow(clippy::module_inception, reason = "the included file declares its own `mod tests`")]
 mod tests {
     include!("image_processor_tests.rs");
 }
@@ -45,6 +45,45 @@ mod tests {
         assert_eq!(img.pixels.len(), 100);
     }
 
+    #[test]
+    fn test_from_rgba() {
+        let bytes = [255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 10, 20, 30, 0];
+        let img = ImageData::from_rgba(2, 2, &bytes).unwrap();
+        assert_eq!((img.width, img.height), (2, 2));
+        assert_eq!(img.pixels[1], RGBA8::new(0, 255, 0, 255));
+        assert_eq!(img.pixels[3], RGBA8::new(10, 20, 30, 0));
+
+        assert!(ImageData::from_rgba(2, 2, &bytes[..12]).is_err());
+        assert!(ImageData::from_rgba(3, 2, &bytes).is_err());
+        assert!(ImageData::from_rgba(u32::MAX, u32::MAX, &bytes).is_err());
+    }
+
+    #[test]
+    fn test_crop() {
+        let img = create_gradient_image(10, 8);
+        let cropped = img.crop(2, 3, 4, 5).unwrap();
+        assert_eq!((cropped.width, cropped.height), (4, 5));
+        assert_eq!(cropped.pixels.len(), 20);
+        assert_eq!(cropped.pixels[0], img.pixels[3 * 10 + 2]);
+        assert_eq!(cropped.pixels[19], img.pixels[7 * 10 + 5]);
+
+        // The whole image is a valid crop
+        assert_eq!(img.crop(0, 0, 10, 8).unwrap().pixels, img.pixels);
+        // Out of bounds, empty and overflowing rectangles are rejected
+        assert!(img.crop(8, 0, 3, 1).is_err());
+        assert!(img.crop(0, 7, 1, 2).is_err());
+        assert!(img.crop(0, 0, 0, 4).is_err());
+        assert!(img.crop(u32::MAX, 0, 2, 1).is_err());
+    }
+
+    #[test]
+    fn test_quantize_colors_clamps_to_max_colors() {
+        let img = create_gradient_image(40, 40);
+        let quantized = quantize_colors(&img, 1000).unwrap();
+        let distinct: std::collections::HashSet<_> = quantized.pixels.iter().collect();
+        assert!(distinct.len() <= MAX_COLORS, "{} colors", distinct.len());
+    }
+
     #[test]
     fn test_quantize_colors_reduces_to_exact_count() {
         let img = create_gradient_image(50, 50);
@@ -166,6 +205,29 @@ mod tests {
         assert!(unique_colors.len() <= 4);
     }
 
+    #[test]
+    fn test_median_cut_ignores_input_order() {
+        // Red spans the widest range but repeats, so every split lands among
+        // colors that tie on red and differ in green and blue
+        let colors: Vec<RGBA8> = (0..64u32)
+            .map(|i| {
+                let r = if i < 10 { 0 } else if i < 40 { 120 } else { 240 };
+                RGBA8::new(r, (i * 37 % 64) as u8, (i * 11 % 48) as u8, 255)
+            })
+            .collect();
+        let mut shuffled = colors.clone();
+        for i in 0..shuffled.len() {
+            shuffled.swap(i, (i * 29 + 7) % 64);
+        }
+        let mut reversed = colors.clone();
+        reversed.reverse();
+
+        let palette = median_cut(&colors, 8);
+        assert_eq!(palette.len(), 8);
+        assert_eq!(median_cut(&shuffled, 8), palette);
+        assert_eq!(median_cut(&reversed, 8), palette);
+    }
+
     #[test]
     fn test_box_max_range() {
         let colors = vec![(0, 0, 0), (255, 255, 255), (128, 128, 128)];
@@ -202,7 +264,7 @@ mod tests {
             (200, 128, 128),
         ];
 
-        let (left, right): (Vec<(u8, u8, u8)>, Vec<(u8, u8, u8)>) = crate::image_processor::split_box(colors);
+        let (left, right) = crate::image_processor::split_box(colors);
         assert!(!left.is_empty());
         assert!(!right.is_empty());
         assert_eq!(left.len() + right.len(), 4);
@@ -242,4 +304,162 @@ mod tests {
         assert_eq!(result.width, 4096);
         assert_eq!(result.height, 4096);
     }
+
+    #[test]
+    fn test_load_image_from_bytes_png() {
+        let mut img = image::RgbaImage::new(4, 3);
+        img.put_pixel(1, 2, image::Rgba([10, 20, 30, 255]));
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgba8(img)
+            .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+            .unwrap();
+
+        let result = load_image_from_bytes(&bytes).unwrap();
+        assert_eq!(result.width, 4);
+        assert_eq!(result.height, 3);
+        assert_eq!(result.pixels[2 * 4 + 1], RGBA8::new(10, 20, 30, 255));
+    }
+
+    #[test]
+    fn test_load_image_with_bit_depth_16_bit_gray_png() {
+        let path = std::env::temp_dir().join("img2svg_gray16.png");
+        let img = image::ImageBuffer::<image::Luma<u16>, _>::from_fn(4, 2, |x, _| image::Luma([x as u16 * 20_000]));
+        image::DynamicImage::ImageLuma16(img).save(&path).unwrap();
+
+        let (result, bit_depth) = load_image_with_bit_depth(&path).unwrap();
+        assert_eq!(bit_depth, 16);
+        assert_eq!((result.width, result.height), (4, 2));
+        // 40000 / 65535 of full scale, rounded to 8 bits
+        assert_eq!(result.pixels[2], RGBA8::new(156, 156, 156, 255));
+
+        let path8 = std::env::temp_dir().join("img2svg_gray8.png");
+        image::DynamicImage::ImageLuma8(image::GrayImage::new(2, 2)).save(&path8).unwrap();
+        assert_eq!(load_image_with_bit_depth(&path8).unwrap().1, 8);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&path8);
+    }
+
+    #[test]
+    fn test_load_image_from_bytes_rejects_garbage() {
+        assert!(load_image_from_bytes(b"not an image").is_err());
+    }
+
+    #[test]
+    fn test_load_image_frames_multi_page_tiff() {
+        use tiff::encoder::{colortype, TiffEncoder};
+
+        let path = std::path::PathBuf::from("/tmp/test_two_pages.tiff");
+        {
+            let file = std::fs::File::create(&path).unwrap();
+            let mut encoder = TiffEncoder::new(file).unwrap();
+            let red: Vec<u8> = [255u8, 0, 0].repeat(4 * 3);
+            let blue: Vec<u8> = [0u8, 0, 255].repeat(2 * 5);
+            encoder.write_image::<colortype::RGB8>(4, 3, &red).unwrap();
+            encoder.write_image::<colortype::RGB8>(2, 5, &blue).unwrap();
+        }
+
+        let frames = load_image_frames(&path).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].width, frames[0].height), (4, 3));
+        assert_eq!((frames[1].width, frames[1].height), (2, 5));
+        assert!(frames[0].pixels.iter().all(|&p| p == RGBA8::new(255, 0, 0, 255)));
+        assert!(frames[1].pixels.iter().all(|&p| p == RGBA8::new(0, 0, 255, 255)));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    fn encode_cmyk_jpeg(ink: [u8; 4]) -> Vec<u8> {
+        let data = ink.repeat(8 * 8);
+        let mut bytes = Vec::new();
+        jpeg_encoder::Encoder::new(&mut bytes, 100)
+            .encode(&data, 8, 8, jpeg_encoder::ColorType::Cmyk)
+            .unwrap();
+        bytes
+    }
+
+    fn strip_adobe_segment(bytes: &[u8]) -> Vec<u8> {
+        let start = bytes
+            .windows(7)
+            .position(|w| w[..2] == [0xFF, 0xEE] && &w[4..] == b"Ado")
+            .unwrap();
+        let len = u16::from_be_bytes([bytes[start + 2], bytes[start + 3]]) as usize;
+        [&bytes[..start], &bytes[start + 2 + len..]].concat()
+    }
+
+    fn assert_close(p: RGBA8, expected: (u8, u8, u8)) {
+        let close = |a: u8, b: u8| (a as i32 - b as i32).abs() <= 4;
+        assert!(
+            close(p.r, expected.0) && close(p.g, expected.1) && close(p.b, expected.2),
+            "got {:?}, expected {:?}",
+            p,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_load_cmyk_jpeg_adobe_inverted() {
+        // Full cyan ink with 20% black
+        let bytes = encode_cmyk_jpeg([255, 0, 0, 51]);
+        assert!(jpeg_adobe_transform(&bytes).is_some());
+        let image_data = load_image_from_bytes(&bytes).unwrap();
+        assert_eq!((image_data.width, image_data.height), (8, 8));
+        assert_close(image_data.pixels[27], (0, 204, 204));
+    }
+
+    #[test]
+    fn test_load_cmyk_jpeg_without_adobe_marker() {
+        // The encoder inverts on write; pre-invert so the file stores plain
+        // ink values, then drop the Adobe segment like non-Adobe writers do
+        let bytes = strip_adobe_segment(&encode_cmyk_jpeg([0, 255, 255, 204]));
+        assert!(jpeg_adobe_transform(&bytes).is_none());
+        let image_data = load_image_from_bytes(&bytes).unwrap();
+        assert_close(image_data.pixels[27], (0, 204, 204));
+
+        let path = std::path::PathBuf::from("/tmp/test_cmyk_plain.jpg");
+        std::fs::write(&path, &bytes).unwrap();
+        assert_close(load_image(&path).unwrap().pixels[0], (0, 204, 204));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_load_image_scaled_decodes_jpeg_at_reduced_size() {
+        let (w, h) = (1024u16, 768u16);
+        let data: Vec<u8> = (0..w as usize * h as usize)
+            .flat_map(|i| {
+                let (x, y) = (i % w as usize, i / w as usize);
+                [(x / 4) as u8, (y / 3) as u8, 128]
+            })
+            .collect();
+        let path = std::path::PathBuf::from("/tmp/test_scaled_decode.jpg");
+        jpeg_encoder::Encoder::new_file(&path, 85)
+            .unwrap()
+            .encode(&data, w, h, jpeg_encoder::ColorType::Rgb)
+            .unwrap();
+
+        // 1/8 DCT scale exactly covers a 128px target
+        let small = load_image_scaled(&path, 128).unwrap();
+        assert_eq!((small.width, small.height), (128, 96));
+        assert_eq!(small.pixels.len(), 128 * 96);
+        // Otherwise the smallest scale that still covers the target
+        let medium = load_image_scaled(&path, 300).unwrap();
+        assert_eq!((medium.width, medium.height), (512, 384));
+        // No reduction needed: full size, same as load_image
+        let full = load_image_scaled(&path, 4096).unwrap();
+        assert_eq!((full.width, full.height), (1024, 768));
+        assert_eq!(full.pixels, load_image(&path).unwrap().pixels);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_supported_extensions_match_is_supported_image() {
+        let extensions = supported_extensions();
+        assert!(extensions.contains(&"png"));
+        assert!(extensions.contains(&"webp"));
+        for ext in extensions {
+            assert!(is_supported_image(std::path::Path::new(&format!("a.{}", ext))));
+            assert!(is_supported_image(std::path::Path::new(&format!("a.{}", ext.to_uppercase()))));
+        }
+        assert!(!is_supported_image(std::path::Path::new("a.svg")));
+    }
 }
@@ -36,21 +36,60 @@ pub mod region_extractor;
 pub mod path_simplifier;
 pub mod bezier_fitter;
 pub mod enhanced_vectorizer;
+pub mod morphology;
+pub mod centerline;
+pub mod primitives;
+pub mod converter;
+pub mod threads;
+pub mod error;
+pub mod validation;
+pub mod batch;
+pub mod data_uri;
+pub mod animation;
+#[cfg(feature = "raster")]
+pub mod raster;
 
-pub use image_processor::{load_image, quantize_colors, ImageData};
-pub use svg_generator::{generate_svg, generate_svg_advanced};
-pub use vectorizer::{vectorize, Curve, Point, VectorizedData};
+pub use image_processor::{
+    load_image, load_image_frames, load_image_frames_scaled, load_image_from_bytes, load_animation_frames,
+    load_image_with_bit_depth,
+    load_image_scaled, quantize_colors, is_supported_image, supported_extensions, ImageData, MAX_COLORS,
+};
+pub use svg_generator::{
+    generate_svg, generate_svg_advanced, generate_svg_string, generate_svg_string_with_precision,
+    generate_svg_to_writer,
+};
+pub use vectorizer::{vectorize, vectorize_with_quantizer, Curve, Point, Quantizer, VectorizedData};
 pub use preprocessor::{preprocess, PreprocessOptions};
 pub use enhanced_vectorizer::{
-    vectorize_enhanced, generate_enhanced_svg, write_enhanced_svg,
-    EnhancedOptions, EnhancedVectorData, EnhancedPath,
+    vectorize_enhanced, vectorize_enhanced_with_stats, vectorize_enhanced_with_progress,
+    generate_enhanced_svg, generate_enhanced_svg_with_options,
+    write_enhanced_svg, write_enhanced_svg_with_options, write_enhanced_svgz,
+    write_enhanced_svgz_with_options, write_enhanced_svg_to_writer, write_enhanced_svg_to_writer_with_options,
+    fit_polyline_to_svg_path, palette_legend,
+    vectorize_mask, merge_paths_by_color, write_color_separations, prepare_enhanced, finish,
+    write_color_separations_with_options,
+    extract_polygons, stroke_polyline, net_polygon_area, ColoredPolygon, ConversionStats,
+    CurveOptions, EnhancedOptions, EnhancedOptionsBuilder, EnhancedVectorData, EnhancedPath, EnhancedStroke, FillRule,
+    PreparedImage, ProgressEvent, RenderMode,
+    StrokeLinecap, StrokeLinejoin, ZOrder,
 };
+pub use enhanced_quantizer::ColorSpace;
+pub use primitives::Primitive;
+pub use converter::Converter;
+pub use threads::set_thread_count;
+pub use error::Img2SvgError;
+pub use validation::{validate_geometry, validate_svg_wellformed};
+pub use batch::{batch_convert_parallel, convert_all, BatchReport};
+pub use data_uri::{convert_to_data_uri, svg_data_uri, DataUriEncoding};
+pub use animation::convert_animated;
+#[cfg(feature = "raster")]
+pub use raster::{rasterize_svg, svg_pixel_diff};
 pub use anyhow::Result;
 
 /// Options for image to SVG conversion
 #[derive(Debug, Clone)]
 pub struct ConversionOptions {
-    /// Number of colors for quantization (default: 16)
+    /// Number of colors for quantization, at most [`MAX_COLORS`] (default: 16)
     pub num_colors: usize,
     /// Edge detection threshold 0.0-1.0 (default: 0.1)
     pub threshold: f64,
@@ -60,6 +99,14 @@ pub struct ConversionOptions {
     pub hierarchical: bool,
     /// Use advanced SVG generation (default: false)
     pub advanced: bool,
+    /// Color quantizer (default: median-cut)
+    pub quantizer: Quantizer,
+    /// Reject out-of-range values with [`Img2SvgError::InvalidOptions`]
+    /// instead of clamping them (default: false)
+    pub strict: bool,
+    /// Decimal places kept in coordinates, trailing zeros trimmed; 0 writes
+    /// integers only (default: 2)
+    pub decimal_precision: u8,
 }
 
 impl Default for ConversionOptions {
@@ -70,7 +117,114 @@ impl Default for ConversionOptions {
             smooth_level: 5,
             hierarchical: false,
             advanced: false,
+            quantizer: Quantizer::MedianCut,
+            strict: false,
+            decimal_precision: 2,
+        }
+    }
+}
+
+impl ConversionOptions {
+    /// Options with every value inside its documented range: out-of-range
+    /// values are clamped, or rejected when `strict` is set.
+    pub fn normalized(&self) -> Result<ConversionOptions> {
+        if self.strict {
+            let problems = [
+                (!(0.0..=1.0).contains(&self.threshold), "threshold must be between 0.0 and 1.0".to_string()),
+                (self.smooth_level > 10, "smooth_level must be at most 10".to_string()),
+                (self.num_colors > MAX_COLORS, format!("num_colors must be at most {}", MAX_COLORS)),
+            ];
+            if let Some((_, msg)) = problems.into_iter().find(|p| p.0) {
+                return Err(Img2SvgError::InvalidOptions(msg).into());
+            }
+        }
+        Ok(ConversionOptions {
+            threshold: if self.threshold.is_nan() { 0.1 } else { self.threshold.clamp(0.0, 1.0) },
+            smooth_level: self.smooth_level.min(10),
+            num_colors: self.num_colors.min(MAX_COLORS),
+            ..self.clone()
+        })
+    }
+
+    /// Start a [`ConversionOptionsBuilder`] from the defaults.
+    pub fn builder() -> ConversionOptionsBuilder {
+        ConversionOptionsBuilder::default()
+    }
+}
+
+/// Chainable construction of [`ConversionOptions`]; unset fields keep
+/// their defaults.
+///
+/// ```rust
+/// use img2svg::ConversionOptions;
+///
+/// let options = ConversionOptions::builder().num_colors(8).smooth_level(3).hierarchical(true).build()?;
+/// assert_eq!(options.num_colors, 8);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConversionOptionsBuilder {
+    options: ConversionOptions,
+}
+
+impl ConversionOptionsBuilder {
+    /// Sets [`ConversionOptions::num_colors`]: 1..=[`MAX_COLORS`], checked by `build`.
+    pub fn num_colors(mut self, num_colors: usize) -> Self {
+        self.options.num_colors = num_colors;
+        self
+    }
+
+    /// Sets [`ConversionOptions::threshold`]: 0.0..=1.0.
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.options.threshold = threshold;
+        self
+    }
+
+    /// Sets [`ConversionOptions::smooth_level`]: 0..=10, clamped by `build`.
+    pub fn smooth_level(mut self, smooth_level: u8) -> Self {
+        self.options.smooth_level = smooth_level;
+        self
+    }
+
+    /// Sets [`ConversionOptions::hierarchical`].
+    pub fn hierarchical(mut self, hierarchical: bool) -> Self {
+        self.options.hierarchical = hierarchical;
+        self
+    }
+
+    /// Sets [`ConversionOptions::advanced`].
+    pub fn advanced(mut self, advanced: bool) -> Self {
+        self.options.advanced = advanced;
+        self
+    }
+
+    /// Sets [`ConversionOptions::quantizer`].
+    pub fn quantizer(mut self, quantizer: Quantizer) -> Self {
+        self.options.quantizer = quantizer;
+        self
+    }
+
+    /// Sets [`ConversionOptions::strict`].
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    /// Sets [`ConversionOptions::decimal_precision`]: decimal places, 0 for integers.
+    pub fn decimal_precision(mut self, decimal_precision: u8) -> Self {
+        self.options.decimal_precision = decimal_precision;
+        self
+    }
+
+    /// Finish the options: `smooth_level` is clamped to 0..=10, and a
+    /// `num_colors` outside 1..=[`MAX_COLORS`] is rejected with
+    /// [`Img2SvgError::InvalidOptions`].
+    pub fn build(self) -> Result<ConversionOptions> {
+        if !(1..=MAX_COLORS).contains(&self.options.num_colors) {
+            let msg = format!("num_colors must be between 1 and {}", MAX_COLORS);
+            return Err(Img2SvgError::InvalidOptions(msg).into());
         }
+        Ok(ConversionOptions { smooth_level: self.options.smooth_level.min(10), ..self.options })
     }
 }
 
@@ -97,25 +251,60 @@ pub fn convert(
     output_path: &std::path::Path,
     options: &ConversionOptions,
 ) -> Result<()> {
+    let options = &options.normalized()?;
     // Load the image
     let image_data = load_image(input_path)?;
 
     // Vectorize the image
-    let vectorized_data = vectorize(
+    let vectorized_data = vectorize_with_quantizer(
         &image_data,
         options.num_colors,
         options.threshold,
         options.smooth_level,
         options.hierarchical,
+        options.quantizer,
     )?;
 
-    // Generate SVG output
-    if options.advanced {
-        generate_svg_advanced(&vectorized_data, output_path)?;
-    } else {
-        generate_svg(&vectorized_data, output_path)?;
-    }
+    // Generate SVG output (advanced mode writes the same document)
+    std::fs::write(output_path, generate_svg_string_with_precision(&vectorized_data, options.decimal_precision))?;
+    Ok(())
+}
 
+/// Convert only a rectangle of an image file to SVG
+///
+/// `rect` is `(x, y, width, height)` in image pixels. The image is cropped
+/// before vectorizing, so the output's viewBox is in crop-local coordinates.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use img2svg::{convert_region, ConversionOptions};
+/// use std::path::Path;
+///
+/// let options = ConversionOptions::default();
+/// convert_region(Path::new("page.png"), Path::new("logo.svg"), (40, 20, 128, 64), &options)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn convert_region(
+    input_path: &std::path::Path,
+    output_path: &std::path::Path,
+    rect: (u32, u32, u32, u32),
+    options: &ConversionOptions,
+) -> Result<()> {
+    let options = &options.normalized()?;
+    let (x, y, width, height) = rect;
+    let image_data = load_image(input_path)?.crop(x, y, width, height)?;
+
+    let vectorized_data = vectorize_with_quantizer(
+        &image_data,
+        options.num_colors,
+        options.threshold,
+        options.smooth_level,
+        options.hierarchical,
+        options.quantizer,
+    )?;
+
+    std::fs::write(output_path, generate_svg_string_with_precision(&vectorized_data, options.decimal_precision))?;
     Ok(())
 }
 
@@ -133,73 +322,80 @@ pub fn convert(
 ///
 /// A String containing the SVG content
 pub fn convert_to_svg_string(image_data: &ImageData, options: &ConversionOptions) -> Result<String> {
-    let vectorized_data = vectorize(
+    let options = &options.normalized()?;
+    let vectorized_data = vectorize_with_quantizer(
         image_data,
         options.num_colors,
         options.threshold,
         options.smooth_level,
         options.hierarchical,
+        options.quantizer,
     )?;
 
-    // Generate SVG to a temporary location, then read it back
-    use std::io::Write;
-    let mut buffer = Vec::new();
-
-    // Write SVG header
-    let bg = vectorized_data.background_color;
-    let bg_str = format!("#{:02x}{:02x}{:02x}", bg.0, bg.1, bg.2);
-
-    writeln!(
-        &mut buffer,
-        r#"<svg width="{}" height="{}" viewBox="0 0 {} {}" xmlns="http://www.w3.org/2000/svg">"#,
-        vectorized_data.width,
-        vectorized_data.height,
-        vectorized_data.width,
-        vectorized_data.height
-    )?;
-    writeln!(
-        &mut buffer,
-        r#"  <rect width="{}" height="{}" fill="{}"/>"#,
-        vectorized_data.width,
-        vectorized_data.height,
-        bg_str
-    )?;
-
-    // Write curves
-    for curve in &vectorized_data.curves {
-        let color_str = format!(
-            "#{:02x}{:02x}{:02x}",
-            curve.color.0, curve.color.1, curve.color.2
-        );
-
-        let path_str = if !curve.subpaths.is_empty() {
-            svg_generator::create_multi_path_string(&curve.subpaths)
-        } else if !curve.points.is_empty() {
-            svg_generator::create_subpath_string(&curve.points, curve.is_closed)
-        } else {
-            continue;
-        };
-
-        if path_str.is_empty() {
-            continue;
-        }
-
-        writeln!(
-            &mut buffer,
-            r#"  <path d="{}" fill="{}" stroke="none"/>"#,
-            path_str, color_str
-        )?;
-    }
+    Ok(generate_svg_string_with_precision(&vectorized_data, options.decimal_precision))
+}
 
-    writeln!(&mut buffer, "</svg>")?;
+/// Convert image data directly to an SVG string with the enhanced pipeline
+///
+/// The in-memory counterpart of [`write_enhanced_svg_with_options`]: the
+/// image is vectorized with [`vectorize_enhanced`] and rendered with the
+/// same options.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use img2svg::{convert_enhanced_to_svg_string, load_image, EnhancedOptions};
+/// use std::path::Path;
+///
+/// let image_data = load_image(Path::new("input.png"))?;
+/// let svg = convert_enhanced_to_svg_string(&image_data, &EnhancedOptions::default())?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn convert_enhanced_to_svg_string(image_data: &ImageData, options: &EnhancedOptions) -> Result<String> {
+    let data = vectorize_enhanced(image_data, options)?;
+    Ok(generate_enhanced_svg_with_options(&data, options))
+}
 
-    Ok(String::from_utf8(buffer)?)
+/// Convert tightly packed RGBA bytes (e.g. a browser canvas `ImageData`)
+/// with the enhanced pipeline and return the SVG. No file IO or image
+/// decoding is involved, which suits WASM builds; see
+/// [`ImageData::from_rgba`] for the expected layout.
+pub fn convert_rgba_to_svg_string(width: u32, height: u32, bytes: &[u8], options: &EnhancedOptions) -> Result<String> {
+    options.validate()?;
+    convert_enhanced_to_svg_string(&ImageData::from_rgba(width, height, bytes)?, options)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_conversion_options_builder() {
+        let options = ConversionOptions::builder()
+            .num_colors(8)
+            .threshold(0.3)
+            .smooth_level(42)
+            .hierarchical(true)
+            .advanced(true)
+            .quantizer(Quantizer::KMeans)
+            .strict(true)
+            .decimal_precision(1)
+            .build()
+            .unwrap();
+        assert_eq!(options.num_colors, 8);
+        assert_eq!(options.threshold, 0.3);
+        assert_eq!(options.smooth_level, 10);
+        assert!(options.hierarchical && options.advanced && options.strict);
+        assert_eq!(options.quantizer, Quantizer::KMeans);
+        assert_eq!(options.decimal_precision, 1);
+
+        assert_eq!(ConversionOptions::builder().build().unwrap().num_colors, 16);
+        for num_colors in [0, 257] {
+            let err = ConversionOptions::builder().num_colors(num_colors).build().unwrap_err();
+            assert!(matches!(err.downcast_ref::<Img2SvgError>(), Some(Img2SvgError::InvalidOptions(_))));
+        }
+    }
+
     #[test]
     fn test_conversion_options_default() {
         let options = ConversionOptions::default();
@@ -208,5 +404,75 @@ mod tests {
         assert_eq!(options.smooth_level, 5);
         assert!(!options.hierarchical);
         assert!(!options.advanced);
+        assert!(!options.strict);
+    }
+
+    #[test]
+    fn test_strict_rejects_out_of_range_options() {
+        let cases = [
+            ConversionOptions { threshold: 1.5, ..Default::default() },
+            ConversionOptions { smooth_level: 11, ..Default::default() },
+            ConversionOptions { num_colors: 300, ..Default::default() },
+        ];
+        for lenient in cases {
+            let strict = ConversionOptions { strict: true, ..lenient.clone() };
+            let err = strict.normalized().unwrap_err();
+            assert!(matches!(err.downcast_ref::<Img2SvgError>(), Some(Img2SvgError::InvalidOptions(_))));
+
+            let clamped = lenient.normalized().unwrap();
+            assert!(clamped.threshold <= 1.0 && clamped.smooth_level <= 10 && clamped.num_colors <= 256);
+        }
+
+        let clamped = ConversionOptions { threshold: 1.5, smooth_level: 11, num_colors: 300, ..Default::default() }
+            .normalized()
+            .unwrap();
+        assert_eq!((clamped.threshold, clamped.smooth_level, clamped.num_colors), (1.0, 10, 256));
+        assert!(ConversionOptions { strict: true, ..Default::default() }.normalized().is_ok());
+    }
+
+    fn red_square() -> ImageData {
+        let pixels = (0..20 * 20)
+            .map(|i| {
+                let (x, y) = (i % 20, i / 20);
+                if (5..15).contains(&x) && (5..15).contains(&y) {
+                    rgb::RGBA8::new(200, 0, 0, 255)
+                } else {
+                    rgb::RGBA8::new(255, 255, 255, 255)
+                }
+            })
+            .collect();
+        ImageData { width: 20, height: 20, pixels }
+    }
+
+    #[test]
+    fn test_convert_enhanced_to_svg_string() {
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+        let svg = convert_enhanced_to_svg_string(&red_square(), &options).unwrap();
+        validate_svg_wellformed(&svg).unwrap();
+        assert!(svg.contains("#c80000"));
+        let data = vectorize_enhanced(&red_square(), &options).unwrap();
+        assert_eq!(svg, generate_enhanced_svg_with_options(&data, &options));
+    }
+
+    #[test]
+    fn test_convert_rgba_to_svg_string() {
+        // Top row red, bottom row white
+        let bytes = [200, 0, 0, 255, 200, 0, 0, 255, 255, 255, 255, 255, 255, 255, 255, 255];
+        let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+        let svg = convert_rgba_to_svg_string(2, 2, &bytes, &options).unwrap();
+        validate_svg_wellformed(&svg).unwrap();
+        assert!(svg.contains("width=\"2\" height=\"2\""));
+        let image = ImageData::from_rgba(2, 2, &bytes).unwrap();
+        assert_eq!(svg, convert_enhanced_to_svg_string(&image, &options).unwrap());
+
+        assert!(convert_rgba_to_svg_string(2, 2, &bytes[..8], &options).is_err());
+    }
+
+    #[test]
+    fn test_convert_to_svg_string_matches_generate_svg() {
+        let options = ConversionOptions { num_colors: 2, decimal_precision: 0, ..Default::default() };
+        let svg = convert_to_svg_string(&red_square(), &options).unwrap();
+        let data = vectorize_with_quantizer(&red_square(), 2, 0.1, 5, false, Quantizer::MedianCut).unwrap();
+        assert_eq!(svg, generate_svg_string_with_precision(&data, 0));
     }
 }
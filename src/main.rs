@@ -1,38 +1,82 @@
 mod cli;
-mod image_processor;
-mod svg_generator;
-mod vectorizer;
-mod preprocessor;
-mod edge_detector;
-mod enhanced_quantizer;
-mod region_extractor;
-mod path_simplifier;
-mod bezier_fitter;
-mod enhanced_vectorizer;
 
 use anyhow::Result;
-use clap::Parser;
 use cli::{Cli, is_supported_image};
-use preprocessor::{preprocess, PreprocessOptions};
-use enhanced_vectorizer::{vectorize_enhanced, write_enhanced_svg, EnhancedOptions};
+use img2svg::image_processor;
+use img2svg::preprocess;
+use img2svg::{
+    generate_enhanced_svg_with_options, vectorize_enhanced_with_progress, write_color_separations_with_options,
+    write_enhanced_svg_with_options, write_enhanced_svgz_with_options, ProgressEvent,
+};
+use img2svg::{convert_animated, convert_to_svg_string, ConversionOptions, ImageData, MAX_COLORS};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
+/// Warn when regions dropped by the minimum-area filter cover more than
+/// this share of the image.
+const DISCARD_WARN_FRACTION: f64 = 0.01;
+
+/// Where the generated SVG is written.
+enum Output<'a> {
+    File(&'a Path),
+    Stdout,
+}
+
 /// Process a single image file.
 fn process_file(
     input_path: &Path,
     output_path: &Path,
     cli: &Cli,
 ) -> Result<()> {
-    let mut image_data = image_processor::load_image(input_path)?;
+    let is_gif = input_path.extension().is_some_and(|e| e.eq_ignore_ascii_case("gif"));
+    if is_gif && !cli.original {
+        let frames = image_processor::load_animation_frames(input_path)?;
+        if frames.len() > 1 {
+            if is_svgz(output_path) {
+                anyhow::bail!("animated output is only written as plain .svg");
+            }
+            eprintln!("  {} frames, writing an animated SVG", frames.len());
+            let frames: Vec<_> = frames
+                .into_iter()
+                .map(|(frame, delay)| (image_processor::resize_if_needed(frame, cli.max_size), delay))
+                .collect();
+            let mut options = cli.enhanced_options();
+            options.source_path = Some(input_path.to_path_buf());
+            std::fs::write(output_path, convert_animated(&frames, &options)?)?;
+            return Ok(());
+        }
+    }
+
+    let mut frames = image_processor::load_image_frames_scaled(input_path, cli.max_size)?;
+    if frames.len() == 1 {
+        return process_image(frames.remove(0), Some(input_path), Output::File(output_path), cli);
+    }
+
+    eprintln!("  {} pages, writing one SVG per page", frames.len());
+    for (index, frame) in frames.into_iter().enumerate() {
+        let page_path = page_output_path(output_path, index + 1);
+        eprintln!("  page {} -> {}", index + 1, page_path.display());
+        process_image(frame, Some(input_path), Output::File(&page_path), cli)?;
+    }
+    Ok(())
+}
 
+/// `out.svg` → `out-2.svg` for the second page of a multi-page input.
+fn page_output_path(output_path: &Path, page: usize) -> PathBuf {
+    let stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = output_path.extension().unwrap_or_default().to_string_lossy();
+    output_path.with_file_name(format!("{}-{}.{}", stem, page, ext))
+}
+
+/// Vectorize decoded image data (read from `source`, if a file) and write the SVG.
+fn process_image(mut image_data: ImageData, source: Option<&Path>, output: Output, cli: &Cli) -> Result<()> {
     // Auto-resize large images to prevent OOM
     image_data = image_processor::resize_if_needed(image_data, cli.max_size);
 
     // Apply preprocessing if requested
     if cli.preprocess {
         eprintln!("  Applying edge-preserving smoothing and color reduction...");
-        let opts = PreprocessOptions::photo();
-        image_data = preprocess(&image_data, &opts)?;
+        image_data = preprocess(&image_data, &cli.preprocess_options())?;
     }
 
     // Provide hints for photographs
@@ -45,29 +89,60 @@ fn process_file(
         eprintln!("  Note: photo detected ({} colors). Try --preprocess for better results.", unique_colors);
     }
 
-    if cli.original {
-        eprintln!("  Using original pipeline (line segments, RDP simplification)...");
-        let vectorized_data = vectorizer::vectorize(
-            &image_data,
-            cli.colors,
-            cli.threshold,
-            cli.smooth,
-            cli.hierarchical,
-        )?;
-
-        if cli.advanced {
-            svg_generator::generate_svg_advanced(&vectorized_data, output_path)?;
+    if image_processor::has_cutout_transparency(&image_data) {
+        if cli.original {
+            eprintln!("  Note: image has transparent pixels; the original pipeline fills them.");
         } else {
-            svg_generator::generate_svg(&vectorized_data, output_path)?;
+            eprintln!("  Note: image has transparent pixels; leaving them out as holes.");
         }
-    } else {
-        let options = EnhancedOptions {
+    }
+
+    if cli.original {
+        eprintln!("  Using original pipeline (line segments, RDP simplification)...");
+        let options = ConversionOptions {
             num_colors: cli.colors,
-            preprocess: cli.preprocess || EnhancedOptions::default().preprocess,
+            threshold: cli.threshold,
+            smooth_level: cli.smooth,
+            hierarchical: cli.hierarchical,
+            advanced: cli.advanced,
+            decimal_precision: cli.enhanced_options().decimal_precision,
             ..Default::default()
         };
-        let vector_data = vectorize_enhanced(&image_data, &options)?;
-        write_enhanced_svg(&vector_data, output_path)?;
+        match output {
+            Output::File(path) => {
+                if is_svgz(path) {
+                    anyhow::bail!(".svgz output is only supported by the default pipeline");
+                }
+                std::fs::write(path, convert_to_svg_string(&image_data, &options)?)?;
+            }
+            Output::Stdout => write_stdout(&convert_to_svg_string(&image_data, &options)?)?,
+        }
+    } else {
+        let mut options = cli.enhanced_options();
+        options.source_path = source.map(Path::to_path_buf);
+        let vector_data = vectorize_enhanced_with_progress(&image_data, &options, &|event| {
+            if let ProgressEvent::RegionsFound(regions) = event {
+                eprintln!("  Fitting {} regions...", regions);
+            }
+        })?;
+        match output {
+            Output::File(path) => {
+                if is_svgz(path) {
+                    write_enhanced_svgz_with_options(&vector_data, path, &options)?;
+                } else {
+                    write_enhanced_svg_with_options(&vector_data, path, &options)?;
+                }
+                if options.color_separations {
+                    let dir = path.parent().unwrap_or(Path::new(""));
+                    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                    let plates = write_color_separations_with_options(&vector_data, dir, &stem, &options)?;
+                    eprintln!("  {} color separations written", plates.len());
+                }
+            }
+            Output::Stdout => {
+                write_stdout(&generate_enhanced_svg_with_options(&vector_data, &options))?
+            }
+        }
         eprintln!(
             "  {} paths, background #{:02x}{:02x}{:02x}",
             vector_data.paths.len(),
@@ -75,30 +150,85 @@ fn process_file(
             vector_data.background_color.1,
             vector_data.background_color.2,
         );
+        let stats = &vector_data.stats;
+        let discarded = stats.discarded_fraction(vector_data.width, vector_data.height);
+        if discarded > DISCARD_WARN_FRACTION {
+            eprintln!(
+                "  Warning: {} small regions ({:.0} px, {:.1}% of the image) were dropped as noise",
+                stats.discarded_regions,
+                stats.discarded_area,
+                discarded * 100.0,
+            );
+        }
+        if cli.stats {
+            println!("{}", serde_json::to_string(stats)?);
+        }
     }
 
     Ok(())
 }
 
+/// Whether the output path asks for gzip-compressed SVG.
+fn is_svgz(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("svgz"))
+}
+
+fn write_stdout(svg: &str) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(svg.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let cli = match Cli::parse_with_config() {
+        Ok(cli) => cli,
+        Err(msg) => {
+            eprintln!("error: {}", msg);
+            std::process::exit(2);
+        }
+    };
+    if let Err(msg) = cli.validate() {
+        eprintln!("error: {}", msg);
+        std::process::exit(2);
+    }
+    if let Some(threads) = cli.threads {
+        img2svg::set_thread_count(threads)?;
+    }
+    let colors = cli.colors.max(cli.enhanced_options().num_colors);
+    if colors > MAX_COLORS {
+        eprintln!("warning: {} colors requested, using the maximum of {}", colors, MAX_COLORS);
+    }
+
+    if cli.stdin {
+        let mut bytes = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut bytes)?;
+        let image_data = image_processor::load_image_from_bytes(&bytes)?;
+        let output = match &cli.output {
+            Some(path) if !cli.writes_stdout() => Output::File(path),
+            _ => Output::Stdout,
+        };
+        return process_image(image_data, None, output, &cli);
+    }
+
+    let input: PathBuf = cli.input.clone().expect("--input is required without --stdin");
 
-    if cli.input.is_dir() {
+    if input.is_dir() {
         // Batch mode: process all supported images in directory
-        let output_dir = cli.output.clone().unwrap_or_else(|| cli.input.clone());
+        let output_dir = cli.output.clone().unwrap_or_else(|| input.clone());
         if !output_dir.exists() {
             std::fs::create_dir_all(&output_dir)?;
         }
 
         let mut count = 0u32;
         let mut errors = 0u32;
-        let entries: Vec<_> = std::fs::read_dir(&cli.input)?
+        let entries: Vec<_> = std::fs::read_dir(&input)?
             .filter_map(|e| e.ok())
             .filter(|e| e.path().is_file() && is_supported_image(&e.path()))
             .collect();
 
         let total = entries.len();
-        eprintln!("Batch converting {} images from {}...", total, cli.input.display());
+        eprintln!("Batch converting {} images from {}...", total, input.display());
 
         for entry in &entries {
             let path = entry.path();
@@ -116,18 +246,21 @@ fn main() -> Result<()> {
             }
         }
 
-        println!("Batch complete: {} converted, {} errors.", count, errors);
+        eprintln!("Batch complete: {} converted, {} errors.", count, errors);
+    } else if cli.writes_stdout() {
+        let image_data = image_processor::load_image_scaled(&input, cli.max_size)?;
+        process_image(image_data, Some(&input), Output::Stdout, &cli)?;
     } else {
         // Single file mode
         let output_path = cli.output.clone().unwrap_or_else(|| {
-            let mut path = cli.input.clone();
+            let mut path = input.clone();
             path.set_extension("svg");
             path
         });
 
-        println!("Converting {} to {}...", cli.input.display(), output_path.display());
-        process_file(&cli.input, &output_path, &cli)?;
-        println!("Conversion complete!");
+        eprintln!("Converting {} to {}...", input.display(), output_path.display());
+        process_file(&input, &output_path, &cli)?;
+        eprintln!("Conversion complete!");
     }
 
     Ok(())
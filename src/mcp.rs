@@ -4,7 +4,7 @@
 //! through the Model Context Protocol, allowing AI assistants to convert images
 //! to SVG format.
 
-use img2svg::{convert, ConversionOptions};
+use img2svg::{convert, ConversionOptions, MAX_COLORS};
 use serde_json::{json, Value};
 use std::io::{self, BufRead, Write};
 use std::path::Path;
@@ -96,9 +96,9 @@ impl Img2SvgMcpServer {
                                 },
                                 "num_colors": {
                                     "type": "integer",
-                                    "description": "Number of colors for quantization (1-64, default: 16). More colors preserve more detail but increase file size.",
+                                    "description": format!("Number of colors for quantization (1-{}, default: 16). More colors preserve more detail but increase file size.", MAX_COLORS),
                                     "minimum": 1,
-                                    "maximum": 64,
+                                    "maximum": MAX_COLORS,
                                     "default": 16
                                 },
                                 "smooth_level": {
@@ -156,6 +156,7 @@ impl Img2SvgMcpServer {
                             threshold,
                             hierarchical: false,
                             advanced: false,
+                            ..Default::default()
                         };
 
                         match convert(Path::new(input), Path::new(output), &options) {
@@ -212,18 +213,18 @@ impl Img2SvgMcpServer {
         let mut stdout_lock = stdout.lock();
 
         for line in stdin.lock().lines() {
-            if let Ok(json_str) = line {
-                if let Ok(req) = serde_json::from_str::<McpRequest>(&json_str) {
-                    let response = match req.kind {
-                        RequestKind::Initialize { id, params } => self.handle_initialize(params, id),
-                        RequestKind::ToolsList { id } => self.handle_tools_list(id),
-                        RequestKind::ToolsCall { id, params } => self.handle_tools_call(params, id),
-                    };
+            if let Ok(json_str) = line
+                && let Ok(req) = serde_json::from_str::<McpRequest>(&json_str)
+            {
+                let response = match req.kind {
+                    RequestKind::Initialize { id, params } => self.handle_initialize(params, id),
+                    RequestKind::ToolsList { id } => self.handle_tools_list(id),
+                    RequestKind::ToolsCall { id, params } => self.handle_tools_call(params, id),
+                };
 
-                    if let Ok(response_json) = serde_json::to_string(&response) {
-                        writeln!(stdout_lock, "{}", response_json).ok();
-                        stdout_lock.flush().ok();
-                    }
+                if let Ok(response_json) = serde_json::to_string(&response) {
+                    writeln!(stdout_lock, "{}", response_json).ok();
+                    stdout_lock.flush().ok();
                 }
             }
         }
@@ -0,0 +1,377 @@
+//! Morphological operations on binary masks and quantized label images.
+//!
+//! Used to clean up the quantized image before contour tracing, e.g. closing
+//! single-pixel seams between neighboring regions so they tile exactly.
+
+/// Dilate a binary mask with a 3×3 square structuring element.
+pub fn dilate(mask: &[bool], width: usize, height: usize) -> Vec<bool> {
+    let mut out = vec![false; mask.len()];
+    for y in 0..height {
+        for x in 0..width {
+            if !mask[y * width + x] {
+                continue;
+            }
+            for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                    out[ny * width + nx] = true;
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Erode a binary mask with a 3×3 square structuring element.
+/// Pixels outside the image count as unset, so the border row/column is
+/// always cleared.
+pub fn erode(mask: &[bool], width: usize, height: usize) -> Vec<bool> {
+    let mut out = vec![false; mask.len()];
+    if width < 3 || height < 3 {
+        return out;
+    }
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            out[y * width + x] = (y - 1..y + 2)
+                .all(|ny| (x - 1..x + 2).all(|nx| mask[ny * width + nx]));
+        }
+    }
+    out
+}
+
+/// Morphological close (dilate then erode). Fills gaps and notches up to
+/// two pixels wide; pixels set in the input always stay set.
+pub fn close(mask: &[bool], width: usize, height: usize) -> Vec<bool> {
+    let closed = erode(&dilate(mask, width, height), width, height);
+    closed.iter().zip(mask).map(|(&c, &m)| c || m).collect()
+}
+
+/// Smooth a binary mask by 3×3 majority vote, `passes` times.
+///
+/// A pixel ends up set when more than half of its in-image 3×3 window is
+/// set (ties keep its value), which removes single-pixel jaggies and specks
+/// along edges while straight runs and broad shapes stay put.
+pub fn majority_smooth(mask: &[bool], width: usize, height: usize, passes: usize) -> Vec<bool> {
+    let mut current = mask.to_vec();
+    for _ in 0..passes {
+        let mut next = current.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let (mut set, mut total) = (0, 0);
+                for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                    for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                        set += current[ny * width + nx] as usize;
+                        total += 1;
+                    }
+                }
+                if 2 * set != total {
+                    next[y * width + x] = 2 * set > total;
+                }
+            }
+        }
+        if next == current {
+            break;
+        }
+        current = next;
+    }
+    current
+}
+
+/// Reassign every 4-connected run of one label smaller than `min_size`
+/// pixels to the neighboring label it is closest to under `distance`, so
+/// specks join a similar region instead of being dropped later.
+pub fn merge_small_regions<T: Copy + PartialEq>(
+    labels: &mut [T],
+    width: usize,
+    height: usize,
+    min_size: usize,
+    distance: impl Fn(T, T) -> i32,
+) {
+    let mut seen = vec![false; labels.len()];
+    let mut stack = Vec::new();
+    for start in 0..labels.len() {
+        if seen[start] {
+            continue;
+        }
+        let label = labels[start];
+        let mut component = Vec::new();
+        let mut neighbors: Vec<T> = Vec::new();
+        seen[start] = true;
+        stack.push(start);
+        while let Some(idx) = stack.pop() {
+            component.push(idx);
+            let (x, y) = (idx % width, idx / width);
+            let adjacent = [
+                (x > 0).then(|| idx - 1),
+                (x + 1 < width).then(|| idx + 1),
+                (y > 0).then(|| idx - width),
+                (y + 1 < height).then(|| idx + width),
+            ];
+            for n in adjacent.into_iter().flatten() {
+                if labels[n] != label {
+                    if !neighbors.contains(&labels[n]) {
+                        neighbors.push(labels[n]);
+                    }
+                } else if !seen[n] {
+                    seen[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+        if component.len() >= min_size {
+            continue;
+        }
+        // First seen wins ties
+        let mut best: Option<(T, i32)> = None;
+        for &n in &neighbors {
+            let d = distance(label, n);
+            if best.is_none_or(|(_, bd)| d < bd) {
+                best = Some((n, d));
+            }
+        }
+        if let Some((target, _)) = best {
+            for idx in component {
+                labels[idx] = target;
+            }
+        }
+    }
+}
+
+/// Heal thin seams of background between foreground regions.
+///
+/// Closes the union of all non-background labels, then hands each pixel the
+/// close added to the most common foreground label among its 8 neighbors.
+/// Afterwards neighboring regions share exact pixel boundaries, so their
+/// traced contours coincide instead of leaving background showing through.
+pub fn heal_gaps<T: Copy + PartialEq>(labels: &mut [T], width: usize, height: usize, background: T) {
+    let foreground: Vec<bool> = labels.iter().map(|&l| l != background).collect();
+    let closed = close(&foreground, width, height);
+
+    let mut fills = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let idx = y * width + x;
+            if foreground[idx] || !closed[idx] {
+                continue;
+            }
+            // Majority vote over foreground neighbors (first seen wins ties)
+            let mut counts: Vec<(T, usize)> = Vec::new();
+            for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                    let n = ny * width + nx;
+                    if !foreground[n] {
+                        continue;
+                    }
+                    match counts.iter_mut().find(|(label, _)| *label == labels[n]) {
+                        Some(entry) => entry.1 += 1,
+                        None => counts.push((labels[n], 1)),
+                    }
+                }
+            }
+            let mut best: Option<(T, usize)> = None;
+            for &(label, count) in &counts {
+                if best.is_none_or(|(_, c)| count > c) {
+                    best = Some((label, count));
+                }
+            }
+            if let Some((label, _)) = best {
+                fills.push((idx, label));
+            }
+        }
+    }
+
+    for (idx, label) in fills {
+        labels[idx] = label;
+    }
+}
+
+/// Thin a binary mask to a one-pixel-wide skeleton (Zhang–Suen).
+///
+/// Border pixels are peeled off in alternating passes until none can go
+/// without breaking connectivity or shortening a line end, so each stroke
+/// keeps its length and topology. Pixels outside the image count as unset.
+pub fn thin(mask: &[bool], width: usize, height: usize) -> Vec<bool> {
+    let mut current = mask.to_vec();
+    loop {
+        let mut changed = false;
+        for step in 0..2 {
+            let mut remove = Vec::new();
+            for y in 0..height {
+                for x in 0..width {
+                    if !current[y * width + x] {
+                        continue;
+                    }
+                    let at = |dx: isize, dy: isize| {
+                        let (nx, ny) = (x as isize + dx, y as isize + dy);
+                        nx >= 0
+                            && ny >= 0
+                            && (nx as usize) < width
+                            && (ny as usize) < height
+                            && current[ny as usize * width + nx as usize]
+                    };
+                    // Neighbors clockwise from north
+                    let n = [at(0, -1), at(1, -1), at(1, 0), at(1, 1), at(0, 1), at(-1, 1), at(-1, 0), at(-1, -1)];
+                    let set = n.iter().filter(|&&v| v).count();
+                    let transitions = (0..8).filter(|&i| !n[i] && n[(i + 1) % 8]).count();
+                    let (north, east, south, west) = (n[0], n[2], n[4], n[6]);
+                    let keeps_corner = if step == 0 {
+                        !(east && south && (north || west))
+                    } else {
+                        !(north && west && (east || south))
+                    };
+                    if (2..=6).contains(&set) && transitions == 1 && keeps_corner {
+                        remove.push(y * width + x);
+                    }
+                }
+            }
+            changed |= !remove.is_empty();
+            for idx in remove {
+                current[idx] = false;
+            }
+        }
+        if !changed {
+            return current;
+        }
+    }
+}
+
+/// 4-connected distance from each set pixel to the nearest unset one:
+/// 1 along the edge of a region, growing inward; 0 for unset pixels.
+/// Pixels outside the image count as unset.
+pub fn distance_to_unset(mask: &[bool], width: usize, height: usize) -> Vec<u32> {
+    let mut distance = vec![0u32; mask.len()];
+    let mut queue = std::collections::VecDeque::new();
+    for y in 0..height {
+        for x in 0..width {
+            let idx = y * width + x;
+            let on_edge = x == 0
+                || y == 0
+                || x + 1 == width
+                || y + 1 == height
+                || !mask[idx - 1]
+                || !mask[idx + 1]
+                || !mask[idx - width]
+                || !mask[idx + width];
+            if mask[idx] && on_edge {
+                distance[idx] = 1;
+                queue.push_back(idx);
+            }
+        }
+    }
+    while let Some(idx) = queue.pop_front() {
+        let (x, y) = (idx % width, idx / width);
+        let next = distance[idx] + 1;
+        for (nx, ny) in [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)] {
+            if nx < width && ny < height {
+                let n = ny * width + nx;
+                if mask[n] && distance[n] == 0 {
+                    distance[n] = next;
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vectorizer::marching_squares_contours;
+
+    #[test]
+    fn test_dilate_single_pixel() {
+        let mut mask = vec![false; 25];
+        mask[12] = true;
+        let dilated = dilate(&mask, 5, 5);
+        assert_eq!(dilated.iter().filter(|&&v| v).count(), 9);
+    }
+
+    #[test]
+    fn test_erode_removes_isolated_pixel() {
+        let mut mask = vec![false; 25];
+        mask[12] = true;
+        let eroded = erode(&mask, 5, 5);
+        assert!(eroded.iter().all(|&v| !v));
+    }
+
+    #[test]
+    fn test_close_fills_one_pixel_gap() {
+        // Two 2px-wide bars separated by a 1px column
+        let (w, h) = (7, 5);
+        let mask: Vec<bool> = (0..w * h).map(|i| i % w != 3).collect();
+        let closed = close(&mask, w, h);
+        assert!((1..h - 1).all(|y| closed[y * w + 3]));
+    }
+
+    #[test]
+    fn test_heal_gaps_closes_seam_between_regions() {
+        // 0 = background, 1 and 2 are regions split by a 1px background seam
+        let (w, h) = (12, 8);
+        let mut labels = vec![0u8; w * h];
+        for y in 1..7 {
+            for x in 1..6 {
+                labels[y * w + x] = 1;
+            }
+            for x in 7..11 {
+                labels[y * w + x] = 2;
+            }
+        }
+        heal_gaps(&mut labels, w, h, 0);
+
+        for y in 1..7 {
+            assert_ne!(labels[y * w + 6], 0, "seam pixel at row {} left uncovered", y);
+        }
+        // Outer background border is untouched
+        assert!((0..w).all(|x| labels[x] == 0));
+        assert!((0..h).all(|y| labels[y * w] == 0));
+    }
+
+    #[test]
+    fn test_majority_smooth_straightens_jagged_edge() {
+        // A block whose top edge alternates up and down by a pixel
+        let (w, h) = (24, 16);
+        let mask: Vec<bool> = (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                let top = if x % 2 == 0 { 4 } else { 5 };
+                (3..21).contains(&x) && y >= top && y < 13
+            })
+            .collect();
+        let points = |mask: &[bool]| -> usize {
+            marching_squares_contours(mask, w, h).iter().map(|c| c.len()).sum()
+        };
+
+        let smoothed = majority_smooth(&mask, w, h, 1);
+        assert!(points(&smoothed) < points(&mask), "{} vs {}", points(&smoothed), points(&mask));
+        assert_eq!(marching_squares_contours(&smoothed, w, h).len(), 1);
+        assert_eq!(majority_smooth(&mask, w, h, 0), mask);
+    }
+
+    #[test]
+    fn test_merge_small_regions_joins_most_similar_neighbor() {
+        // Labels are gray levels: a 2px speck of 90 sits on the border
+        // between a 100 region and a 200 region
+        let (w, h) = (8, 4);
+        let mut labels: Vec<i32> = (0..w * h).map(|i| if i % w < 4 { 100 } else { 200 }).collect();
+        labels[w + 3] = 90;
+        labels[w + 4] = 90;
+        merge_small_regions(&mut labels, w, h, 4, |a, b| (a - b).abs());
+        assert!(!labels.contains(&90));
+        assert_eq!(labels[w + 4], 100);
+        assert_eq!(labels.iter().filter(|&&l| l == 100).count(), 17);
+    }
+
+    #[test]
+    fn test_thin_reduces_bar_to_one_pixel_line() {
+        let (w, h) = (20, 9);
+        let mask: Vec<bool> = (0..w * h).map(|i| (2..18).contains(&(i % w)) && (3..6).contains(&(i / w))).collect();
+        let skeleton = thin(&mask, w, h);
+        let rows: Vec<usize> = (0..h).filter(|&y| (0..w).any(|x| skeleton[y * w + x])).collect();
+        assert_eq!(rows, vec![4]);
+        let run = (0..w).filter(|&x| skeleton[4 * w + x]).count();
+        assert!(run >= 12, "skeleton too short: {}", run);
+
+        let distance = distance_to_unset(&mask, w, h);
+        assert_eq!((distance[3 * w + 8], distance[4 * w + 8], distance[0]), (1, 2, 0));
+    }
+}
@@ -149,17 +149,17 @@ pub fn visvalingam_whyatt(
                 let next = find_next_alive(&alive, idx);
 
                 if let (Some(p), Some(nx)) = (prev, next) {
-                    if let Some(pp) = find_prev_alive(&alive, p) {
-                        if !corner_set.contains(&p) {
-                            areas[p] = triangle_area(&points[pp], &points[p], &points[nx])
-                                .max(min_val);
-                        }
+                    if let Some(pp) = find_prev_alive(&alive, p)
+                        && !corner_set.contains(&p)
+                    {
+                        areas[p] = triangle_area(&points[pp], &points[p], &points[nx])
+                            .max(min_val);
                     }
-                    if let Some(nn) = find_next_alive(&alive, nx) {
-                        if !corner_set.contains(&nx) {
-                            areas[nx] = triangle_area(&points[p], &points[nx], &points[nn])
-                                .max(min_val);
-                        }
+                    if let Some(nn) = find_next_alive(&alive, nx)
+                        && !corner_set.contains(&nx)
+                    {
+                        areas[nx] = triangle_area(&points[p], &points[nx], &points[nn])
+                            .max(min_val);
                     }
                 }
             }
@@ -175,6 +175,79 @@ pub fn visvalingam_whyatt(
         .collect()
 }
 
+/// [`visvalingam_whyatt`] for closed contours: the point list is treated as
+/// a cycle, so no point is protected merely for being first or last. The
+/// output starts at the sharpest turn, keeping the seam where a Bézier fit
+/// places its endpoint on a natural corner.
+pub fn visvalingam_whyatt_closed(
+    points: &[Point],
+    min_area: f64,
+    corner_indices: &[usize],
+) -> Vec<Point> {
+    let n = points.len();
+    if n <= 3 {
+        return points.to_vec();
+    }
+
+    let start = sharpest_turn(points);
+    let points: Vec<Point> = points[start..].iter().chain(&points[..start]).cloned().collect();
+    let corner_set: HashSet<usize> = corner_indices.iter().map(|&i| (i + n - start) % n).collect();
+
+    let mut prev: Vec<usize> = (0..n).map(|i| (i + n - 1) % n).collect();
+    let mut next: Vec<usize> = (0..n).map(|i| (i + 1) % n).collect();
+    let area = |i: usize, prev: &[usize], next: &[usize]| {
+        if corner_set.contains(&i) {
+            f64::MAX
+        } else {
+            triangle_area(&points[prev[i]], &points[i], &points[next[i]])
+        }
+    };
+    let mut areas: Vec<f64> = (0..n).map(|i| area(i, &prev, &next)).collect();
+    let mut alive: Vec<bool> = vec![true; n];
+    let mut alive_count = n;
+
+    while alive_count > 3 {
+        let Some((idx, min_val)) = (0..n)
+            .filter(|&i| alive[i])
+            .map(|i| (i, areas[i]))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+        else {
+            break;
+        };
+        if min_val >= min_area {
+            break;
+        }
+        alive[idx] = false;
+        alive_count -= 1;
+        let (p, nx) = (prev[idx], next[idx]);
+        next[p] = nx;
+        prev[nx] = p;
+        for i in [p, nx] {
+            if !corner_set.contains(&i) {
+                areas[i] = area(i, &prev, &next).max(min_val);
+            }
+        }
+    }
+
+    points
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| alive[*i])
+        .map(|(_, p)| p)
+        .collect()
+}
+
+/// Index of the point where a closed polyline turns most sharply.
+fn sharpest_turn(points: &[Point]) -> usize {
+    let n = points.len();
+    (0..n)
+        .min_by(|&a, &b| {
+            let angle = |i: usize| calculate_angle(&points[(i + n - 1) % n], &points[i], &points[(i + 1) % n]);
+            angle(a).total_cmp(&angle(b))
+        })
+        .unwrap_or(0)
+}
+
 /// Gaussian-weighted smoothing that preserves corners.
 pub fn smooth_with_corners(points: &[Point], window_size: usize, corner_threshold_deg: f64) -> Vec<Point> {
     if points.len() <= window_size {
@@ -211,11 +284,11 @@ pub fn smooth_with_corners(points: &[Point], window_size: usize, corner_threshol
         let mut sum_y = 0.0;
         let mut sum_w = 0.0;
 
-        for j in start..end {
+        for (j, p) in points.iter().enumerate().take(end).skip(start) {
             let wi = (j as isize - i as isize + half as isize) as usize;
             let w = if wi < weights.len() { weights[wi] } else { 0.0 };
-            sum_x += points[j].x * w;
-            sum_y += points[j].y * w;
+            sum_x += p.x * w;
+            sum_y += p.y * w;
             sum_w += w;
         }
 
@@ -229,6 +302,118 @@ pub fn smooth_with_corners(points: &[Point], window_size: usize, corner_threshol
     smoothed
 }
 
+/// Resample a polyline at (close to) equal arc-length spacing.
+///
+/// The segment count is rounded so the spacing divides the total length
+/// exactly; for closed paths the closing segment counts too. Corners that
+/// fall between samples are cut by up to half the spacing.
+pub fn resample_even(points: &[Point], spacing: f64, closed: bool) -> Vec<Point> {
+    if points.len() < 2 || !spacing.is_finite() || spacing <= 0.0 {
+        return points.to_vec();
+    }
+    let mut path = points.to_vec();
+    if closed {
+        path.push(points[0].clone());
+    }
+    let seg_len = |a: &Point, b: &Point| ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt();
+    let total: f64 = path.windows(2).map(|w| seg_len(&w[0], &w[1])).sum();
+    if total == 0.0 {
+        return points.to_vec();
+    }
+
+    let min_segments = if closed { 3 } else { 1 };
+    let segments = ((total / spacing).round() as usize).max(min_segments);
+    let step = total / segments as f64;
+    let samples = if closed { segments } else { segments + 1 };
+
+    let mut out = Vec::with_capacity(samples);
+    let mut seg = 0;
+    let mut seg_start = 0.0;
+    for i in 0..samples {
+        let target = (i as f64 * step).min(total);
+        while seg + 2 < path.len() && seg_start + seg_len(&path[seg], &path[seg + 1]) < target {
+            seg_start += seg_len(&path[seg], &path[seg + 1]);
+            seg += 1;
+        }
+        let (a, b) = (&path[seg], &path[seg + 1]);
+        let len = seg_len(a, b);
+        let t = if len > 0.0 { ((target - seg_start) / len).clamp(0.0, 1.0) } else { 0.0 };
+        out.push(Point { x: a.x + (b.x - a.x) * t, y: a.y + (b.y - a.y) * t });
+    }
+    out
+}
+
+/// Make near-horizontal and near-vertical segments exactly axis-aligned.
+///
+/// A segment within `tolerance_deg` of an axis counts as aligned. Each run
+/// of consecutive horizontal segments gets its points' mean y, and each run
+/// of vertical segments its points' mean x, so corners where the two meet
+/// land on both lines.
+pub fn snap_axis_aligned(points: &[Point], tolerance_deg: f64, closed: bool) -> Vec<Point> {
+    let n = points.len();
+    if n < 2 || !(tolerance_deg > 0.0 && tolerance_deg < 45.0) {
+        return points.to_vec();
+    }
+    let seg_count = if closed { n } else { n - 1 };
+    let slope = tolerance_deg.to_radians().tan();
+    // Some(true) = horizontal, Some(false) = vertical
+    let axis: Vec<Option<bool>> = (0..seg_count)
+        .map(|s| {
+            let (a, b) = (&points[s], &points[(s + 1) % n]);
+            let (dx, dy) = ((b.x - a.x).abs(), (b.y - a.y).abs());
+            if dx == 0.0 && dy == 0.0 {
+                None
+            } else if dy <= dx * slope {
+                Some(true)
+            } else if dx <= dy * slope {
+                Some(false)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let mut out = points.to_vec();
+    for horizontal in [true, false] {
+        let aligned = |s: usize| axis[s] == Some(horizontal);
+        // A closed ring made of one axis only has no corner to start from
+        if closed && (0..seg_count).all(aligned) {
+            continue;
+        }
+        // Start right after a non-aligned segment so no run straddles the start
+        let start = if closed {
+            (0..seg_count).find(|&s| !aligned((s + seg_count - 1) % seg_count)).unwrap_or(0)
+        } else {
+            0
+        };
+
+        let mut run: Vec<usize> = Vec::new();
+        for k in 0..=seg_count {
+            let s = (start + k) % seg_count;
+            if k < seg_count && aligned(s) {
+                if run.is_empty() {
+                    run.push(s);
+                }
+                run.push((s + 1) % n);
+                continue;
+            }
+            if !run.is_empty() {
+                let coord = |p: &Point| if horizontal { p.y } else { p.x };
+                let mean = run.iter().map(|&i| coord(&points[i])).sum::<f64>() / run.len() as f64;
+                for &i in &run {
+                    if horizontal {
+                        out[i].y = mean;
+                    } else {
+                        out[i].x = mean;
+                    }
+                }
+                run.clear();
+            }
+        }
+    }
+    out
+}
+
 #[inline]
 fn triangle_area(p1: &Point, p2: &Point, p3: &Point) -> f64 {
     ((p1.x * (p2.y - p3.y) + p2.x * (p3.y - p1.y) + p3.x * (p1.y - p2.y)) / 2.0).abs()
@@ -294,6 +479,33 @@ mod tests {
         assert!(simplified.len() < points.len());
     }
 
+    #[test]
+    fn test_visvalingam_closed_has_no_seam_kink() {
+        let n = 120;
+        let circle: Vec<Point> = (0..n)
+            .map(|i| {
+                let t = i as f64 / n as f64 * std::f64::consts::TAU;
+                Point { x: 50.0 + 30.0 * t.cos(), y: 50.0 + 30.0 * t.sin() }
+            })
+            .collect();
+        // Turning angle at each vertex of the result, as a closed polygon
+        let turns = |pts: &[Point]| -> Vec<f64> {
+            let m = pts.len();
+            (0..m)
+                .map(|i| std::f64::consts::PI - calculate_angle(&pts[(i + m - 1) % m], &pts[i], &pts[(i + 1) % m]))
+                .collect()
+        };
+
+        let closed = visvalingam_whyatt_closed(&circle, 2.0, &[]);
+        assert!(closed.len() < n / 2, "kept {} points", closed.len());
+        let t = turns(&closed);
+        let mean = t.iter().sum::<f64>() / t.len() as f64;
+        let (first, last) = (t[0], t[t.len() - 1]);
+        for seam in [first, last] {
+            assert!((seam - mean).abs() < 0.5 * mean, "seam turn {} vs mean {}", seam, mean);
+        }
+    }
+
     #[test]
     fn test_visvalingam_small_input() {
         let points = vec![
@@ -331,4 +543,63 @@ mod tests {
         );
         assert!((area - 6.0).abs() < 0.01);
     }
+
+    #[test]
+    fn test_resample_even_uniform_spacing() {
+        // Square outline with dense points on one side and sparse on the others
+        let mut points: Vec<Point> = (0..40).map(|i| Point { x: i as f64 * 0.25, y: 0.0 }).collect();
+        points.push(Point { x: 10.0, y: 0.0 });
+        points.push(Point { x: 10.0, y: 10.0 });
+        points.push(Point { x: 0.0, y: 10.0 });
+
+        let resampled = resample_even(&points, 1.0, true);
+        assert_eq!(resampled.len(), 40);
+        let n = resampled.len();
+        let spacings: Vec<f64> = (0..n)
+            .map(|i| {
+                let (a, b) = (&resampled[i], &resampled[(i + 1) % n]);
+                ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt()
+            })
+            .collect();
+        let mean = spacings.iter().sum::<f64>() / n as f64;
+        let variance = spacings.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n as f64;
+        assert!((mean - 1.0).abs() < 0.05, "mean spacing {}", mean);
+        assert!(variance < 0.01, "spacing variance {}", variance);
+    }
+
+    #[test]
+    fn test_snap_axis_aligned_slanted_rectangle() {
+        let points = vec![
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 10.0, y: 0.3 },
+            Point { x: 10.2, y: 8.0 },
+            Point { x: 0.1, y: 8.2 },
+        ];
+        let snapped = snap_axis_aligned(&points, 5.0, true);
+        for i in 0..4 {
+            let (a, b) = (&snapped[i], &snapped[(i + 1) % 4]);
+            assert!(a.x == b.x || a.y == b.y, "edge {} not axis-aligned: {:?} -> {:?}", i, a, b);
+        }
+        assert!((snapped[0].y - 0.15).abs() < 1e-12);
+        assert!((snapped[1].x - 10.1).abs() < 1e-12);
+
+        // A tolerance below the slant leaves the shape alone
+        assert_eq!(snap_axis_aligned(&points, 1.0, true)[1].y, 0.3);
+    }
+
+    #[test]
+    fn test_snap_axis_aligned_keeps_diagonals() {
+        let points = vec![
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 5.0, y: 5.0 },
+            Point { x: 10.0, y: 5.1 },
+            Point { x: 15.0, y: 4.9 },
+        ];
+        let snapped = snap_axis_aligned(&points, 3.0, false);
+        assert_eq!((snapped[0].x, snapped[0].y), (0.0, 0.0));
+        assert_eq!(snapped[1].x, 5.0);
+        // The two near-horizontal segments share one line
+        assert!(snapped[1].y == snapped[2].y && snapped[2].y == snapped[3].y);
+        assert!((snapped[3].y - 5.0).abs() < 1e-12);
+    }
 }
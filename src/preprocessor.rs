@@ -6,9 +6,11 @@
 use crate::image_processor::ImageData;
 use rgb::RGBA8;
 use anyhow::Result;
+use serde::Deserialize;
 
 /// Preprocessing options
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct PreprocessOptions {
     /// Color reduction level (0-1, 0 = none, 1 = maximum reduction)
     pub color_reduction: f32,
@@ -99,10 +101,10 @@ fn bilateral_filter(
     let lut_size: usize = 256;
     let bin_scale = 195075.0 / lut_size as f64;
     let mut range_lut = vec![0u32; lut_size];
-    for i in 0..lut_size {
+    for (i, slot) in range_lut.iter_mut().enumerate() {
         let dist = i as f64 * bin_scale;
         let weight = (-dist / range_denom).exp();
-        range_lut[i] = (weight * 1024.0) as u32; // fixed-point 10-bit
+        *slot = (weight * 1024.0) as u32; // fixed-point 10-bit
     }
 
     let mut output = vec![RGBA8::new(0, 0, 0, 255); pixels.len()];
@@ -143,7 +145,7 @@ fn bilateral_filter(
                 }
             }
 
-            if sum_w > 0 {
+            if let Some(sum_w) = std::num::NonZeroU64::new(sum_w) {
                 output[ci] = RGBA8::new(
                     (sum_r / sum_w) as u8,
                     (sum_g / sum_w) as u8,
@@ -159,6 +161,55 @@ fn bilateral_filter(
     output
 }
 
+/// Composite every pixel over an opaque background, producing a fully
+/// opaque image.
+///
+/// Blending happens on the stored sRGB values, which is what browsers do
+/// when painting a semi-transparent fill over the background rect, so the
+/// flattened colors match how the source image displays. A translucent
+/// `background` is itself composited over white first.
+pub fn flatten_alpha(image_data: &ImageData, background: RGBA8) -> ImageData {
+    let blend = |fg: u8, bg: u8, a: u32| ((fg as u32 * a + bg as u32 * (255 - a) + 127) / 255) as u8;
+    let ba = background.a as u32;
+    let bg = RGBA8::new(
+        blend(background.r, 255, ba),
+        blend(background.g, 255, ba),
+        blend(background.b, 255, ba),
+        255,
+    );
+
+    let pixels = image_data
+        .pixels
+        .iter()
+        .map(|p| {
+            let a = p.a as u32;
+            RGBA8::new(blend(p.r, bg.r, a), blend(p.g, bg.g, a), blend(p.b, bg.b, a), 255)
+        })
+        .collect();
+
+    ImageData {
+        width: image_data.width,
+        height: image_data.height,
+        pixels,
+    }
+}
+
+/// Binarize alpha: pixels with alpha below `cutoff` become fully transparent,
+/// all others fully opaque. Colors are kept either way.
+pub fn binarize_alpha(image_data: &ImageData, cutoff: u8) -> ImageData {
+    let pixels = image_data
+        .pixels
+        .iter()
+        .map(|p| RGBA8::new(p.r, p.g, p.b, if p.a < cutoff { 0 } else { 255 }))
+        .collect();
+
+    ImageData {
+        width: image_data.width,
+        height: image_data.height,
+        pixels,
+    }
+}
+
 /// Reduce colors through posterization
 fn reduce_colors(pixels: &[RGBA8], reduction: f32) -> Vec<RGBA8> {
     // Calculate number of color levels (256 -> 2-256 based on reduction)
@@ -252,6 +303,23 @@ mod tests {
         assert_eq!(result[2].a, 0);
     }
 
+    #[test]
+    fn test_flatten_alpha_over_white() {
+        let img = ImageData {
+            width: 3,
+            height: 1,
+            pixels: vec![
+                RGBA8::new(255, 0, 0, 128),
+                RGBA8::new(0, 0, 255, 255),
+                RGBA8::new(0, 0, 0, 0),
+            ],
+        };
+        let flat = flatten_alpha(&img, RGBA8::new(255, 255, 255, 255));
+        assert_eq!(flat.pixels[0], RGBA8::new(255, 127, 127, 255));
+        assert_eq!(flat.pixels[1], RGBA8::new(0, 0, 255, 255));
+        assert_eq!(flat.pixels[2], RGBA8::new(255, 255, 255, 255));
+    }
+
     #[test]
     fn test_bilateral_filter_uniform() {
         // All same color - should remain same
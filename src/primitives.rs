@@ -0,0 +1,291 @@
+//! Shapes SVG can draw as `<circle>`, `<ellipse>` or `<rect>` elements
+//! instead of fitted curves, and recognition of the round ones.
+
+use crate::bezier_fitter::{BezierCurve, PathFormat};
+use crate::vectorizer::Point;
+
+/// Largest distance (px) a contour point may lie from the fitted shape.
+pub const PRIMITIVE_TOLERANCE: f64 = 1.0;
+
+/// Handle length, as a share of the radius, of a cubic quarter-circle arc.
+const KAPPA: f64 = 0.552_284_749_831;
+
+/// A shape SVG has its own element for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Primitive {
+    Circle { cx: f64, cy: f64, r: f64 },
+    /// Axis-aligned ellipse
+    Ellipse { cx: f64, cy: f64, rx: f64, ry: f64 },
+    /// Axis-aligned rectangle from its top-left corner
+    Rect { x: f64, y: f64, width: f64, height: f64 },
+}
+
+impl Primitive {
+    /// SVG element name.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Primitive::Circle { .. } => "circle",
+            Primitive::Ellipse { .. } => "ellipse",
+            Primitive::Rect { .. } => "rect",
+        }
+    }
+
+    /// Geometry attributes, e.g. `cx="50" cy="50" r="40"`.
+    pub fn attributes(&self, format: &PathFormat) -> String {
+        match *self {
+            Primitive::Circle { cx, cy, r } => {
+                format!("cx=\"{}\" cy=\"{}\" r=\"{}\"", format.num(cx), format.num(cy), format.num(r))
+            }
+            Primitive::Ellipse { cx, cy, rx, ry } => format!(
+                "cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\"",
+                format.num(cx),
+                format.num(cy),
+                format.num(rx),
+                format.num(ry)
+            ),
+            Primitive::Rect { x, y, width, height } => format!(
+                "x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+                format.num(x),
+                format.num(y),
+                format.num(width),
+                format.num(height)
+            ),
+        }
+    }
+
+    /// The element with no presentation attributes, e.g.
+    /// `<circle cx="50" cy="50" r="40"/>`.
+    pub fn to_svg(&self, format: &PathFormat) -> String {
+        format!("<{} {}/>", self.tag(), self.attributes(format))
+    }
+
+    /// Center and half extents on each axis.
+    fn center_and_radii(&self) -> (f64, f64, f64, f64) {
+        match *self {
+            Primitive::Circle { cx, cy, r } => (cx, cy, r, r),
+            Primitive::Ellipse { cx, cy, rx, ry } => (cx, cy, rx, ry),
+            Primitive::Rect { x, y, width, height } => (x + width / 2.0, y + height / 2.0, width / 2.0, height / 2.0),
+        }
+    }
+
+    /// The same kind of shape with a new center and half extents; a round
+    /// shape becomes a circle when they match.
+    fn with_center_and_radii(&self, cx: f64, cy: f64, rx: f64, ry: f64) -> Primitive {
+        match self {
+            Primitive::Rect { .. } => Primitive::Rect { x: cx - rx, y: cy - ry, width: 2.0 * rx, height: 2.0 * ry },
+            _ if rx == ry => Primitive::Circle { cx, cy, r: rx },
+            _ => Primitive::Ellipse { cx, cy, rx, ry },
+        }
+    }
+
+    /// Curves tracing the shape clockwise on screen, for when it has to
+    /// join other subpaths in one `d`: four cubic arcs, or four lines for
+    /// a rectangle.
+    pub fn to_curves(&self) -> Vec<BezierCurve> {
+        if let Primitive::Rect { x, y, width, height } = *self {
+            let corners = [(x, y), (x + width, y), (x + width, y + height), (x, y + height)];
+            return (0..4)
+                .map(|k| {
+                    let ((x0, y0), (x1, y1)) = (corners[k], corners[(k + 1) % 4]);
+                    let at = |t: f64| Point { x: x0 + (x1 - x0) * t, y: y0 + (y1 - y0) * t };
+                    BezierCurve { start: at(0.0), control1: at(1.0 / 3.0), control2: at(2.0 / 3.0), end: at(1.0) }
+                })
+                .collect();
+        }
+        let (cx, cy, rx, ry) = self.center_and_radii();
+        // Quarter points, clockwise with y pointing down: right, bottom, left, top
+        let quarters = [(1.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (0.0, -1.0)];
+        (0..4)
+            .map(|k| {
+                let (ux, uy) = quarters[k];
+                let (vx, vy) = quarters[(k + 1) % 4];
+                let at = |x: f64, y: f64| Point { x: cx + x * rx, y: cy + y * ry };
+                BezierCurve {
+                    start: at(ux, uy),
+                    control1: at(ux + KAPPA * vx, uy + KAPPA * vy),
+                    control2: at(vx + KAPPA * ux, vy + KAPPA * uy),
+                    end: at(vx, vy),
+                }
+            })
+            .collect()
+    }
+
+    /// The shape stretched by `sx` horizontally and `sy` vertically.
+    pub fn scaled(&self, sx: f64, sy: f64) -> Primitive {
+        let (cx, cy, rx, ry) = self.center_and_radii();
+        self.with_center_and_radii(cx * sx, cy * sy, rx * sx, ry * sy)
+    }
+
+    /// Every number written out passed through `f`.
+    pub fn map(&self, f: impl Fn(f64) -> f64) -> Primitive {
+        match *self {
+            Primitive::Circle { cx, cy, r } => Primitive::Circle { cx: f(cx), cy: f(cy), r: f(r) },
+            Primitive::Ellipse { cx, cy, rx, ry } => {
+                self.with_center_and_radii(f(cx), f(cy), f(rx), f(ry))
+            }
+            Primitive::Rect { x, y, width, height } => {
+                Primitive::Rect { x: f(x), y: f(y), width: f(width), height: f(height) }
+            }
+        }
+    }
+}
+
+/// Least-squares circle, or failing that axis-aligned ellipse, through the
+/// points of a closed contour, if no point strays more than `tolerance` from
+/// it. Contours with fewer than eight points or a radius under 2 px are
+/// never primitives.
+pub fn fit_primitive(points: &[Point], tolerance: f64) -> Option<Primitive> {
+    if points.len() < 8 {
+        return None;
+    }
+    // Work relative to the centroid for numerical stability
+    let n = points.len() as f64;
+    let mx = points.iter().map(|p| p.x).sum::<f64>() / n;
+    let my = points.iter().map(|p| p.y).sum::<f64>() / n;
+    let local: Vec<(f64, f64)> = points.iter().map(|p| (p.x - mx, p.y - my)).collect();
+
+    if let Some((cx, cy, r)) = fit_circle(&local)
+        && r >= 2.0
+        && local.iter().all(|&(x, y)| ((x - cx).hypot(y - cy) - r).abs() <= tolerance)
+    {
+        return Some(Primitive::Circle { cx: cx + mx, cy: cy + my, r });
+    }
+
+    let (cx, cy, rx, ry) = fit_ellipse(&local)?;
+    if rx.min(ry) < 2.0 {
+        return None;
+    }
+    let fits = local.iter().all(|&(x, y)| {
+        let (dx, dy) = (x - cx, y - cy);
+        let rho = ((dx / rx).powi(2) + (dy / ry).powi(2)).sqrt();
+        let d = dx.hypot(dy);
+        // The boundary along this direction lies at d / rho from the center
+        rho > 0.0 && (d - d / rho).abs() <= tolerance
+    });
+    fits.then_some(Primitive::Ellipse { cx: cx + mx, cy: cy + my, rx, ry })
+}
+
+/// Kåsa fit: least squares of `x² + y² + Dx + Ey + F` over the points.
+fn fit_circle(points: &[(f64, f64)]) -> Option<(f64, f64, f64)> {
+    let mut normal = [[0.0; 4]; 3];
+    for &(x, y) in points {
+        let row = [x, y, 1.0];
+        let rhs = -(x * x + y * y);
+        for i in 0..3 {
+            for j in 0..3 {
+                normal[i][j] += row[i] * row[j];
+            }
+            normal[i][3] += row[i] * rhs;
+        }
+    }
+    let [d, e, f] = solve(normal)?;
+    let (cx, cy) = (-d / 2.0, -e / 2.0);
+    let r2 = cx * cx + cy * cy - f;
+    (r2 > 0.0).then(|| (cx, cy, r2.sqrt()))
+}
+
+/// Least squares of `Ax² + Cy² + Dx + Ey = 1` over the points.
+fn fit_ellipse(points: &[(f64, f64)]) -> Option<(f64, f64, f64, f64)> {
+    let mut normal = [[0.0; 5]; 4];
+    for &(x, y) in points {
+        let row = [x * x, y * y, x, y];
+        for i in 0..4 {
+            for j in 0..4 {
+                normal[i][j] += row[i] * row[j];
+            }
+            normal[i][4] += row[i];
+        }
+    }
+    let [a, c, d, e] = solve(normal)?;
+    if a <= 0.0 || c <= 0.0 {
+        return None;
+    }
+    let (cx, cy) = (-d / (2.0 * a), -e / (2.0 * c));
+    let g = 1.0 + a * cx * cx + c * cy * cy;
+    Some((cx, cy, (g / a).sqrt(), (g / c).sqrt()))
+}
+
+/// Solve the `N`x`N` system in the augmented matrix `m` (last column the
+/// right-hand side) by Gaussian elimination with partial pivoting.
+fn solve<const N: usize, const M: usize>(mut m: [[f64; M]; N]) -> Option<[f64; N]> {
+    for col in 0..N {
+        let pivot = (col..N).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+        if m[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        m.swap(col, pivot);
+        for row in col + 1..N {
+            let factor = m[row][col] / m[col][col];
+            let pivot_row = m[col];
+            for (v, p) in m[row].iter_mut().zip(pivot_row).skip(col) {
+                *v -= factor * p;
+            }
+        }
+    }
+    let mut x = [0.0; N];
+    for row in (0..N).rev() {
+        let tail: f64 = (row + 1..N).map(|k| m[row][k] * x[k]).sum();
+        x[row] = (m[row][N] - tail) / m[row][row];
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(cx: f64, cy: f64, rx: f64, ry: f64, n: usize) -> Vec<Point> {
+        (0..n)
+            .map(|i| {
+                let t = i as f64 / n as f64 * std::f64::consts::TAU;
+                Point { x: cx + rx * t.cos(), y: cy + ry * t.sin() }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fit_primitive_shapes() {
+        match fit_primitive(&ring(50.0, 40.0, 20.0, 20.0, 32), PRIMITIVE_TOLERANCE) {
+            Some(Primitive::Circle { cx, cy, r }) => {
+                assert!((cx - 50.0).abs() < 1e-6 && (cy - 40.0).abs() < 1e-6 && (r - 20.0).abs() < 1e-6);
+            }
+            other => panic!("expected a circle, got {:?}", other),
+        }
+        match fit_primitive(&ring(50.0, 40.0, 30.0, 12.0, 48), PRIMITIVE_TOLERANCE) {
+            Some(Primitive::Ellipse { rx, ry, .. }) => {
+                assert!((rx - 30.0).abs() < 1e-6 && (ry - 12.0).abs() < 1e-6);
+            }
+            other => panic!("expected an ellipse, got {:?}", other),
+        }
+        let square: Vec<Point> = (0..40)
+            .map(|i| {
+                let s = (i % 10) as f64 * 4.0;
+                match i / 10 {
+                    0 => Point { x: s, y: 0.0 },
+                    1 => Point { x: 40.0, y: s },
+                    2 => Point { x: 40.0 - s, y: 40.0 },
+                    _ => Point { x: 0.0, y: 40.0 - s },
+                }
+            })
+            .collect();
+        assert_eq!(fit_primitive(&square, PRIMITIVE_TOLERANCE), None);
+    }
+
+    #[test]
+    fn test_primitive_curves_wind_clockwise() {
+        let curves = Primitive::Ellipse { cx: 10.0, cy: 10.0, rx: 8.0, ry: 4.0 }.to_curves();
+        assert_eq!(curves.len(), 4);
+        assert_eq!((curves[0].start.x, curves[0].start.y), (18.0, 10.0));
+        assert_eq!((curves[0].end.x, curves[0].end.y), (10.0, 14.0));
+        assert_eq!((curves[3].end.x, curves[3].end.y), (18.0, 10.0));
+        assert_eq!(
+            Primitive::Circle { cx: 1.0, cy: 2.0, r: 3.0 }.scaled(2.0, 1.0),
+            Primitive::Ellipse { cx: 2.0, cy: 2.0, rx: 6.0, ry: 3.0 }
+        );
+
+        let rect = Primitive::Rect { x: 2.0, y: 4.0, width: 6.0, height: 2.0 };
+        let corners: Vec<(f64, f64)> = rect.to_curves().iter().map(|c| (c.start.x, c.start.y)).collect();
+        assert_eq!(corners, [(2.0, 4.0), (8.0, 4.0), (8.0, 6.0), (2.0, 6.0)]);
+        assert_eq!(rect.scaled(2.0, 0.5), Primitive::Rect { x: 4.0, y: 2.0, width: 12.0, height: 1.0 });
+    }
+}
@@ -0,0 +1,364 @@
+//! Minimal SVG rasterizer for regression tests (`raster` feature).
+//!
+//! Understands the subset this crate emits: `<rect>`, `<polygon>`,
+//! `<circle>`, `<ellipse>` and `<path>` fills (absolute or relative
+//! `M L H V C S Q T Z` commands) with `#rrggbb` colors, filled by the nonzero
+//! rule and scaled from the root `viewBox`. Strokes, transforms and opacity
+//! are ignored, which is close enough for comparing two conversions.
+
+use crate::image_processor::ImageData;
+use crate::vectorizer::Point;
+use rgb::RGBA8;
+
+/// Segments each curve is flattened into.
+const CURVE_STEPS: usize = 16;
+
+/// Mean per-pixel difference (0.0 = identical, 1.0 = opposite) between two
+/// SVGs rasterized at `width`x`height`, averaged over RGBA channels.
+pub fn svg_pixel_diff(a: &str, b: &str, width: u32, height: u32) -> f64 {
+    let (a, b) = (rasterize_svg(a, width, height), rasterize_svg(b, width, height));
+    if a.pixels.is_empty() {
+        return 0.0;
+    }
+    let total: u64 = a
+        .pixels
+        .iter()
+        .zip(&b.pixels)
+        .map(|(p, q)| {
+            (p.r.abs_diff(q.r) as u64) + (p.g.abs_diff(q.g) as u64) + (p.b.abs_diff(q.b) as u64) + (p.a.abs_diff(q.a) as u64)
+        })
+        .sum();
+    total as f64 / (a.pixels.len() as f64 * 4.0 * 255.0)
+}
+
+/// Rasterize `svg` onto a transparent `width`x`height` canvas.
+pub fn rasterize_svg(svg: &str, width: u32, height: u32) -> ImageData {
+    let mut canvas = ImageData {
+        width,
+        height,
+        pixels: vec![RGBA8::new(0, 0, 0, 0); width as usize * height as usize],
+    };
+    let mut scale = (1.0, 1.0, 0.0, 0.0);
+
+    let mut rest = svg;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('>') else { break };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+
+        if name == "svg" {
+            scale = root_scale(tag, width, height);
+            continue;
+        }
+        let Some(color) = attr(tag, "fill").and_then(parse_color) else { continue };
+        let num = |n: &str| attr(tag, n).and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0);
+        let rings = match name {
+            "rect" => {
+                let (x, y, w, h) = (num("x"), num("y"), num("width"), num("height"));
+                vec![vec![pt(x, y), pt(x + w, y), pt(x + w, y + h), pt(x, y + h)]]
+            }
+            "polygon" => vec![numbers(attr(tag, "points").unwrap_or("")).chunks_exact(2).map(|c| pt(c[0], c[1])).collect()],
+            "circle" => vec![ellipse_ring(num("cx"), num("cy"), num("r"), num("r"))],
+            "ellipse" => vec![ellipse_ring(num("cx"), num("cy"), num("rx"), num("ry"))],
+            "path" => path_rings(attr(tag, "d").unwrap_or("")),
+            _ => continue,
+        };
+        let (sx, sy, ox, oy) = scale;
+        let rings: Vec<Vec<Point>> = rings
+            .into_iter()
+            .map(|ring| ring.into_iter().map(|p| pt((p.x - ox) * sx, (p.y - oy) * sy)).collect())
+            .collect();
+        fill_nonzero(&mut canvas, &rings, color);
+    }
+    canvas
+}
+
+fn pt(x: f64, y: f64) -> Point {
+    Point { x, y }
+}
+
+/// (sx, sy, min_x, min_y) mapping user units onto the canvas.
+fn root_scale(tag: &str, width: u32, height: u32) -> (f64, f64, f64, f64) {
+    let view_box = attr(tag, "viewBox").map(numbers).filter(|v| v.len() == 4 && v[2] > 0.0 && v[3] > 0.0);
+    match view_box {
+        Some(v) => (width as f64 / v[2], height as f64 / v[3], v[0], v[1]),
+        None => {
+            let size = |n: &str| attr(tag, n).and_then(|v| v.parse::<f64>().ok()).filter(|&v| v > 0.0);
+            let sx = size("width").map_or(1.0, |w| width as f64 / w);
+            let sy = size("height").map_or(1.0, |h| height as f64 / h);
+            (sx, sy, 0.0, 0.0)
+        }
+    }
+}
+
+/// Value of attribute `name` in a tag body.
+fn attr<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    for quote in ['"', '\''] {
+        let key = format!(" {}={}", name, quote);
+        if let Some(start) = tag.find(&key) {
+            let value = &tag[start + key.len()..];
+            return value.find(quote).map(|end| &value[..end]);
+        }
+    }
+    None
+}
+
+fn parse_color(value: &str) -> Option<RGBA8> {
+    if value == "currentColor" {
+        return Some(RGBA8::new(0, 0, 0, 255));
+    }
+    let hex = value.strip_prefix('#')?;
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    match hex.len() {
+        6 => Some(RGBA8::new(channel(0)?, channel(2)?, channel(4)?, 255)),
+        3 => {
+            let short = |i: usize| u8::from_str_radix(hex.get(i..i + 1)?, 16).ok().map(|v| v * 17);
+            Some(RGBA8::new(short(0)?, short(1)?, short(2)?, 255))
+        }
+        _ => None,
+    }
+}
+
+fn numbers(s: &str) -> Vec<f64> {
+    s.split(|c: char| c.is_whitespace() || c == ',')
+        .filter_map(|t| t.parse().ok())
+        .collect()
+}
+
+fn ellipse_ring(cx: f64, cy: f64, rx: f64, ry: f64) -> Vec<Point> {
+    (0..CURVE_STEPS * 4)
+        .map(|i| {
+            let t = i as f64 / (CURVE_STEPS * 4) as f64 * std::f64::consts::TAU;
+            pt(cx + rx * t.cos(), cy + ry * t.sin())
+        })
+        .collect()
+}
+
+/// Flatten path data into closed polygon rings.
+fn path_rings(d: &str) -> Vec<Vec<Point>> {
+    let tokens = path_tokens(d);
+    let mut rings: Vec<Vec<Point>> = Vec::new();
+    let mut ring: Vec<Point> = Vec::new();
+    let (mut cur, mut start) = (pt(0.0, 0.0), pt(0.0, 0.0));
+    // Previous control point, for S and T reflection
+    let mut last_ctrl: Option<(char, Point)> = None;
+    let mut i = 0;
+    let mut cmd = 'M';
+
+    let cubic = |ring: &mut Vec<Point>, p0: &Point, c1: &Point, c2: &Point, p3: &Point| {
+        for s in 1..=CURVE_STEPS {
+            let t = s as f64 / CURVE_STEPS as f64;
+            let u = 1.0 - t;
+            let (a, b, c, e) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            ring.push(pt(
+                a * p0.x + b * c1.x + c * c2.x + e * p3.x,
+                a * p0.y + b * c1.y + c * c2.y + e * p3.y,
+            ));
+        }
+    };
+
+    while i < tokens.len() {
+        if let PathToken::Cmd(c) = tokens[i] {
+            cmd = c;
+            i += 1;
+            if c == 'Z' || c == 'z' {
+                if ring.len() > 2 {
+                    rings.push(std::mem::take(&mut ring));
+                }
+                ring.clear();
+                cur = start.clone();
+                last_ctrl = None;
+                continue;
+            }
+        }
+        let arity = match cmd.to_ascii_uppercase() {
+            'M' | 'L' | 'T' => 2,
+            'H' | 'V' => 1,
+            'S' | 'Q' => 4,
+            'C' => 6,
+            _ => break,
+        };
+        let args: Vec<f64> = tokens[i..].iter().take(arity).map_while(|t| match t {
+            PathToken::Num(v) => Some(*v),
+            PathToken::Cmd(_) => None,
+        }).collect();
+        if args.len() < arity {
+            break;
+        }
+        i += arity;
+
+        let rel = cmd.is_ascii_lowercase();
+        let at = |k: usize| {
+            if rel { pt(cur.x + args[k], cur.y + args[k + 1]) } else { pt(args[k], args[k + 1]) }
+        };
+        let reflect = |kind: char| match &last_ctrl {
+            Some((k, c)) if *k == kind => pt(2.0 * cur.x - c.x, 2.0 * cur.y - c.y),
+            _ => cur.clone(),
+        };
+        match cmd.to_ascii_uppercase() {
+            'M' => {
+                if ring.len() > 2 {
+                    rings.push(std::mem::take(&mut ring));
+                }
+                ring.clear();
+                cur = at(0);
+                start = cur.clone();
+                ring.push(cur.clone());
+                // Further pairs after M are line-tos
+                cmd = if rel { 'l' } else { 'L' };
+                last_ctrl = None;
+            }
+            'L' => {
+                cur = at(0);
+                ring.push(cur.clone());
+                last_ctrl = None;
+            }
+            'H' => {
+                cur = pt(if rel { cur.x + args[0] } else { args[0] }, cur.y);
+                ring.push(cur.clone());
+                last_ctrl = None;
+            }
+            'V' => {
+                cur = pt(cur.x, if rel { cur.y + args[0] } else { args[0] });
+                ring.push(cur.clone());
+                last_ctrl = None;
+            }
+            'C' => {
+                let (c1, c2, end) = (at(0), at(2), at(4));
+                cubic(&mut ring, &cur, &c1, &c2, &end);
+                last_ctrl = Some(('C', c2));
+                cur = end;
+            }
+            'S' => {
+                let (c1, c2, end) = (reflect('C'), at(0), at(2));
+                cubic(&mut ring, &cur, &c1, &c2, &end);
+                last_ctrl = Some(('C', c2));
+                cur = end;
+            }
+            'Q' | 'T' => {
+                let (q, end) = if cmd.eq_ignore_ascii_case(&'Q') { (at(0), at(2)) } else { (reflect('Q'), at(0)) };
+                let c1 = pt(cur.x + 2.0 / 3.0 * (q.x - cur.x), cur.y + 2.0 / 3.0 * (q.y - cur.y));
+                let c2 = pt(end.x + 2.0 / 3.0 * (q.x - end.x), end.y + 2.0 / 3.0 * (q.y - end.y));
+                cubic(&mut ring, &cur, &c1, &c2, &end);
+                last_ctrl = Some(('Q', q));
+                cur = end;
+            }
+            _ => unreachable!(),
+        }
+    }
+    if ring.len() > 2 {
+        rings.push(ring);
+    }
+    rings
+}
+
+enum PathToken {
+    Cmd(char),
+    Num(f64),
+}
+
+fn path_tokens(d: &str) -> Vec<PathToken> {
+    let mut tokens = Vec::new();
+    let mut number = String::new();
+    let flush = |number: &mut String, tokens: &mut Vec<PathToken>| {
+        if let Ok(v) = number.parse() {
+            tokens.push(PathToken::Num(v));
+        }
+        number.clear();
+    };
+    for c in d.chars() {
+        if c.is_ascii_digit() || c == '.' || c == 'e' {
+            // A second '.' starts a new number ("0.5.5" = 0.5, .5)
+            if c == '.' && number.contains('.') {
+                flush(&mut number, &mut tokens);
+            }
+            number.push(c);
+        } else if c == '-' || c == '+' {
+            if !number.ends_with('e') {
+                flush(&mut number, &mut tokens);
+            }
+            number.push(c);
+        } else {
+            flush(&mut number, &mut tokens);
+            if c.is_ascii_alphabetic() {
+                tokens.push(PathToken::Cmd(c));
+            }
+        }
+    }
+    flush(&mut number, &mut tokens);
+    tokens
+}
+
+/// Paint pixels whose centers lie inside `rings` under the nonzero rule.
+fn fill_nonzero(canvas: &mut ImageData, rings: &[Vec<Point>], color: RGBA8) {
+    let (w, h) = (canvas.width as usize, canvas.height as usize);
+    let mut crossings: Vec<(f64, i32)> = Vec::new();
+    for y in 0..h {
+        let sy = y as f64 + 0.5;
+        crossings.clear();
+        for ring in rings {
+            for k in 0..ring.len() {
+                let (a, b) = (&ring[k], &ring[(k + 1) % ring.len()]);
+                if (a.y <= sy) != (b.y <= sy) {
+                    let x = a.x + (sy - a.y) / (b.y - a.y) * (b.x - a.x);
+                    crossings.push((x, if b.y > a.y { 1 } else { -1 }));
+                }
+            }
+        }
+        crossings.sort_by(|p, q| p.0.total_cmp(&q.0));
+        let mut winding = 0;
+        for pair in crossings.windows(2) {
+            winding += pair[0].1;
+            if winding == 0 {
+                continue;
+            }
+            let from = (pair[0].0 - 0.5).ceil().max(0.0) as usize;
+            let to = ((pair[1].0 - 0.5).ceil().max(0.0) as usize).min(w);
+            for x in from..to {
+                canvas.pixels[y * w + x] = color;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = r##"<svg width="20" height="20" viewBox="0 0 20 20" xmlns="http://www.w3.org/2000/svg">
+  <rect width="20" height="20" fill="#ffffff"/>
+  <path fill="#c80000" d="M5,5L15,5L15,15L5,15Z"/>
+</svg>"##;
+
+    #[test]
+    fn test_rasterize_fills_shapes() {
+        let img = rasterize_svg(SQUARE, 20, 20);
+        assert_eq!(img.pixels[0], RGBA8::new(255, 255, 255, 255));
+        assert_eq!(img.pixels[10 * 20 + 10], RGBA8::new(200, 0, 0, 255));
+        let red = img.pixels.iter().filter(|p| p.r == 200).count();
+        assert_eq!(red, 100);
+
+        // Same square at double resolution through the viewBox
+        let big = rasterize_svg(SQUARE, 40, 40);
+        assert_eq!(big.pixels.iter().filter(|p| p.r == 200).count(), 400);
+
+        // A hole wound the other way stays unpainted under nonzero
+        let ring = SQUARE.replace("L5,15Z", "L5,15ZM8,8L8,12L12,12L12,8Z");
+        let holed = rasterize_svg(&ring, 20, 20);
+        assert_eq!(holed.pixels[10 * 20 + 10], RGBA8::new(255, 255, 255, 255));
+    }
+
+    #[test]
+    fn test_svg_pixel_diff() {
+        assert_eq!(svg_pixel_diff(SQUARE, SQUARE, 20, 20), 0.0);
+
+        let moved = SQUARE.replace("M5,5L15,5L15,15L5,15Z", "M5,5c5,-3 10,3 10,0L15,15L5,15Z");
+        let diff = svg_pixel_diff(SQUARE, &moved, 20, 20);
+        assert!(diff > 0.0 && diff < 0.1, "{}", diff);
+
+        let recolored = SQUARE.replace("#c80000", "#0000c8");
+        assert!(svg_pixel_diff(SQUARE, &recolored, 20, 20) > diff);
+    }
+}
@@ -73,7 +73,7 @@ pub fn extract_regions_by_index(
 }
 
 /// 8-connectivity flood fill by exact palette index match.
-fn flood_fill_by_index(
+pub(crate) fn flood_fill_by_index(
     start_x: u32,
     start_y: u32,
     width: u32,
@@ -347,8 +347,20 @@ fn enforce_max_gap(points: &[Point], keep: &mut [bool], max_gap: f64) {
 }
 
 /// Detect background color by sampling border pixels (most frequent color).
+/// The result may be fully transparent when most of the border is.
 pub fn detect_background_color(
     image_data: &crate::image_processor::ImageData,
+) -> (u8, u8, u8, u8) {
+    detect_background_color_with_fallback(image_data, 0.0)
+}
+
+/// [`detect_background_color`], falling back to the most common color of the
+/// whole image (the largest region) when the border's most frequent color
+/// covers less than `min_border_share` (0–1) of the border, as when the
+/// subject bleeds off every edge.
+pub fn detect_background_color_with_fallback(
+    image_data: &crate::image_processor::ImageData,
+    min_border_share: f64,
 ) -> (u8, u8, u8, u8) {
     let w = image_data.width as usize;
     let h = image_data.height as usize;
@@ -356,30 +368,50 @@ pub fn detect_background_color(
         return (255, 255, 255, 255);
     }
 
-    let mut color_counts: std::collections::HashMap<u32, (usize, (u8, u8, u8, u8))> =
-        std::collections::HashMap::new();
-
-    let mut sample_border = |x: usize, y: usize| {
-        let p = &image_data.pixels[y * w + x];
-        let key = (p.r as u32) << 16 | (p.g as u32) << 8 | p.b as u32;
-        let entry = color_counts
-            .entry(key)
-            .or_insert((0, (p.r, p.g, p.b, p.a)));
-        entry.0 += 1;
-    };
+    let mut color_counts: ColorCounts = std::collections::HashMap::new();
 
     // Sample all border pixels
     for x in 0..w {
-        sample_border(x, 0);
-        sample_border(x, h - 1);
+        count_color(&mut color_counts, &image_data.pixels[x]);
+        count_color(&mut color_counts, &image_data.pixels[(h - 1) * w + x]);
     }
     for y in 1..h - 1 {
-        sample_border(0, y);
-        sample_border(w - 1, y);
+        count_color(&mut color_counts, &image_data.pixels[y * w]);
+        count_color(&mut color_counts, &image_data.pixels[y * w + w - 1]);
+    }
+
+    let border_pixels: usize = color_counts.values().map(|(count, _)| count).sum();
+    match most_frequent(&color_counts) {
+        Some((count, color)) if count as f64 >= min_border_share * border_pixels as f64 => color,
+        Some(_) => {
+            let mut color_counts: ColorCounts = std::collections::HashMap::new();
+            for p in &image_data.pixels {
+                count_color(&mut color_counts, p);
+            }
+            most_frequent(&color_counts).map_or((255, 255, 255, 255), |(_, color)| color)
+        }
+        None => (255, 255, 255, 255),
     }
+}
+
+/// Pixel count and color per packed RGBA key.
+type ColorCounts = std::collections::HashMap<u32, (usize, (u8, u8, u8, u8))>;
+
+/// Count one pixel. Fully transparent pixels are one color whatever their
+/// RGB, so a transparent border is detected as a transparent background.
+fn count_color(color_counts: &mut ColorCounts, p: &rgb::RGBA8) {
+    let key = if p.a == 0 {
+        0
+    } else {
+        (p.r as u32) << 24 | (p.g as u32) << 16 | (p.b as u32) << 8 | p.a as u32
+    };
+    let entry = color_counts.entry(key).or_insert((0, (p.r, p.g, p.b, p.a)));
+    entry.0 += 1;
+}
 
-    // Deterministic tie-breaking: highest count wins; on tie, prefer lighter color
-    // (lighter colors are more common backgrounds). Uses luminance as tie-breaker.
+/// Deterministic tie-breaking: highest count wins; on tie, prefer lighter color
+/// (lighter colors are more common backgrounds). Uses luminance as tie-breaker.
+fn most_frequent(color_counts: &ColorCounts) -> Option<(usize, (u8, u8, u8, u8))> {
     color_counts
         .values()
         .max_by(|(count_a, ca), (count_b, cb)| {
@@ -389,31 +421,61 @@ pub fn detect_background_color(
                 lum_a.cmp(&lum_b)
             })
         })
-        .map(|(_, color)| *color)
-        .unwrap_or((255, 255, 255, 255))
+        .copied()
 }
 
 /// Recolor regions using original (pre-quantized) image pixels for true color accuracy.
+///
+/// By default only boundary pixels are sampled, which leans toward edge and
+/// anti-aliasing colors. With `sample_interior` every pixel of the region
+/// (recovered by flood-filling `indices` from its boundary) is averaged.
 pub fn recolor_from_original(
     regions: &mut [Region],
     original: &crate::image_processor::ImageData,
-    _indices: &[usize],
+    indices: &[usize],
     _palette: &[rgb::RGBA8],
+    sample_interior: bool,
 ) {
     let w = original.width as usize;
     let h = original.height as usize;
+    let use_indices = sample_interior && indices.len() == w * h;
+    let mut visited = if use_indices { vec![false; w * h] } else { Vec::new() };
 
     for region in regions.iter_mut() {
-        // Sample original image colors along the boundary
+        // Interior sampling: the region's full pixel set
+        let interior = match region.boundary.first() {
+            Some(seed) if use_indices => {
+                let (sx, sy) = (seed.x.round() as usize, seed.y.round() as usize);
+                if sx < w && sy < h && !visited[sy * w + sx] {
+                    let pixels = flood_fill_by_index(
+                        sx as u32, sy as u32, original.width, original.height,
+                        indices, indices[sy * w + sx], &mut visited,
+                    );
+                    pixels.into_iter().map(|(x, y)| (x as usize, y as usize)).collect()
+                } else {
+                    Vec::new()
+                }
+            }
+            _ => Vec::new(),
+        };
+        // Otherwise sample original image colors along the boundary
+        let samples: Vec<(usize, usize)> = if interior.is_empty() {
+            region
+                .boundary
+                .iter()
+                .map(|pt| (pt.x.round() as usize, pt.y.round() as usize))
+                .collect()
+        } else {
+            interior
+        };
+
         let mut sr: u64 = 0;
         let mut sg: u64 = 0;
         let mut sb: u64 = 0;
         let mut sa: u64 = 0;
         let mut count: u64 = 0;
 
-        for pt in &region.boundary {
-            let px = pt.x.round() as usize;
-            let py = pt.y.round() as usize;
+        for (px, py) in samples {
             if px < w && py < h {
                 let p = &original.pixels[py * w + px];
                 sr += p.r as u64;
@@ -424,7 +486,7 @@ pub fn recolor_from_original(
             }
         }
 
-        if count > 0 {
+        if let Some(count) = std::num::NonZeroU64::new(count) {
             region.color = (
                 (sr / count) as u8,
                 (sg / count) as u8,
@@ -510,6 +572,24 @@ mod tests {
         assert_eq!(bg, (255, 0, 0, 255));
     }
 
+    #[test]
+    fn test_detect_background_color_falls_back_to_largest_region() {
+        use crate::image_processor::ImageData;
+        // Red fills the top-left and bottom-right corners (60% of the border,
+        // 36% of the area); blue covers the rest
+        let n = 20;
+        let pixels = (0..n * n)
+            .map(|i| {
+                let (x, y) = (i % n, i / n);
+                if x + y < 12 || x + y >= 2 * n - 12 - 1 { RGBA8::new(255, 0, 0, 255) } else { RGBA8::new(0, 0, 255, 255) }
+            })
+            .collect();
+        let img = ImageData { width: n as u32, height: n as u32, pixels };
+        assert_eq!(detect_background_color(&img), (255, 0, 0, 255));
+        assert_eq!(detect_background_color_with_fallback(&img, 0.5), (255, 0, 0, 255));
+        assert_eq!(detect_background_color_with_fallback(&img, 0.7), (0, 0, 255, 255));
+    }
+
     #[test]
     fn test_curvature_straight_line() {
         let points = vec![
@@ -529,4 +609,39 @@ mod tests {
         ];
         assert!(compute_curvature(&points, 1) > 0.5);
     }
+
+    #[test]
+    fn test_recolor_interior_vs_boundary() {
+        // 8x8 red block whose outer ring is a pink anti-aliasing halo, on a
+        // white background; the quantizer put halo and block in one index
+        let (w, h) = (12u32, 12u32);
+        let mut pixels = vec![RGBA8::new(255, 255, 255, 255); 144];
+        let mut indices = vec![0usize; 144];
+        for y in 2..10 {
+            for x in 2..10 {
+                let halo = x == 2 || x == 9 || y == 2 || y == 9;
+                pixels[y * 12 + x] = if halo { RGBA8::new(255, 160, 160, 255) } else { RGBA8::new(200, 0, 0, 255) };
+                indices[y * 12 + x] = 1;
+            }
+        }
+        let original = crate::image_processor::ImageData { width: w, height: h, pixels };
+        let palette = vec![RGBA8::new(255, 255, 255, 255), RGBA8::new(210, 40, 40, 255)];
+        let regions: Vec<Region> = extract_regions_by_index(w, h, &indices, &palette, 1)
+            .into_iter()
+            .filter(|r| r.area == 64)
+            .collect();
+        assert_eq!(regions.len(), 1);
+
+        let mut boundary = regions.clone();
+        recolor_from_original(&mut boundary, &original, &indices, &palette, false);
+        let mut interior = regions;
+        recolor_from_original(&mut interior, &original, &indices, &palette, true);
+
+        // Boundary sampling sees only the halo
+        assert_eq!(boundary[0].color, (255, 160, 160, 255));
+        // Interior sampling averages 28 halo and 36 red pixels
+        let (r, g, _, _) = interior[0].color;
+        assert_eq!(r, ((28 * 255 + 36 * 200) / 64) as u8);
+        assert_eq!(g, ((28 * 160) / 64) as u8);
+    }
 }
@@ -1,25 +1,52 @@
+use crate::bezier_fitter::PathFormat;
 use crate::image_processor::Result;
 use crate::vectorizer::{Point, VectorizedData};
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 use std::path::Path;
 
 pub fn generate_svg(vectorized_data: &VectorizedData, output_path: &Path) -> Result<()> {
-    let mut file = File::create(output_path)?;
+    let mut file = BufWriter::new(File::create(output_path)?);
+    generate_svg_to_writer(vectorized_data, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Write the SVG document to any sink (stdout, a buffer, a gzip encoder, ...).
+pub fn generate_svg_to_writer<W: Write>(vectorized_data: &VectorizedData, w: &mut W) -> Result<()> {
+    w.write_all(generate_svg_string(vectorized_data).as_bytes())?;
+    Ok(())
+}
+
+/// Render vectorized data as an SVG document, as written by [`generate_svg`].
+pub fn generate_svg_string(vectorized_data: &VectorizedData) -> String {
+    render_svg(vectorized_data, None)
+}
+
+/// Render vectorized data as an SVG document with coordinates rounded to
+/// `precision` decimal places (trailing zeros trimmed) instead of the
+/// 0.5 px grid; 0 writes integers only.
+pub fn generate_svg_string_with_precision(vectorized_data: &VectorizedData, precision: u8) -> String {
+    render_svg(vectorized_data, Some(precision))
+}
+
+fn render_svg(vectorized_data: &VectorizedData, precision: Option<u8>) -> String {
+    let mut svg = String::new();
     let bg = vectorized_data.background_color;
     let bg_str = format!("#{:02x}{:02x}{:02x}", bg.0, bg.1, bg.2);
 
-    writeln!(
-        file,
-        r#"<svg width="{}" height="{}" viewBox="0 0 {} {}" xmlns="http://www.w3.org/2000/svg">"#,
+    svg.push_str(&format!(
+        "<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" xmlns=\"http://www.w3.org/2000/svg\">\n",
         vectorized_data.width, vectorized_data.height,
         vectorized_data.width, vectorized_data.height
-    )?;
-    writeln!(
-        file,
-        r#"  <rect width="{}" height="{}" fill="{}"/>"#,
-        vectorized_data.width, vectorized_data.height, bg_str
-    )?;
+    ));
+    // A fully transparent background is left out rather than painted
+    if bg.3 > 0 {
+        svg.push_str(&format!(
+            "  <rect width=\"{}\" height=\"{}\" fill=\"{}\"{}/>\n",
+            vectorized_data.width, vectorized_data.height, bg_str, opacity_attr("fill-opacity", bg.3)
+        ));
+    }
 
     for curve in &vectorized_data.curves {
         let color_str = format!(
@@ -28,9 +55,9 @@ pub fn generate_svg(vectorized_data: &VectorizedData, output_path: &Path) -> Res
         );
 
         let path_str = if !curve.subpaths.is_empty() {
-            create_multi_path_string(&curve.subpaths)
+            multi_path_string(&curve.subpaths, precision)
         } else if !curve.points.is_empty() {
-            create_subpath_string(&curve.points, curve.is_closed)
+            subpath_string(&curve.points, curve.is_closed, precision)
         } else {
             continue;
         };
@@ -39,15 +66,24 @@ pub fn generate_svg(vectorized_data: &VectorizedData, output_path: &Path) -> Res
             continue;
         }
 
-        writeln!(
-            file,
-            r#"  <path d="{}" fill="{}" stroke="none"/>"#,
-            path_str, color_str
-        )?;
+        svg.push_str(&format!(
+            "  <path d=\"{}\" fill=\"{}\"{} stroke=\"none\"/>\n",
+            path_str, color_str, opacity_attr("fill-opacity", curve.color.3)
+        ));
     }
 
-    writeln!(file, "</svg>")?;
-    Ok(())
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// An opacity attribute (`fill-opacity`, `stroke-opacity`, ...) carrying a
+/// color's alpha byte, with a leading space; empty for opaque colors.
+pub(crate) fn opacity_attr(name: &str, alpha: u8) -> String {
+    if alpha == 255 {
+        return String::new();
+    }
+    let format = PathFormat { precision: 3, ..Default::default() };
+    format!(" {}=\"{}\"", name, format.num(alpha as f64 / 255.0))
 }
 
 pub fn generate_svg_advanced(vectorized_data: &VectorizedData, output_path: &Path) -> Result<()> {
@@ -57,6 +93,16 @@ pub fn generate_svg_advanced(vectorized_data: &VectorizedData, output_path: &Pat
 
 /// Build a single SVG path `d` attribute containing multiple M...Z subpaths.
 pub fn create_multi_path_string(subpaths: &[Vec<Point>]) -> String {
+    multi_path_string(subpaths, None)
+}
+
+/// [`create_multi_path_string`] with coordinates rounded to `precision`
+/// decimal places.
+pub fn create_multi_path_string_with_precision(subpaths: &[Vec<Point>], precision: u8) -> String {
+    multi_path_string(subpaths, Some(precision))
+}
+
+fn multi_path_string(subpaths: &[Vec<Point>], precision: Option<u8>) -> String {
     let mut path = String::new();
     for sp in subpaths {
         if sp.len() < 3 {
@@ -65,16 +111,18 @@ pub fn create_multi_path_string(subpaths: &[Vec<Point>]) -> String {
         if !path.is_empty() {
             path.push(' ');
         }
-        path.push_str(&create_subpath_string(sp, true));
+        path.push_str(&subpath_string(sp, true, precision));
     }
     path
 }
 
 /// Format a coordinate compactly: use integer if close to whole, else 1 decimal.
+/// Whole values go through i64, so coordinates far beyond any real image
+/// size are never truncated.
 fn fmt_coord(v: f64) -> String {
     let rounded = (v * 2.0).round() / 2.0; // snap to 0.5 grid
     if (rounded - rounded.round()).abs() < 0.01 {
-        format!("{}", rounded.round() as i32)
+        format!("{}", rounded.round() as i64)
     } else {
         format!("{:.1}", rounded)
     }
@@ -84,15 +132,32 @@ fn fmt_coord(v: f64) -> String {
 /// Marching squares + RDP already produces accurate contours; line segments
 /// are compact and browsers anti-alias them smoothly.
 pub fn create_subpath_string(pts: &[Point], closed: bool) -> String {
+    subpath_string(pts, closed, None)
+}
+
+/// [`create_subpath_string`] with coordinates rounded to `precision`
+/// decimal places.
+pub fn create_subpath_string_with_precision(pts: &[Point], closed: bool, precision: u8) -> String {
+    subpath_string(pts, closed, Some(precision))
+}
+
+/// Subpath with coordinates on the 0.5 px grid (`None`) or rounded to the
+/// given number of decimals.
+fn subpath_string(pts: &[Point], closed: bool, precision: Option<u8>) -> String {
     let n = pts.len();
     if n == 0 {
         return String::new();
     }
 
-    let mut path = format!("M{} {}", fmt_coord(pts[0].x), fmt_coord(pts[0].y));
+    let format = precision.map(|precision| PathFormat { precision, ..Default::default() });
+    let coord = |v: f64| match &format {
+        Some(format) => format.num(v),
+        None => fmt_coord(v),
+    };
+    let mut path = format!("M{} {}", coord(pts[0].x), coord(pts[0].y));
 
-    for i in 1..n {
-        path.push_str(&format!("L{} {}", fmt_coord(pts[i].x), fmt_coord(pts[i].y)));
+    for p in &pts[1..] {
+        path.push_str(&format!("L{} {}", coord(p.x), coord(p.y)));
     }
     if closed {
         path.push('Z');
@@ -102,6 +167,7 @@ pub fn create_subpath_string(pts: &[Point], closed: bool) -> String {
 }
 
 #[cfg(test)]
+#[allow(clippy::module_inception, reason = "the included file declares its own `mod tests`")]
 mod tests {
     include!("svg_generator_tests.rs");
 }
@@ -121,6 +121,20 @@ mod tests {
         let _ = fs::remove_file(&output_path);
     }
 
+    #[test]
+    fn test_generate_svg_to_writer_matches_file() {
+        let data = create_test_vectorized_data();
+        let output_path = PathBuf::from("/tmp/test_writer.svg");
+        generate_svg(&data, &output_path).unwrap();
+
+        let mut buffer = Vec::new();
+        generate_svg_to_writer(&data, &mut buffer).unwrap();
+        assert_eq!(buffer, fs::read(&output_path).unwrap());
+        assert_eq!(String::from_utf8(buffer).unwrap(), generate_svg_string(&data));
+
+        let _ = fs::remove_file(&output_path);
+    }
+
     #[test]
     fn test_generate_svg_advanced() {
         let data = create_test_vectorized_data();
@@ -183,6 +197,15 @@ mod tests {
         assert_eq!(fmt_coord(10.5), "10.5");
     }
 
+    #[test]
+    fn test_fmt_coord_large_values() {
+        assert_eq!(fmt_coord(70000.5), "70000.5");
+        assert_eq!(fmt_coord(70000.0), "70000");
+        // Beyond i32 range
+        assert_eq!(fmt_coord(3_000_000_000.0), "3000000000");
+        assert_eq!(fmt_coord(-3_000_000_000.5), "-3000000000.5");
+    }
+
     #[test]
     fn test_fmt_coord_rounding() {
         // Should round to nearest 0.5, then format as integer if close to whole
@@ -264,6 +287,23 @@ mod tests {
 
     // === create_multi_path_string Tests ===
 
+    #[test]
+    fn test_subpath_precision_changes_length() {
+        let pts: Vec<Point> = (0..20)
+            .map(|i| {
+                let t = i as f64 * 0.3141;
+                Point { x: 50.0 + 40.0 * t.cos(), y: 50.0 + 40.0 * t.sin() }
+            })
+            .collect();
+        let coarse = create_subpath_string_with_precision(&pts, true, 1);
+        let fine = create_subpath_string_with_precision(&pts, true, 3);
+        assert!(fine.len() > coarse.len() + 20, "{} vs {}", fine, coarse);
+
+        let integers = create_subpath_string_with_precision(&pts, true, 0);
+        assert!(!integers.contains('.'), "{}", integers);
+        assert_eq!(create_subpath_string_with_precision(&[Point { x: 1.5, y: 2.0 }], false, 3), "M1.5 2");
+    }
+
     #[test]
     fn test_create_multi_path_string_empty() {
         let subpaths: Vec<Vec<Point>> = vec![];
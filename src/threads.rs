@@ -0,0 +1,44 @@
+//! Thread-count control for the parallel stages of the enhanced pipeline.
+//!
+//! By default work runs on rayon's global pool, which uses every core. After
+//! [`set_thread_count`] it runs on a dedicated pool of that size instead, so
+//! a conversion never uses more threads than requested.
+
+use anyhow::Result;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::{Arc, RwLock};
+
+static POOL: RwLock<Option<Arc<ThreadPool>>> = RwLock::new(None);
+
+/// Limit conversions to `threads` worker threads; 0 restores the default of
+/// one per core. Applies to conversions started afterwards.
+pub fn set_thread_count(threads: usize) -> Result<()> {
+    let pool = if threads == 0 {
+        None
+    } else {
+        Some(Arc::new(ThreadPoolBuilder::new().num_threads(threads).build()?))
+    };
+    *POOL.write().unwrap_or_else(|e| e.into_inner()) = pool;
+    Ok(())
+}
+
+/// Run `op` on the configured pool (or the global one).
+pub(crate) fn install<R: Send>(op: impl FnOnce() -> R + Send) -> R {
+    let pool = POOL.read().unwrap_or_else(|e| e.into_inner()).clone();
+    match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_uses_configured_pool() {
+        assert!(set_thread_count(2).is_ok());
+        assert_eq!(install(rayon::current_num_threads), 2);
+        assert!(set_thread_count(0).is_ok());
+    }
+}
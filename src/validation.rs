@@ -0,0 +1,183 @@
+//! Self-checks for generated output: geometry sanity before emission and a
+//! lightweight well-formedness check of the SVG text.
+
+use crate::enhanced_vectorizer::EnhancedVectorData;
+use crate::error::Img2SvgError;
+use crate::vectorizer::Point;
+use anyhow::Result;
+
+/// Check that every curve point is finite and within the canvas grown by
+/// its own size on each side (fitting may overshoot a little, never that far).
+pub fn validate_geometry(data: &EnhancedVectorData) -> Result<()> {
+    let (w, h) = (data.width as f64, data.height as f64);
+    let check = |p: &Point, what: &str| -> Result<()> {
+        if !p.x.is_finite() || !p.y.is_finite() {
+            return Err(Img2SvgError::InvalidGeometry(format!("{} ({}, {}) is not finite", what, p.x, p.y)).into());
+        }
+        if p.x < -w || p.x > 2.0 * w || p.y < -h || p.y > 2.0 * h {
+            return Err(Img2SvgError::InvalidGeometry(format!(
+                "{} ({}, {}) is far outside the {}x{} canvas",
+                what, p.x, p.y, data.width, data.height
+            ))
+            .into());
+        }
+        Ok(())
+    };
+
+    let paths = data
+        .paths
+        .iter()
+        .flat_map(|p| std::iter::once(&p.curves).chain(&p.holes))
+        .chain(std::iter::once(&data.silhouette))
+        .chain(data.strokes.iter().map(|s| &s.curves));
+    for curves in paths {
+        for c in curves {
+            check(&c.start, "curve start")?;
+            check(&c.control1, "control point")?;
+            check(&c.control2, "control point")?;
+            check(&c.end, "curve end")?;
+        }
+    }
+    Ok(())
+}
+
+/// Check that `svg` is well-formed markup: every tag closed and properly
+/// nested, attribute values quoted, and an `<svg>` element present.
+/// Comments, `<?...?>` and `<!...>` declarations are skipped.
+pub fn validate_svg_wellformed(svg: &str) -> Result<()> {
+    let malformed = |msg: String| -> anyhow::Error { Img2SvgError::MalformedSvg(msg).into() };
+    let mut stack: Vec<&str> = Vec::new();
+    let mut seen_svg = false;
+    let mut rest = svg;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").ok_or_else(|| malformed("unterminated comment".into()))?;
+            rest = &comment[end + 3..];
+            continue;
+        }
+        let end = rest.find('>').ok_or_else(|| malformed("unterminated tag".into()))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            match stack.pop() {
+                Some(open) if open == name => continue,
+                Some(open) => return Err(malformed(format!("</{}> closes <{}>", name, open))),
+                None => return Err(malformed(format!("</{}> without an open tag", name))),
+            }
+        }
+
+        let self_closing = tag.ends_with('/');
+        let body = tag.strip_suffix('/').unwrap_or(tag);
+        let name_len = body.find(char::is_whitespace).unwrap_or(body.len());
+        let name = &body[..name_len];
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')) {
+            return Err(malformed(format!("bad tag name in <{}>", tag)));
+        }
+        check_attributes(&body[name_len..]).map_err(|msg| malformed(format!("<{}>: {}", name, msg)))?;
+        seen_svg |= name == "svg";
+        if !self_closing {
+            stack.push(name);
+        }
+    }
+
+    if let Some(open) = stack.last() {
+        return Err(malformed(format!("<{}> is never closed", open)));
+    }
+    if !seen_svg {
+        return Err(malformed("no <svg> element".into()));
+    }
+    Ok(())
+}
+
+/// Check a tag's `name="value"` list.
+fn check_attributes(mut attrs: &str) -> std::result::Result<(), String> {
+    loop {
+        attrs = attrs.trim_start();
+        if attrs.is_empty() {
+            return Ok(());
+        }
+        let eq = attrs.find('=').ok_or_else(|| format!("attribute without value near `{}`", attrs))?;
+        let name = attrs[..eq].trim_end();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("bad attribute name `{}`", name));
+        }
+        let value = attrs[eq + 1..].trim_start();
+        let quote = value.chars().next().filter(|&c| c == '"' || c == '\'')
+            .ok_or_else(|| format!("unquoted value for `{}`", name))?;
+        let close = value[1..].find(quote).ok_or_else(|| format!("unterminated value for `{}`", name))?;
+        if value[1..1 + close].contains('<') {
+            return Err(format!("`<` in value of `{}`", name));
+        }
+        attrs = &value[close + 2..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bezier_fitter::BezierCurve;
+    use crate::enhanced_vectorizer::{generate_enhanced_svg, EnhancedPath};
+
+    fn square_data() -> EnhancedVectorData {
+        let p = |x: f64, y: f64| Point { x, y };
+        let line = |a: Point, b: Point| BezierCurve { start: a.clone(), control1: a, control2: b.clone(), end: b };
+        let curves = vec![
+            line(p(2.0, 2.0), p(8.0, 2.0)),
+            line(p(8.0, 2.0), p(8.0, 8.0)),
+            line(p(8.0, 8.0), p(2.0, 2.0)),
+        ];
+        EnhancedVectorData {
+            width: 10,
+            height: 10,
+            background_color: (255, 255, 255, 255),
+            paths: vec![EnhancedPath { curves, color: (0, 0, 0, 255), quantized: (0, 0, 0, 255), area: 18, svg_override: None, holes: Vec::new(), primitive: None }],
+            stats: Default::default(),
+            coverage: Vec::new(),
+            silhouette: Vec::new(),
+            strokes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_validate_geometry_catches_nan() {
+        let mut data = square_data();
+        assert!(validate_geometry(&data).is_ok());
+
+        data.paths[0].curves[1].control1.x = f64::NAN;
+        let err = validate_geometry(&data).unwrap_err();
+        assert!(matches!(err.downcast_ref::<Img2SvgError>(), Some(Img2SvgError::InvalidGeometry(_))));
+
+        let mut data = square_data();
+        data.paths[0].curves[0].end.y = 1e9;
+        assert!(validate_geometry(&data).is_err());
+    }
+
+    #[test]
+    fn test_validate_svg_wellformed() {
+        assert!(validate_svg_wellformed(&generate_enhanced_svg(&square_data())).is_ok());
+        assert!(validate_svg_wellformed("<!DOCTYPE html>\n<html><body><svg a='1'><!-- x --></svg></body></html>").is_ok());
+
+        for bad in [
+            "<svg><path d=\"M0,0\"></svg>",
+            "<svg><path d=M0 /></svg>",
+            "<svg><path d=\"M0 /></svg>",
+            "<svg></g>",
+            "<svg",
+            "<g/>",
+        ] {
+            let err = validate_svg_wellformed(bad).unwrap_err();
+            assert!(
+                matches!(err.downcast_ref::<Img2SvgError>(), Some(Img2SvgError::MalformedSvg(_))),
+                "{}",
+                bad
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::image_processor::Result;
+use crate::enhanced_quantizer::quantize_enhanced;
 use crate::image_processor::{quantize_colors, ImageData};
 use std::collections::HashMap;
 
@@ -24,22 +25,54 @@ pub struct VectorizedData {
     pub background_color: (u8, u8, u8, u8),
 }
 
+/// Color quantizer used by the legacy pipeline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Quantizer {
+    /// Median-cut (`quantize_colors`)
+    #[default]
+    MedianCut,
+    /// K-means++ with perceptual distance (`quantize_enhanced`)
+    KMeans,
+}
+
 /// Region-based vectorization using marching-squares contour tracing.
 /// For each unique color, builds a binary mask and traces sub-pixel-accurate
 /// contours that properly enclose all pixels of that color.
 pub fn vectorize(
+    image_data: &ImageData,
+    num_colors: usize,
+    threshold: f64,
+    smooth_level: u8,
+    hierarchical: bool,
+) -> Result<VectorizedData> {
+    vectorize_with_quantizer(
+        image_data,
+        num_colors,
+        threshold,
+        smooth_level,
+        hierarchical,
+        Quantizer::MedianCut,
+    )
+}
+
+/// [`vectorize`] with an explicit choice of color quantizer.
+pub fn vectorize_with_quantizer(
     image_data: &ImageData,
     num_colors: usize,
     _threshold: f64,
     smooth_level: u8,
     _hierarchical: bool,
+    quantizer: Quantizer,
 ) -> Result<VectorizedData> {
-    let quantized = quantize_colors(image_data, num_colors)?;
+    let quantized = match quantizer {
+        Quantizer::MedianCut => quantize_colors(image_data, num_colors)?,
+        Quantizer::KMeans => quantize_enhanced(image_data, num_colors).0,
+    };
     let width = quantized.width as usize;
     let height = quantized.height as usize;
 
     // Group pixels by quantized color
-    let mut color_pixels: HashMap<(u8, u8, u8, u8), Vec<(usize, usize)>> = HashMap::new();
+    let mut color_pixels: HashMap<_, Vec<(usize, usize)>> = HashMap::new();
     for y in 0..height {
         for x in 0..width {
             let p = quantized.pixels[y * width + x];
@@ -50,7 +83,7 @@ pub fn vectorize(
 
     // Sort colors by pixel count (largest area first for proper z-order)
     let mut color_list: Vec<_> = color_pixels.into_iter().collect();
-    color_list.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+    color_list.sort_by_key(|c| std::cmp::Reverse(c.1.len()));
 
     let mut curves = Vec::new();
     let total_pixels = width * height;
@@ -124,6 +157,20 @@ pub fn vectorize(
     })
 }
 
+/// How marching squares resolves a saddle cell, where two diagonally
+/// opposite corners are inside and the other two outside.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SaddlePolicy {
+    /// Inside corners stay separate (4-connected regions)
+    #[default]
+    Disconnect,
+    /// Inside corners are joined through the cell (8-connected regions)
+    Connect,
+    /// Join the inside corners when most of the surrounding 4x4 block of
+    /// pixels is inside, i.e. when the interpolated center value is inside
+    Majority,
+}
+
 /// Marching squares contour tracing on a binary mask.
 /// Produces sub-pixel contours at the boundary between true/false cells.
 /// The grid has (width+1) x (height+1) vertices; each cell (x,y) corresponds
@@ -132,6 +179,16 @@ pub fn marching_squares_contours(
     mask: &[bool],
     width: usize,
     height: usize,
+) -> Vec<Vec<Point>> {
+    marching_squares_contours_with_policy(mask, width, height, SaddlePolicy::Disconnect)
+}
+
+/// [`marching_squares_contours`] with an explicit saddle resolution policy.
+pub fn marching_squares_contours_with_policy(
+    mask: &[bool],
+    width: usize,
+    height: usize,
+    saddle: SaddlePolicy,
 ) -> Vec<Vec<Point>> {
     // Pixel (px, py) occupies the square [px, px+1] x [py, py+1].
     // We build a grid of (width+2) x (height+2) cells so that the image pixels
@@ -191,22 +248,42 @@ pub fn marching_squares_contours(
         }
     };
 
+    // Whether the inside corners of saddle cell (cx, cy) are joined.
+    let saddle_connects = |cx: usize, cy: usize| -> bool {
+        match saddle {
+            SaddlePolicy::Disconnect => false,
+            SaddlePolicy::Connect => true,
+            SaddlePolicy::Majority => {
+                // The cell's own corners are always 2 of 4, so the ring of
+                // twelve pixels around them decides
+                let inside = (cy.saturating_sub(1)..=cy + 2)
+                    .flat_map(|gy| (cx.saturating_sub(1)..=cx + 2).map(move |gx| (gx, gy)))
+                    .filter(|&(gx, gy)| corner_inside(gx, gy))
+                    .count();
+                inside > 8
+            }
+        }
+    };
+
     // For each case, the edges that form segments.
     // Returns pairs of (entry_side, exit_side).
     // Sides: 0=top, 1=right, 2=bottom, 3=left
-    let case_edges = |case: u8| -> Vec<(u8, u8)> {
+    let case_edges = |case: u8, cx: usize, cy: usize| -> Vec<(u8, u8)> {
         match case {
             0 | 15 => vec![],
             1  => vec![(2, 3)],
             2  => vec![(1, 2)],
             3  => vec![(1, 3)],
             4  => vec![(0, 1)],
-            5  => vec![(0, 1), (2, 3)], // saddle
+            // Saddles: either cut off each inside corner, or each outside one
+            5 if saddle_connects(cx, cy) => vec![(0, 3), (2, 1)],
+            5  => vec![(0, 1), (2, 3)],
             6  => vec![(0, 2)],
             7  => vec![(0, 3)],
             8  => vec![(3, 0)],
             9  => vec![(2, 0)],
-            10 => vec![(3, 0), (1, 2)], // saddle
+            10 if saddle_connects(cx, cy) => vec![(1, 0), (3, 2)],
+            10 => vec![(3, 0), (1, 2)],
             11 => vec![(1, 0)],
             12 => vec![(3, 1)],
             13 => vec![(2, 1)],
@@ -235,12 +312,17 @@ pub fn marching_squares_contours(
         }
     };
 
+    // Every cell contributes at most two segments, so no closed contour can be
+    // longer than this. Guarantees termination even if a chain never returns
+    // to its start edge; whatever was traced so far is kept.
+    let max_steps = grid_w * grid_h * 2;
+
     let mut contours = Vec::new();
 
     for cy in 0..grid_h {
         for cx in 0..grid_w {
             let case = cell_case(cx, cy);
-            let edges = case_edges(case);
+            let edges = case_edges(case, cx, cy);
 
             for &(entry, exit) in &edges {
                 if edge_visited.contains_key(&(cx, cy, entry)) {
@@ -256,7 +338,7 @@ pub fn marching_squares_contours(
 
                 let start_key = (cx, cy, entry);
 
-                loop {
+                for _ in 0..max_steps {
                     edge_visited.insert((cur_cx, cur_cy, cur_entry), true);
                     edge_visited.insert((cur_cx, cur_cy, cur_exit), true);
                     contour.push(edge_point(cur_cx, cur_cy, cur_exit));
@@ -267,7 +349,7 @@ pub fn marching_squares_contours(
 
                     if let Some((ncx, ncy)) = next_cell {
                         let ncase = cell_case(ncx, ncy);
-                        let nedges = case_edges(ncase);
+                        let nedges = case_edges(ncase, ncx, ncy);
 
                         // Find the edge pair that enters from next_entry_side
                         if let Some(&(ne, nx)) = nedges.iter().find(|&&(e, _)| e == next_entry_side) {
@@ -351,8 +433,8 @@ fn rdp_simplify(points: &[Point], epsilon: f64) -> Vec<Point> {
     let first = &points[0];
     let last = &points[points.len() - 1];
 
-    for i in 1..points.len() - 1 {
-        let d = point_to_line_distance(&points[i], first, last);
+    for (i, point) in points.iter().enumerate().take(points.len() - 1).skip(1) {
+        let d = point_to_line_distance(point, first, last);
         if d > max_dist {
             max_dist = d;
             max_idx = i;
@@ -389,6 +471,7 @@ fn point_to_line_distance(point: &Point, line_start: &Point, line_end: &Point) -
 }
 
 #[cfg(test)]
+#[allow(clippy::module_inception, reason = "the included file declares its own `mod tests`")]
 mod tests {
     include!("vectorizer_tests.rs");
 }
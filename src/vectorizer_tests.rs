@@ -361,9 +361,9 @@ mod tests {
         let mask = create_test_mask(10, 10, "full");
         let contours = marching_squares_contours(&mask, 10, 10);
         // Should have one contour around the border
-        assert!(contours.len() >= 1);
+        assert!(!contours.is_empty());
         // Check that contour is closed and roughly rectangular
-        if contours.len() > 0 {
+        if !contours.is_empty() {
             assert!(contours[0].len() >= 4);
         }
     }
@@ -373,7 +373,7 @@ mod tests {
         let mask = create_test_mask(20, 20, "square");
         let contours = marching_squares_contours(&mask, 20, 20);
         // Should find at least one contour
-        assert!(contours.len() >= 1);
+        assert!(!contours.is_empty());
         // Each contour should have enough points
         for contour in &contours {
             assert!(contour.len() >= 4);
@@ -385,7 +385,53 @@ mod tests {
         let mask = create_test_mask(20, 20, "circle");
         let contours = marching_squares_contours(&mask, 20, 20);
         // Should find at least one contour
-        assert!(contours.len() >= 1);
+        assert!(!contours.is_empty());
+    }
+
+    #[test]
+    fn test_vectorize_kmeans_palette_differs_from_median_cut() {
+        let (w, h) = (32u32, 32u32);
+        let pixels: Vec<RGBA8> = (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                RGBA8::new((x * 8) as u8, (y * 8) as u8, 128, 255)
+            })
+            .collect();
+        let img = ImageData { width: w, height: h, pixels };
+        let palette = |quantizer: Quantizer| {
+            let data = vectorize_with_quantizer(&img, 6, 0.1, 2, false, quantizer).unwrap();
+            let mut colors: Vec<_> = data.curves.iter().map(|c| c.color).collect();
+            colors.push(data.background_color);
+            colors.sort_unstable();
+            colors.dedup();
+            colors
+        };
+
+        let median_cut = palette(Quantizer::MedianCut);
+        let kmeans = palette(Quantizer::KMeans);
+        assert!(median_cut.len() > 1 && kmeans.len() > 1);
+        assert!(kmeans.len() <= 6);
+        assert_ne!(median_cut, kmeans);
+    }
+
+    #[test]
+    fn test_marching_squares_terminates_on_saddle_heavy_mask() {
+        // Single-pixel checkerboard with a diagonal stripe: nearly every cell
+        // is a 5/10 saddle case
+        let (w, h) = (33, 29);
+        let mask: Vec<bool> = (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                (x + y) % 2 == 0 || (x + 2 * y) % 7 == 0
+            })
+            .collect();
+        let contours = marching_squares_contours(&mask, w, h);
+        assert!(!contours.is_empty());
+        let max_len = (w + 2) * (h + 2) * 2;
+        for contour in &contours {
+            assert!(contour.len() <= max_len);
+            assert!(contour.iter().all(|p| (0.0..=w as f64).contains(&p.x) && (0.0..=h as f64).contains(&p.y)));
+        }
     }
 
     #[test]
@@ -393,7 +439,7 @@ mod tests {
         let mask = create_test_mask(20, 20, "horizontal_line");
         let contours = marching_squares_contours(&mask, 20, 20);
         // Should find contours for the line
-        assert!(contours.len() >= 1);
+        assert!(!contours.is_empty());
     }
 
     #[test]
@@ -401,7 +447,7 @@ mod tests {
         let mask = create_test_mask(20, 20, "vertical_line");
         let contours = marching_squares_contours(&mask, 20, 20);
         // Should find contours for the line
-        assert!(contours.len() >= 1);
+        assert!(!contours.is_empty());
     }
 
     #[test]
@@ -409,7 +455,7 @@ mod tests {
         let mask = create_test_mask(10, 10, "checkerboard");
         let contours = marching_squares_contours(&mask, 10, 10);
         // Should find multiple contours
-        assert!(contours.len() > 0);
+        assert!(!contours.is_empty());
     }
 
     // === Vectorize Tests ===
@@ -489,4 +535,62 @@ mod tests {
             assert_eq!(curve.color.3, 255); // All colors should have alpha 255
         }
     }
+
+    fn mask_from_rows(rows: &[&str]) -> (Vec<bool>, usize, usize) {
+        let mask = rows.iter().flat_map(|r| r.chars().map(|c| c == '#')).collect();
+        (mask, rows[0].len(), rows.len())
+    }
+
+    /// Whether segments ab and cd cross at a point interior to both.
+    fn segments_cross(a: &Point, b: &Point, c: &Point, d: &Point) -> bool {
+        let orient = |p: &Point, q: &Point, r: &Point| (q.x - p.x) * (r.y - p.y) - (q.y - p.y) * (r.x - p.x);
+        let (d1, d2) = (orient(c, d, a), orient(c, d, b));
+        let (d3, d4) = (orient(a, b, c), orient(a, b, d));
+        d1 * d2 < 0.0 && d3 * d4 < 0.0
+    }
+
+    fn assert_no_crossings(contours: &[Vec<Point>]) {
+        let segments: Vec<(&Point, &Point)> = contours
+            .iter()
+            .flat_map(|c| (0..c.len()).map(move |i| (&c[i], &c[(i + 1) % c.len()])))
+            .collect();
+        for i in 0..segments.len() {
+            for j in i + 1..segments.len() {
+                let (a, b) = segments[i];
+                let (c, d) = segments[j];
+                assert!(!segments_cross(a, b, c, d), "segments {:?}-{:?} and {:?}-{:?} cross", a, b, c, d);
+            }
+        }
+    }
+
+    #[test]
+    fn test_saddle_policies_on_diagonal_pinch() {
+        // Two blocks touching only at a corner
+        let (mask, w, h) = mask_from_rows(&["##..", "##..", "..##", "..##"]);
+        let policies = [
+            (SaddlePolicy::Disconnect, 2),
+            (SaddlePolicy::Connect, 1),
+            // Half the surrounding block is inside: not a majority
+            (SaddlePolicy::Majority, 2),
+        ];
+        for (policy, expected) in policies {
+            let contours = marching_squares_contours_with_policy(&mask, w, h, policy);
+            assert_eq!(contours.len(), expected, "{:?}", policy);
+            assert_no_crossings(&contours);
+        }
+        assert_eq!(
+            marching_squares_contours(&mask, w, h).len(),
+            marching_squares_contours_with_policy(&mask, w, h, SaddlePolicy::Disconnect).len()
+        );
+
+        // Solid block with a diagonal pair of holes: mostly inside, so the
+        // majority rule joins the inside corners and the holes stay apart
+        let (mask, w, h) = mask_from_rows(&["####", "##.#", "#.##", "####"]);
+        let joined = marching_squares_contours_with_policy(&mask, w, h, SaddlePolicy::Majority);
+        assert_eq!(joined.len(), 3);
+        assert_no_crossings(&joined);
+        let split = marching_squares_contours_with_policy(&mask, w, h, SaddlePolicy::Disconnect);
+        assert_eq!(split.len(), 2);
+        assert_no_crossings(&split);
+    }
 }
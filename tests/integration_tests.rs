@@ -184,10 +184,10 @@ fn test_example_images_exist() {
         let mut count = 0;
 
         for entry in entries {
-            if let Ok(entry) = entry {
-                if entry.path().extension().and_then(|s| s.to_str()) == Some("png") {
-                    count += 1;
-                }
+            if let Ok(entry) = entry
+                && entry.path().extension().and_then(|s| s.to_str()) == Some("png")
+            {
+                count += 1;
             }
         }
 
@@ -257,3 +257,387 @@ fn test_svg_output_format() {
     let _ = fs::remove_file(&test_img);
     let _ = fs::remove_file(&test_svg);
 }
+
+#[test]
+fn test_cli_stdin_to_stdout() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let test_img = PathBuf::from("/tmp/test_cli_stdin.png");
+    create_test_png(&test_img, 40, 40, "circle");
+    let bytes = fs::read(&test_img).expect("Failed to read PNG");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_img2svg"))
+        .args(["--stdin", "--stdout", "-c", "4"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to spawn img2svg");
+    child.stdin.take().unwrap().write_all(&bytes).unwrap();
+    let output = child.wait_with_output().expect("img2svg did not finish");
+
+    assert!(output.status.success());
+    let svg = String::from_utf8(output.stdout).expect("stdout is not UTF-8");
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains("<path"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+
+    let _ = fs::remove_file(&test_img);
+}
+
+#[test]
+fn test_cli_stats_prints_json() {
+    use std::process::Command;
+
+    let test_img = PathBuf::from("/tmp/test_cli_stats.png");
+    let test_svg = PathBuf::from("/tmp/test_cli_stats.svg");
+    create_test_png(&test_img, 40, 40, "circle");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_img2svg"))
+        .args(["-i", test_img.to_str().unwrap(), "-o", test_svg.to_str().unwrap(), "-c", "4", "--stats"])
+        .output()
+        .expect("Failed to run img2svg");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("stdout is not UTF-8");
+    let stats: serde_json::Value = serde_json::from_str(stdout.trim()).expect("stdout is not JSON");
+    assert!(stats["discarded_regions"].is_u64());
+    assert!(stats["discarded_area"].is_number());
+    assert!(stats["kmeans_iterations"].is_u64());
+    assert!(test_svg.exists());
+
+    let _ = fs::remove_file(&test_img);
+    let _ = fs::remove_file(&test_svg);
+}
+
+#[test]
+fn test_cli_rejects_invalid_config_value() {
+    use std::process::Command;
+
+    let test_img = PathBuf::from("/tmp/test_cli_bad_config.png");
+    let test_svg = PathBuf::from("/tmp/test_cli_bad_config.svg");
+    let config = PathBuf::from("/tmp/test_cli_bad_config.toml");
+    create_test_png(&test_img, 40, 40, "circle");
+    fs::write(&config, "[enhanced]\nsnap_grid = 0.0\n").unwrap();
+    let _ = fs::remove_file(&test_svg);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_img2svg"))
+        .args(["-i", test_img.to_str().unwrap(), "-o", test_svg.to_str().unwrap()])
+        .args(["--config", config.to_str().unwrap()])
+        .output()
+        .expect("Failed to run img2svg");
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("snap_grid must be a positive number"), "{}", stderr);
+    assert!(!test_svg.exists());
+
+    let _ = fs::remove_file(&test_img);
+    let _ = fs::remove_file(&config);
+}
+
+#[test]
+fn test_color_separations_one_file_per_color() {
+    use img2svg::image_processor::ImageData;
+    use img2svg::{vectorize_enhanced, write_color_separations, write_color_separations_with_options, EnhancedOptions};
+    use rgb::RGBA8;
+
+    // Red, green and blue blocks on a white substrate
+    let (w, h) = (60u32, 20u32);
+    let mut pixels = Vec::new();
+    for y in 0..h {
+        for x in 0..w {
+            let inside = (4..16).contains(&y) && x % 20 >= 4 && x % 20 < 16;
+            pixels.push(match (inside, x / 20) {
+                (false, _) => RGBA8::new(255, 255, 255, 255),
+                (true, 0) => RGBA8::new(255, 0, 0, 255),
+                (true, 1) => RGBA8::new(0, 255, 0, 255),
+                _ => RGBA8::new(0, 0, 255, 255),
+            });
+        }
+    }
+    let img = ImageData { width: w, height: h, pixels };
+    let options = EnhancedOptions { num_colors: 4, preprocess: false, ..Default::default() };
+    let data = vectorize_enhanced(&img, &options).unwrap();
+
+    let dir = PathBuf::from("/tmp/test_color_separations");
+    let _ = fs::remove_dir_all(&dir);
+    let written = write_color_separations(&data, &dir, "art").unwrap();
+    assert_eq!(written.len(), 3);
+
+    for path in &written {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.starts_with("art_") && name.ends_with(".svg"), "{}", name);
+        let svg = fs::read_to_string(path).unwrap();
+        assert!(!svg.contains("<rect"), "separation has a background: {}", name);
+        let fills: std::collections::HashSet<&str> = svg
+            .split("fill=\"")
+            .skip(1)
+            .map(|s| &s[..7])
+            .collect();
+        assert_eq!(fills.len(), 1, "{} has fills {:?}", name, fills);
+        assert!(name.contains(&fills.iter().next().unwrap()[1..]));
+    }
+
+    // Output settings carry over to every plate
+    let options = EnhancedOptions { emit_area_attr: true, ..options };
+    let written = write_color_separations_with_options(&data, &dir, "art", &options).unwrap();
+    assert_eq!(written.len(), 3);
+    for path in &written {
+        let svg = fs::read_to_string(path).unwrap();
+        assert!(svg.contains("data-area=\"144\""), "{}", svg);
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_write_enhanced_svgz_round_trip() {
+    use flate2::read::GzDecoder;
+    use img2svg::{vectorize_enhanced, write_enhanced_svgz, EnhancedOptions};
+    use std::io::Read;
+
+    let test_img = PathBuf::from("/tmp/test_svgz_input.png");
+    let test_svgz = PathBuf::from("/tmp/test_svgz_output.svgz");
+    create_test_png(&test_img, 40, 40, "circle");
+
+    let image_data = load_image(&test_img).expect("Failed to load image");
+    let options = EnhancedOptions { num_colors: 4, ..Default::default() };
+    let data = vectorize_enhanced(&image_data, &options).expect("Failed to vectorize");
+    write_enhanced_svgz(&data, &test_svgz).expect("Failed to write SVGZ");
+
+    let compressed = fs::read(&test_svgz).expect("Failed to read SVGZ");
+    assert_eq!(&compressed[..2], &[0x1f, 0x8b], "not gzip data");
+    let mut svg = String::new();
+    GzDecoder::new(&compressed[..]).read_to_string(&mut svg).expect("Failed to decompress");
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains("</svg>"));
+
+    let _ = fs::remove_file(&test_img);
+    let _ = fs::remove_file(&test_svgz);
+}
+
+#[test]
+fn test_write_enhanced_svg_to_writer_sinks() {
+    use flate2::read::GzDecoder;
+    use img2svg::{
+        generate_enhanced_svg, vectorize_enhanced, write_enhanced_svg, write_enhanced_svg_to_writer, EnhancedOptions,
+    };
+    use std::io::Read;
+
+    let test_img = PathBuf::from("/tmp/test_writer_input.png");
+    let test_svg = PathBuf::from("/tmp/test_writer_output.svg");
+    create_test_png(&test_img, 40, 40, "circle");
+
+    let image_data = load_image(&test_img).expect("Failed to load image");
+    let options = EnhancedOptions { num_colors: 4, ..Default::default() };
+    let data = vectorize_enhanced(&image_data, &options).expect("Failed to vectorize");
+
+    let mut buffer = Vec::new();
+    write_enhanced_svg_to_writer(&data, &mut buffer).expect("Failed to write to buffer");
+    assert_eq!(String::from_utf8(buffer.clone()).unwrap(), generate_enhanced_svg(&data));
+
+    write_enhanced_svg(&data, &test_svg).expect("Failed to write SVG");
+    assert_eq!(fs::read(&test_svg).unwrap(), buffer);
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    write_enhanced_svg_to_writer(&data, &mut encoder).expect("Failed to write to encoder");
+    let compressed = encoder.finish().unwrap();
+    let mut svg = String::new();
+    GzDecoder::new(&compressed[..]).read_to_string(&mut svg).expect("Failed to decompress");
+    assert_eq!(svg.as_bytes(), &buffer[..]);
+
+    let _ = fs::remove_file(&test_img);
+    let _ = fs::remove_file(&test_svg);
+}
+
+#[test]
+fn test_convert_region_uses_crop_local_viewbox() {
+    use img2svg::{convert_region, ConversionOptions};
+
+    let test_img = PathBuf::from("/tmp/test_region_input.png");
+    let test_svg = PathBuf::from("/tmp/test_region_output.svg");
+    create_test_png(&test_img, 80, 60, "circle");
+
+    let options = ConversionOptions { num_colors: 4, ..Default::default() };
+    convert_region(&test_img, &test_svg, (20, 10, 40, 30), &options).expect("Failed to convert region");
+
+    let svg = fs::read_to_string(&test_svg).expect("Failed to read SVG");
+    assert!(svg.contains(r#"viewBox="0 0 40 30""#));
+    assert!(svg.contains(r#"width="40""#));
+    assert!(svg.contains("<path"));
+
+    // A rectangle reaching past the image is an error
+    assert!(convert_region(&test_img, &test_svg, (60, 0, 40, 30), &options).is_err());
+
+    let _ = fs::remove_file(&test_img);
+    let _ = fs::remove_file(&test_svg);
+}
+
+#[test]
+fn test_single_thread_matches_multi_thread() {
+    use img2svg::image_processor::ImageData;
+    use img2svg::{generate_enhanced_svg, set_thread_count, vectorize_enhanced, EnhancedOptions};
+    use rgb::RGBA8;
+
+    // Three flat colors with distinct areas: k-means++ finds them exactly
+    let (w, h) = (48u32, 32u32);
+    let pixels = (0..w * h)
+        .map(|i| {
+            let (x, y) = (i % w, i / w);
+            if (6..26).contains(&y) && (4..20).contains(&x) {
+                RGBA8::new(220, 30, 30, 255)
+            } else if x.abs_diff(34).pow(2) + y.abs_diff(16).pow(2) < 64 {
+                RGBA8::new(20, 40, 200, 255)
+            } else {
+                RGBA8::new(255, 255, 255, 255)
+            }
+        })
+        .collect();
+    let img = ImageData { width: w, height: h, pixels };
+    let options = EnhancedOptions { num_colors: 3, preprocess: false, ..Default::default() };
+
+    let multi = generate_enhanced_svg(&vectorize_enhanced(&img, &options).unwrap());
+    set_thread_count(1).unwrap();
+    let single = vectorize_enhanced(&img, &options).map(|d| generate_enhanced_svg(&d));
+    set_thread_count(0).unwrap();
+
+    let single = single.expect("single-threaded conversion failed");
+    assert!(single.contains("<path"));
+    assert_eq!(single, multi);
+}
+
+#[test]
+fn test_indexed_png_transparency_becomes_holes() {
+    use img2svg::{extract_polygons, generate_enhanced_svg, load_image_from_bytes, vectorize_enhanced, EnhancedOptions};
+
+    // Palette: 0 white, 1 red, 2 black made fully transparent by tRNS
+    let (w, h) = (40u32, 40u32);
+    let indices: Vec<u8> = (0..w * h)
+        .map(|i| {
+            let (x, y) = (i % w, i / w);
+            if (16..24).contains(&x) && (16..24).contains(&y) {
+                2
+            } else if (8..32).contains(&x) && (8..32).contains(&y) {
+                1
+            } else {
+                0
+            }
+        })
+        .collect();
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut bytes, w, h);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(vec![255, 255, 255, 200, 0, 0, 0, 0, 0]);
+        encoder.set_trns(vec![255, 255, 0]);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&indices).unwrap();
+    }
+
+    let img = load_image_from_bytes(&bytes).unwrap();
+    assert_eq!(img.pixels[20 * w as usize + 20].a, 0);
+
+    let options = EnhancedOptions { num_colors: 3, preprocess: false, ..Default::default() };
+    let data = vectorize_enhanced(&img, &options).unwrap();
+    assert!(data.paths.iter().all(|p| p.color == (200, 0, 0, 255)));
+    assert!(!generate_enhanced_svg(&data).contains("#000000"));
+
+    let polygons = extract_polygons(&img, &options).unwrap();
+    assert_eq!(polygons.len(), 1);
+    assert_eq!(polygons[0].holes.len(), 1);
+}
+
+#[test]
+fn test_batch_convert_parallel_collects_errors() {
+    use img2svg::{batch_convert_parallel, EnhancedOptions};
+
+    let input_dir = std::env::temp_dir().join("img2svg_batch_in");
+    let output_dir = std::env::temp_dir().join("img2svg_batch_out");
+    let _ = fs::remove_dir_all(&input_dir);
+    let _ = fs::remove_dir_all(&output_dir);
+    fs::create_dir_all(&input_dir).unwrap();
+    for (i, pattern) in ["circle", "checkerboard", "solid", "circle", "checkerboard"].iter().enumerate() {
+        create_test_png(&input_dir.join(format!("img{}.png", i)), 24, 24, pattern);
+    }
+    fs::write(input_dir.join("broken.png"), b"not a png").unwrap();
+    fs::write(input_dir.join("notes.txt"), b"skipped").unwrap();
+
+    let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+    let report = batch_convert_parallel(&input_dir, &output_dir, &options, 2).unwrap();
+
+    assert_eq!(report.converted.len(), 5);
+    assert_eq!(report.failed.len(), 1);
+    assert!(report.failed[0].0.ends_with("broken.png"));
+    for (_, svg) in &report.converted {
+        assert!(fs::read_to_string(svg).unwrap().contains("<svg"));
+    }
+
+    let _ = fs::remove_dir_all(&input_dir);
+    let _ = fs::remove_dir_all(&output_dir);
+}
+
+#[test]
+fn test_convert_all_reports_each_file() {
+    use img2svg::{convert_all, EnhancedOptions};
+
+    let input_dir = std::env::temp_dir().join("img2svg_convert_all_in");
+    let output_dir = std::env::temp_dir().join("img2svg_convert_all_out");
+    let _ = fs::remove_dir_all(&input_dir);
+    let _ = fs::remove_dir_all(&output_dir);
+    fs::create_dir_all(&input_dir).unwrap();
+    let inputs: Vec<PathBuf> = ["circle", "checkerboard"]
+        .iter()
+        .map(|pattern| {
+            let path = input_dir.join(format!("{}.png", pattern));
+            create_test_png(&path, 24, 24, pattern);
+            path
+        })
+        .collect();
+
+    let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+    let results = convert_all(&inputs, &output_dir, &options);
+
+    assert_eq!(results.len(), 2);
+    for ((input, result), expected) in results.iter().zip(&inputs) {
+        assert_eq!(input, expected);
+        assert!(result.is_ok(), "{:?}", result);
+    }
+    assert!(output_dir.join("circle.svg").exists());
+    assert!(output_dir.join("checkerboard.svg").exists());
+
+    let _ = fs::remove_dir_all(&input_dir);
+    let _ = fs::remove_dir_all(&output_dir);
+}
+
+#[test]
+fn test_half_alpha_square_keeps_fill_opacity() {
+    use img2svg::{generate_enhanced_svg, vectorize_enhanced, EnhancedOptions};
+    use img2svg::svg_generator::generate_svg_string;
+
+    let path = std::env::temp_dir().join("img2svg_half_alpha.png");
+    let mut img = image::RgbaImage::from_pixel(40, 40, image::Rgba([0, 0, 0, 0]));
+    for y in 10..30 {
+        for x in 10..30 {
+            img.put_pixel(x, y, image::Rgba([200, 0, 0, 128]));
+        }
+    }
+    img.save(&path).unwrap();
+    let image_data = load_image(&path).unwrap();
+
+    let options = EnhancedOptions { num_colors: 2, preprocess: false, ..Default::default() };
+    let data = vectorize_enhanced(&image_data, &options).unwrap();
+    assert_eq!(data.background_color.3, 0);
+    let svg = generate_enhanced_svg(&data);
+    assert!(!svg.contains("<rect"), "transparent background painted: {}", svg);
+    assert!(svg.contains("fill=\"#c80000\" fill-opacity=\"0.502\""), "{}", svg);
+
+    let legacy = vectorize(&image_data, 2, 0.1, 0, false).unwrap();
+    let svg = generate_svg_string(&legacy);
+    assert!(!svg.contains("<rect"), "{}", svg);
+    assert!(svg.contains("fill-opacity=\"0.502\""), "{}", svg);
+
+    let _ = fs::remove_file(&path);
+}